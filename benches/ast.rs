@@ -0,0 +1,93 @@
+//! Compare the owned syntax tree against the arena-allocated one on large expressions.
+
+use std::hint::black_box;
+
+use criterion::{Criterion, criterion_group, criterion_main};
+use ecc::ast::arena::{ArenaProgram, ArenaStatement, ExprArena, ExprId, ExprNode};
+use ecc::ast::{Expr, Program, Statement};
+use ecc::{lexer, parser};
+
+/// Build a program returning one long expression with the given number of terms.
+fn large_program(terms: usize) -> Program {
+    let operators = ["+", "*", "-", "/", "%"];
+    let mut source = String::from("int main(void) { return 1");
+    for i in 1..terms {
+        let operator = operators[i % operators.len()];
+        source.push_str(&format!(" {operator} -({i} + ~2)"));
+    }
+    source.push_str("; }");
+
+    let tokens = lexer::tokenize(&source).unwrap();
+    parser::parse_token_stream(tokens).unwrap()
+}
+
+fn count_owned(expr: &Expr) -> usize {
+    match expr {
+        Expr::Integer { .. } | Expr::Boolean { .. } => 1,
+        Expr::Unary { operand, .. } => 1 + count_owned(operand),
+        Expr::Binary { left, right, .. } => 1 + count_owned(left) + count_owned(right),
+        Expr::Generic {
+            controlling,
+            associations,
+            ..
+        } => {
+            let associations: usize = associations
+                .iter()
+                .map(|association| count_owned(&association.expr))
+                .sum();
+            1 + count_owned(controlling) + associations
+        }
+    }
+}
+
+fn count_arena(exprs: &ExprArena, id: ExprId) -> usize {
+    match exprs[id] {
+        ExprNode::Integer { .. } | ExprNode::Boolean { .. } => 1,
+        ExprNode::Unary { operand, .. } => 1 + count_arena(exprs, operand),
+        ExprNode::Binary { left, right, .. } => {
+            1 + count_arena(exprs, left) + count_arena(exprs, right)
+        }
+        ExprNode::Generic {
+            controlling,
+            associations,
+            ..
+        } => {
+            let associations: usize = exprs[associations]
+                .iter()
+                .map(|association| count_arena(exprs, association.expr))
+                .sum();
+            1 + count_arena(exprs, controlling) + associations
+        }
+    }
+}
+
+fn bench_ast(c: &mut Criterion) {
+    let owned = large_program(5_000);
+    let arena = ArenaProgram::from_program(&owned);
+
+    let Statement::Return(Some(owned_root)) = &owned.function.body[0] else {
+        unreachable!()
+    };
+    let ArenaStatement::Return(Some(arena_root)) = arena.function.body[0] else {
+        unreachable!()
+    };
+
+    c.bench_function("owned clone and drop", |b| {
+        b.iter(|| black_box(&owned).clone())
+    });
+    c.bench_function("arena clone and drop", |b| {
+        b.iter(|| black_box(&arena).clone())
+    });
+    c.bench_function("owned walk", |b| {
+        b.iter(|| count_owned(black_box(owned_root)))
+    });
+    c.bench_function("arena walk", |b| {
+        b.iter(|| count_arena(&arena.exprs, black_box(arena_root)))
+    });
+    c.bench_function("arena from owned", |b| {
+        b.iter(|| ArenaProgram::from_program(black_box(&owned)))
+    });
+}
+
+criterion_group!(benches, bench_ast);
+criterion_main!(benches);
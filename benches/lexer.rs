@@ -0,0 +1,36 @@
+//! Measure lexing throughput on a multi-thousand-line file.
+
+use std::hint::black_box;
+
+use criterion::{Criterion, Throughput, criterion_group, criterion_main};
+use ecc::lexer::Lexer;
+
+/// Build a source file with the given number of lines.
+///
+/// Only the first function makes a valid program, but the lexer does not care about that.
+fn large_source(lines: usize) -> String {
+    let mut source = String::new();
+    for i in 0..lines {
+        match i % 4 {
+            0 => source.push_str(&format!("int function_{i}(void) {{\n")),
+            1 => source.push_str(&format!("    // Compute value number {i}.\n")),
+            2 => source.push_str(&format!("    return -({i} + 42) * ~7 % 3 / !0;\n")),
+            _ => source.push_str("}\n"),
+        }
+    }
+    source
+}
+
+fn bench_lexer(c: &mut Criterion) {
+    let source = large_source(10_000);
+
+    let mut group = c.benchmark_group("lexer");
+    group.throughput(Throughput::Bytes(source.len() as u64));
+    group.bench_function("10000 lines", |b| {
+        b.iter(|| Lexer::new(black_box(&source)).count())
+    });
+    group.finish();
+}
+
+criterion_group!(benches, bench_lexer);
+criterion_main!(benches);
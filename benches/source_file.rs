@@ -0,0 +1,63 @@
+//! Measure how long it takes to get a large source file from disk, reading it into a string
+//! compared to mapping it into memory, on its own and together with lexing it.
+
+use std::hint::black_box;
+use std::path::Path;
+
+use criterion::{Criterion, Throughput, criterion_group, criterion_main};
+use ecc::lexer::Lexer;
+use ecc::source_file::SourceFile;
+
+/// Write a generated source file with the given number of lines, like a big lookup table.
+fn write_large_source(path: &Path, lines: usize) -> u64 {
+    let mut source = String::from("int main(void) {\n");
+    for i in 0..lines {
+        source.push_str(&format!("    // Entry number {i} of the table.\n"));
+    }
+    source.push_str("    return 0;\n}\n");
+
+    std::fs::write(path, &source).unwrap();
+    source.len() as u64
+}
+
+/// Open the benchmark's source file.
+fn open(path: &Path) -> SourceFile {
+    // SAFETY: The benchmark is the only thing that uses the file, and it is not changed until it
+    // is removed at the end.
+    unsafe { SourceFile::open(path) }.unwrap()
+}
+
+fn bench_source_file(c: &mut Criterion) {
+    let path = std::env::temp_dir().join("ecc_bench_source_file.c");
+    let size = write_large_source(&path, 500_000);
+    assert!(open(&path).is_mapped());
+
+    let mut group = c.benchmark_group("open");
+    group.throughput(Throughput::Bytes(size));
+    group.bench_function("read", |b| {
+        b.iter(|| std::fs::read_to_string(black_box(&path)).unwrap().len())
+    });
+    group.bench_function("mapped", |b| b.iter(|| open(black_box(&path)).len()));
+    group.finish();
+
+    let mut group = c.benchmark_group("open and lex");
+    group.throughput(Throughput::Bytes(size));
+    group.bench_function("read", |b| {
+        b.iter(|| {
+            let source = std::fs::read_to_string(black_box(&path)).unwrap();
+            Lexer::new(&source).count()
+        })
+    });
+    group.bench_function("mapped", |b| {
+        b.iter(|| {
+            let source = open(black_box(&path));
+            Lexer::new(&source).count()
+        })
+    });
+    group.finish();
+
+    std::fs::remove_file(path).unwrap();
+}
+
+criterion_group!(benches, bench_source_file);
+criterion_main!(benches);
@@ -0,0 +1,21 @@
+//! Feed arbitrary input to the lexer, and render every error it reports.
+
+#![no_main]
+
+use ecc::diagnostics::{self, Diagnostic};
+use ecc::lexer;
+use libfuzzer_sys::fuzz_target;
+
+fuzz_target!(|data: &[u8]| {
+    let Ok(source) = std::str::from_utf8(data) else {
+        return;
+    };
+
+    if let Err(errors) = lexer::tokenize(source) {
+        for e in errors {
+            let diagnostic = Diagnostic::from(e);
+            diagnostics::render(&diagnostic, "fuzz.c", source);
+            diagnostics::to_json(&diagnostic, "fuzz.c", source);
+        }
+    }
+});
@@ -0,0 +1,22 @@
+//! Feed arbitrary input through the lexer and parser, and render the error if there is one.
+
+#![no_main]
+
+use ecc::diagnostics;
+use ecc::{lexer, parser};
+use libfuzzer_sys::fuzz_target;
+
+fuzz_target!(|data: &[u8]| {
+    let Ok(source) = std::str::from_utf8(data) else {
+        return;
+    };
+
+    let Ok(tokens) = lexer::tokenize(source) else {
+        return;
+    };
+
+    if let Err(diagnostic) = parser::parse_token_stream(tokens) {
+        diagnostics::render(&diagnostic, "fuzz.c", source);
+        diagnostics::to_json(&diagnostic, "fuzz.c", source);
+    }
+});
@@ -0,0 +1,47 @@
+/*
+ * C bindings for ecc, the Eggs C Compiler.
+ *
+ * Link against the `ecc` shared library built by `cargo build --release`
+ * (`target/release/libecc.so`).
+ */
+
+#ifndef ECC_H
+#define ECC_H
+
+#ifdef __cplusplus
+extern "C" {
+#endif
+
+/* The source compiled successfully. */
+#define ECC_OK 0
+
+/* The source has errors, which are described by the diagnostics. */
+#define ECC_COMPILE_ERROR 1
+
+/* An argument was null or the source was not valid UTF-8. */
+#define ECC_INVALID_ARGUMENT 2
+
+/* The compiler itself crashed. This is always a bug in ecc. */
+#define ECC_INTERNAL_ERROR 3
+
+/*
+ * Compile a NUL-terminated string of C source code to x86-64 assembly.
+ *
+ * On success, `*assembly_out` is set to the generated assembly. Whether or
+ * not compilation succeeded, `*diagnostics_out` is set to every diagnostic
+ * produced, one JSON object per line. Either out-parameter may be null if the
+ * caller is not interested in it. Anything not set is set to null instead.
+ *
+ * Returns one of the ECC_* status codes. Every string returned must be
+ * released with `ecc_string_free`.
+ */
+int ecc_compile(const char *source, char **assembly_out, char **diagnostics_out);
+
+/* Release a string returned by ecc. Passing null does nothing. */
+void ecc_string_free(char *string);
+
+#ifdef __cplusplus
+}
+#endif
+
+#endif /* ECC_H */
@@ -1,9 +1,21 @@
+pub mod arena;
+pub mod dot;
+pub mod dump;
+pub mod pretty;
+pub mod visit;
+
+pub use visit::{Visitor, VisitorMut};
+
+use crate::span::Span;
+use crate::types::Type;
+
 /// A program.
 ///
 /// This node represents a C program. For now, a program consists of a single function declaration.
 /// It can technically be called whatever, but if the name of the function is not `main`, the
 /// linker will yell at you.
-#[derive(Clone, Debug)]
+#[derive(Clone, PartialEq, Debug)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct Program {
     /// The single function of the program.
     pub function: Function,
@@ -12,27 +24,184 @@ pub struct Program {
 /// A function node.
 ///
 /// Functions act as reusable blocks of code that can be parameterized. For now, a function
-/// consists only of a name and a body. The return type is assumed to be `int` and the parameter
-/// list is assumed to be `void`. The name can be any identifier, but the linker will generate an
-/// error if there is no `main` function defined.
-#[derive(Clone, Debug)]
+/// consists only of a return type, a name and a body. The return type is `int` or `void`, and the
+/// parameter list is assumed to be `void`. The name can be any identifier, but the linker will
+/// generate an error if there is no `main` function defined.
+#[derive(Clone, PartialEq, Debug)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct Function {
     /// The function's name.
     pub name: String,
 
+    /// Where the function's name appears in the source.
+    pub name_span: Span,
+
+    /// Whether the function is declared `static`, so that it cannot be seen from other files.
+    pub is_static: bool,
+
+    /// Whether the function is declared `inline`.
+    pub inline: bool,
+
+    /// Whether the function is declared never to return, with `_Noreturn` or
+    /// `__attribute__((noreturn))`.
+    pub noreturn: bool,
+
+    /// The alignment of the function's code in bytes, if `__attribute__((aligned))` asks for one.
+    pub aligned: Option<u32>,
+
+    /// Whether the function is declared with `__attribute__((unused))`, which says that it is
+    /// fine for it never to be used.
+    pub unused: bool,
+
+    /// The attributes the function is declared with that are not supported, and have no effect.
+    pub ignored_attributes: Vec<Attribute>,
+
+    /// The type of the value the function returns.
+    pub return_type: Type,
+
+    /// The keyword the return type is spelled with, if it is `bool`.
+    pub bool_keyword: BoolKeyword,
+
     /// The body of the function.
     pub body: Vec<Statement>,
 }
 
+impl Function {
+    /// The specifiers the function is declared with, spelled the way C spells them and in the
+    /// order they are conventionally written.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use ecc::{lexer, parser};
+    ///
+    /// let tokens = lexer::tokenize("inline static int f(void) { return 0; }").unwrap();
+    /// let function = parser::parse_function(tokens).unwrap();
+    ///
+    /// assert_eq!(function.specifiers().collect::<Vec<_>>(), ["static", "inline"]);
+    /// ```
+    pub fn specifiers(&self) -> impl Iterator<Item = &'static str> {
+        [
+            (self.is_static, "static"),
+            (self.inline, "inline"),
+            (self.noreturn, "_Noreturn"),
+        ]
+        .into_iter()
+        .filter_map(|(declared, spelling)| declared.then_some(spelling))
+    }
+
+    /// The supported attributes the function is declared with, other than `noreturn`, which is
+    /// one of its [`specifiers`](Self::specifiers).
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use ecc::{lexer, parser};
+    ///
+    /// let source = "__attribute__((unused, aligned(32))) int f(void) { return 0; }";
+    /// let function = parser::parse_function(lexer::tokenize(source).unwrap()).unwrap();
+    ///
+    /// assert_eq!(function.attributes().collect::<Vec<_>>(), ["aligned(32)", "unused"]);
+    /// ```
+    pub fn attributes(&self) -> impl Iterator<Item = String> {
+        let aligned = self.aligned.map(|align| format!("aligned({align})"));
+        let unused = self.unused.then(|| "unused".to_owned());
+        aligned.into_iter().chain(unused)
+    }
+
+    /// Whether running the function can reach its closing brace without returning.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use ecc::{lexer, parser};
+    ///
+    /// let empty = parser::parse_function(lexer::tokenize("int f(void) {}").unwrap()).unwrap();
+    /// let full = parser::parse_function(lexer::tokenize("int f(void) { return 1; }").unwrap());
+    ///
+    /// assert!(empty.can_fall_off_end());
+    /// assert!(!full.unwrap().can_fall_off_end());
+    /// ```
+    pub fn can_fall_off_end(&self) -> bool {
+        // Nothing branches yet, so the end is reached unless there is a return statement somewhere.
+        !self
+            .body
+            .iter()
+            .any(|statement| matches!(statement, Statement::Return(_)))
+    }
+}
+
+/// The keyword a `bool` type is spelled with.
+///
+/// `bool` and `_Bool` name the same type, so this only matters for printing a tree back the way it
+/// was written.
+#[derive(Clone, Copy, PartialEq, Eq, Default, Debug)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum BoolKeyword {
+    /// `bool`, which is a keyword since C23.
+    #[default]
+    Bool,
+
+    /// `_Bool`, the keyword C99 introduced.
+    UnderscoreBool,
+}
+
+impl BoolKeyword {
+    /// Spell a type, using this keyword if it is `bool`.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use ecc::ast::BoolKeyword;
+    /// use ecc::types::Type;
+    ///
+    /// assert_eq!(BoolKeyword::UnderscoreBool.spell(&Type::Bool), "_Bool");
+    /// assert_eq!(BoolKeyword::UnderscoreBool.spell(&Type::Int), "int");
+    /// ```
+    pub fn spell(self, ty: &Type) -> String {
+        match (ty, self) {
+            (Type::Bool, Self::UnderscoreBool) => "_Bool".to_owned(),
+            _ => ty.to_string(),
+        }
+    }
+}
+
+/// A GNU attribute, written inside `__attribute__((...))`.
+#[derive(Clone, PartialEq, Eq, Debug)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Attribute {
+    /// The attribute's name, as it is spelled.
+    pub name: String,
+
+    /// The attribute's arguments, if it has any, spelled the way they were written but with any
+    /// whitespace between two tokens shrunk to a single space.
+    pub arguments: Option<String>,
+
+    /// Where the attribute appears in the source, arguments and all.
+    pub span: Span,
+}
+
+impl std::fmt::Display for Attribute {
+    /// Write the attribute the way it would appear inside `__attribute__((...))`.
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match &self.arguments {
+            Some(arguments) => write!(f, "{}({arguments})", self.name),
+            None => write!(f, "{}", self.name),
+        }
+    }
+}
+
 /// An operator that can appear in a unary expression.
-#[derive(Clone, Copy, Debug)]
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub enum UnaryOp {
     Compliment,
     NegateArith,
     NegateLogical,
 }
 
-#[derive(Clone, Copy, Debug)]
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub enum BinaryOp {
     Plus,
     Minus,
@@ -45,15 +214,20 @@ pub enum BinaryOp {
 ///
 /// Expressions are any part of the source code which can evaluate to a value. For example,
 /// literals like integers, floating point numbers, or strings.
-#[derive(Clone, Debug)]
+#[derive(Clone, PartialEq, Debug)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub enum Expr {
     /// An integer literal.
-    Integer(i32),
+    Integer { value: i32, span: Span },
+
+    /// A `true` or `false` literal.
+    Boolean { value: bool, span: Span },
 
     /// A unary expression.
     Unary {
         operator: UnaryOp,
         operand: Box<Expr>,
+        span: Span,
     },
 
     /// A binary expression.
@@ -61,14 +235,63 @@ pub enum Expr {
         operator: BinaryOp,
         left: Box<Expr>,
         right: Box<Expr>,
+        span: Span,
     },
+
+    /// A `_Generic` selection, which stands for the association that matches the type of its
+    /// controlling expression. Which one that is, is up to [`crate::sema::select`].
+    Generic {
+        controlling: Box<Expr>,
+        associations: Vec<Association>,
+        span: Span,
+    },
+}
+
+/// One association of a `_Generic` selection, like `int: 1` or `default: 0`.
+#[derive(Clone, PartialEq, Debug)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Association {
+    /// The type the association is for, or `None` for `default`.
+    pub ty: Option<Type>,
+
+    /// The keyword the type is spelled with, if it is `bool`.
+    pub bool_keyword: BoolKeyword,
+
+    /// Where the type name or `default` is.
+    pub type_span: Span,
+
+    /// The expression the selection stands for if the association is chosen.
+    pub expr: Expr,
+}
+
+impl Expr {
+    /// Where the whole expression appears in the source.
+    ///
+    /// Parentheses around an expression are not part of its span, since the tree does not keep
+    /// them.
+    pub fn span(&self) -> Span {
+        match self {
+            Self::Integer { span, .. }
+            | Self::Boolean { span, .. }
+            | Self::Unary { span, .. }
+            | Self::Binary { span, .. }
+            | Self::Generic { span, .. } => *span,
+        }
+    }
 }
 
 /// A statement.
 ///
 /// As opposed to expressions, statements *do* something. They are like commands.
-#[derive(Clone, Debug)]
+#[derive(Clone, PartialEq, Debug)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub enum Statement {
-    /// A return statement.
-    Return(Expr),
+    /// A return statement, with the value to return unless the function returns `void`.
+    Return(Option<Expr>),
+
+    /// A basic `asm("...")` statement, whose text is copied into the generated assembly as is.
+    ///
+    /// The text is what the string literal means, with its escape sequences already replaced by
+    /// the characters they stand for.
+    Asm { template: String, span: Span },
 }
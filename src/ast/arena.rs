@@ -0,0 +1,391 @@
+//! An arena-allocated form of the syntax tree.
+//!
+//! The owned tree in [`crate::ast`] puts every subexpression in its own [`Box`], so cloning or
+//! dropping a large expression means one allocator call per node, and walking it chases pointers
+//! all over the heap. The arena form stores every expression of a program in a single [`Vec`] and
+//! refers to subexpressions by [`ExprId`] instead. Cloning the whole program is then a handful of
+//! copies, and nodes that were built together sit next to each other in memory.
+//!
+//! The arena form is built from the owned tree with [`ArenaProgram::from_program`] and can be
+//! turned back into it with [`ArenaProgram::to_program`], so passes can pick whichever form suits
+//! them. `cargo bench --bench ast` compares the two on large inputs.
+
+use std::ops::Index;
+
+use crate::span::Span;
+use crate::types::Type;
+
+use super::{
+    Association, Attribute, BinaryOp, BoolKeyword, Expr, Function, Program, Statement, UnaryOp,
+};
+
+/// A reference to an expression stored in an [`ExprArena`].
+///
+/// An id is only meaningful for the arena that handed it out.
+#[derive(Clone, Copy, PartialEq, Eq, Hash, Debug)]
+pub struct ExprId(u32);
+
+/// An expression whose subexpressions live in an [`ExprArena`].
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum ExprNode {
+    /// An integer literal.
+    Integer { value: i32, span: Span },
+
+    /// A `true` or `false` literal.
+    Boolean { value: bool, span: Span },
+
+    /// A unary expression.
+    Unary {
+        operator: UnaryOp,
+        operand: ExprId,
+        span: Span,
+    },
+
+    /// A binary expression.
+    Binary {
+        operator: BinaryOp,
+        left: ExprId,
+        right: ExprId,
+        span: Span,
+    },
+
+    /// A `_Generic` selection.
+    Generic {
+        controlling: ExprId,
+        associations: AssociationIds,
+        span: Span,
+    },
+}
+
+/// The associations of a `_Generic` selection, which sit next to each other in an
+/// [`ExprArena`], and are looked up by indexing it.
+#[derive(Clone, Copy, PartialEq, Eq, Hash, Debug)]
+pub struct AssociationIds {
+    start: u32,
+    len: u32,
+}
+
+/// An association of a `_Generic` selection whose expression lives in an [`ExprArena`].
+#[derive(Clone, PartialEq, Eq, Debug)]
+pub struct ArenaAssociation {
+    /// The type the association is for, or `None` for `default`.
+    pub ty: Option<Type>,
+
+    /// The keyword the type is spelled with, if it is `bool`.
+    pub bool_keyword: BoolKeyword,
+
+    /// Where the type name or `default` is.
+    pub type_span: Span,
+
+    /// The expression the selection stands for if the association is chosen.
+    pub expr: ExprId,
+}
+
+/// Storage for every expression in a program.
+///
+/// Nodes are only ever added, so an [`ExprId`] stays valid for as long as the arena does. Since
+/// children have to exist before their parents can refer to them, a node's children always come
+/// before it.
+#[derive(Clone, PartialEq, Eq, Default, Debug)]
+pub struct ExprArena {
+    nodes: Vec<ExprNode>,
+
+    /// The associations of every `_Generic` selection, each selection's next to each other.
+    associations: Vec<ArenaAssociation>,
+}
+
+impl ExprArena {
+    /// Create an empty arena.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Add a node to the arena, returning its id.
+    pub fn alloc(&mut self, node: ExprNode) -> ExprId {
+        let id = ExprId(self.nodes.len() as u32);
+        self.nodes.push(node);
+        id
+    }
+
+    /// The number of nodes in the arena.
+    pub fn len(&self) -> usize {
+        self.nodes.len()
+    }
+
+    /// Return true if the arena holds no nodes.
+    pub fn is_empty(&self) -> bool {
+        self.nodes.is_empty()
+    }
+
+    /// Copy an owned expression into the arena, returning the id of its root.
+    pub fn alloc_expr(&mut self, expr: &Expr) -> ExprId {
+        let node = match expr {
+            Expr::Integer { value, span } => ExprNode::Integer {
+                value: *value,
+                span: *span,
+            },
+            Expr::Boolean { value, span } => ExprNode::Boolean {
+                value: *value,
+                span: *span,
+            },
+            Expr::Unary {
+                operator,
+                operand,
+                span,
+            } => ExprNode::Unary {
+                operator: *operator,
+                operand: self.alloc_expr(operand),
+                span: *span,
+            },
+            Expr::Binary {
+                operator,
+                left,
+                right,
+                span,
+            } => ExprNode::Binary {
+                operator: *operator,
+                left: self.alloc_expr(left),
+                right: self.alloc_expr(right),
+                span: *span,
+            },
+            Expr::Generic {
+                controlling,
+                associations,
+                span,
+            } => {
+                let controlling = self.alloc_expr(controlling);
+                let associations: Vec<_> = associations
+                    .iter()
+                    .map(|association| ArenaAssociation {
+                        ty: association.ty.clone(),
+                        bool_keyword: association.bool_keyword,
+                        type_span: association.type_span,
+                        expr: self.alloc_expr(&association.expr),
+                    })
+                    .collect();
+
+                let ids = AssociationIds {
+                    start: self.associations.len() as u32,
+                    len: associations.len() as u32,
+                };
+                self.associations.extend(associations);
+                ExprNode::Generic {
+                    controlling,
+                    associations: ids,
+                    span: *span,
+                }
+            }
+        };
+
+        self.alloc(node)
+    }
+
+    /// Rebuild the owned expression rooted at the given id.
+    pub fn to_expr(&self, id: ExprId) -> Expr {
+        match self[id] {
+            ExprNode::Integer { value, span } => Expr::Integer { value, span },
+            ExprNode::Boolean { value, span } => Expr::Boolean { value, span },
+            ExprNode::Unary {
+                operator,
+                operand,
+                span,
+            } => Expr::Unary {
+                operator,
+                operand: Box::new(self.to_expr(operand)),
+                span,
+            },
+            ExprNode::Binary {
+                operator,
+                left,
+                right,
+                span,
+            } => Expr::Binary {
+                operator,
+                left: Box::new(self.to_expr(left)),
+                right: Box::new(self.to_expr(right)),
+                span,
+            },
+            ExprNode::Generic {
+                controlling,
+                associations,
+                span,
+            } => Expr::Generic {
+                controlling: Box::new(self.to_expr(controlling)),
+                associations: self[associations]
+                    .iter()
+                    .map(|association| Association {
+                        ty: association.ty.clone(),
+                        bool_keyword: association.bool_keyword,
+                        type_span: association.type_span,
+                        expr: self.to_expr(association.expr),
+                    })
+                    .collect(),
+                span,
+            },
+        }
+    }
+}
+
+impl Index<ExprId> for ExprArena {
+    type Output = ExprNode;
+
+    fn index(&self, id: ExprId) -> &ExprNode {
+        &self.nodes[id.0 as usize]
+    }
+}
+
+impl Index<AssociationIds> for ExprArena {
+    type Output = [ArenaAssociation];
+
+    fn index(&self, ids: AssociationIds) -> &[ArenaAssociation] {
+        let start = ids.start as usize;
+        &self.associations[start..start + ids.len as usize]
+    }
+}
+
+/// A program whose expressions live in an arena.
+///
+/// # Examples
+///
+/// ```
+/// use ecc::ast::arena::{ArenaProgram, ArenaStatement, ExprNode};
+/// use ecc::{lexer, parser};
+///
+/// let tokens = lexer::tokenize("int main(void) { return 1 + 2; }").unwrap();
+/// let program = parser::parse_token_stream(tokens).unwrap();
+/// let arena = ArenaProgram::from_program(&program);
+///
+/// let ArenaStatement::Return(Some(root)) = arena.function.body[0] else {
+///     unreachable!()
+/// };
+/// assert!(matches!(arena.exprs[root], ExprNode::Binary { .. }));
+/// assert_eq!(arena.exprs.len(), 3);
+/// assert_eq!(arena.to_program(), program);
+/// ```
+#[derive(Clone, PartialEq, Eq, Debug)]
+pub struct ArenaProgram {
+    /// Every expression in the program.
+    pub exprs: ExprArena,
+
+    /// The single function of the program.
+    pub function: ArenaFunction,
+}
+
+/// A function whose expressions live in the program's arena.
+#[derive(Clone, PartialEq, Eq, Debug)]
+pub struct ArenaFunction {
+    /// The function's name.
+    pub name: String,
+
+    /// Where the function's name appears in the source.
+    pub name_span: Span,
+
+    /// Whether the function is declared `static`.
+    pub is_static: bool,
+
+    /// Whether the function is declared `inline`.
+    pub inline: bool,
+
+    /// Whether the function is declared never to return.
+    pub noreturn: bool,
+
+    /// The alignment of the function's code in bytes, if it asks for one.
+    pub aligned: Option<u32>,
+
+    /// Whether the function is declared with `__attribute__((unused))`.
+    pub unused: bool,
+
+    /// The attributes the function is declared with that have no effect.
+    pub ignored_attributes: Vec<Attribute>,
+
+    /// The type of the value the function returns.
+    pub return_type: Type,
+
+    /// The keyword the return type is spelled with, if it is `bool`.
+    pub bool_keyword: BoolKeyword,
+
+    /// The body of the function.
+    pub body: Vec<ArenaStatement>,
+}
+
+/// A statement whose expressions live in the program's arena.
+#[derive(Clone, PartialEq, Eq, Debug)]
+pub enum ArenaStatement {
+    /// A return statement.
+    Return(Option<ExprId>),
+
+    /// A basic `asm` statement.
+    Asm { template: String, span: Span },
+}
+
+impl ArenaProgram {
+    /// Copy an owned program into a fresh arena.
+    pub fn from_program(program: &Program) -> Self {
+        let mut exprs = ExprArena::new();
+        let body = program
+            .function
+            .body
+            .iter()
+            .map(|statement| match statement {
+                Statement::Return(expr) => {
+                    ArenaStatement::Return(expr.as_ref().map(|expr| exprs.alloc_expr(expr)))
+                }
+                Statement::Asm { template, span } => ArenaStatement::Asm {
+                    template: template.clone(),
+                    span: *span,
+                },
+            })
+            .collect();
+
+        Self {
+            exprs,
+            function: ArenaFunction {
+                name: program.function.name.clone(),
+                name_span: program.function.name_span,
+                is_static: program.function.is_static,
+                inline: program.function.inline,
+                noreturn: program.function.noreturn,
+                aligned: program.function.aligned,
+                unused: program.function.unused,
+                ignored_attributes: program.function.ignored_attributes.clone(),
+                return_type: program.function.return_type.clone(),
+                bool_keyword: program.function.bool_keyword,
+                body,
+            },
+        }
+    }
+
+    /// Rebuild the owned form of the program.
+    pub fn to_program(&self) -> Program {
+        let body = self
+            .function
+            .body
+            .iter()
+            .map(|statement| match statement {
+                ArenaStatement::Return(id) => {
+                    Statement::Return(id.map(|id| self.exprs.to_expr(id)))
+                }
+                ArenaStatement::Asm { template, span } => Statement::Asm {
+                    template: template.clone(),
+                    span: *span,
+                },
+            })
+            .collect();
+
+        Program {
+            function: Function {
+                name: self.function.name.clone(),
+                name_span: self.function.name_span,
+                is_static: self.function.is_static,
+                inline: self.function.inline,
+                noreturn: self.function.noreturn,
+                aligned: self.function.aligned,
+                unused: self.function.unused,
+                ignored_attributes: self.function.ignored_attributes.clone(),
+                return_type: self.function.return_type.clone(),
+                bool_keyword: self.function.bool_keyword,
+                body,
+            },
+        }
+    }
+}
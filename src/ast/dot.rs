@@ -0,0 +1,142 @@
+//! Drawing the abstract syntax tree as a GraphViz graph.
+//!
+//! This is what `--emit=ast-dot` prints. Piping it through `dot -Tsvg` gives a picture of the tree,
+//! which is much easier to follow than a dump when learning how a program is parsed.
+//!
+//! ```text
+//! ecc --emit=ast-dot program.c | dot -Tsvg > program.svg
+//! ```
+
+use std::fmt::Write;
+
+use super::pretty::{self, binary_spelling, unary_spelling};
+use super::{Expr, Function, Program, Statement};
+
+/// Draw a whole program as a `digraph`.
+///
+/// # Examples
+///
+/// ```
+/// use ecc::{ast, lexer, parser};
+///
+/// let tokens = lexer::tokenize("int main(void) { return -1; }").unwrap();
+/// let program = parser::parse_token_stream(tokens).unwrap();
+/// let graph = ast::dot::dot_program(&program);
+///
+/// assert!(graph.starts_with("digraph ast {"));
+/// assert!(graph.contains(r#"n3 [label="unary -"];"#));
+/// assert!(graph.contains("n2 -> n3;"));
+/// ```
+pub fn dot_program(program: &Program) -> String {
+    let mut graph = Graph::default();
+    let root = graph.node("program");
+    let function = graph.function(&program.function);
+    graph.edge(root, function);
+    graph.finish()
+}
+
+/// A graph being built, with nodes numbered in the order they were added.
+#[derive(Default)]
+struct Graph {
+    body: String,
+    nodes: usize,
+}
+
+impl Graph {
+    /// Add a node with the given label, returning its number.
+    fn node(&mut self, label: &str) -> usize {
+        let id = self.nodes;
+        self.nodes += 1;
+
+        let label = label.replace('\\', "\\\\").replace('"', "\\\"");
+        writeln!(self.body, "    n{id} [label=\"{label}\"];").unwrap();
+        id
+    }
+
+    fn edge(&mut self, from: usize, to: usize) {
+        writeln!(self.body, "    n{from} -> n{to};").unwrap();
+    }
+
+    fn function(&mut self, function: &Function) -> usize {
+        let specifiers: String = function
+            .specifiers()
+            .map(str::to_owned)
+            .chain(function.attributes())
+            .map(|specifier| format!("{specifier} "))
+            .collect();
+        let id = self.node(&format!("{specifiers}function {}", function.name));
+        for statement in &function.body {
+            let child = self.statement(statement);
+            self.edge(id, child);
+        }
+        id
+    }
+
+    fn statement(&mut self, statement: &Statement) -> usize {
+        match statement {
+            Statement::Return(expr) => {
+                let id = self.node("return");
+                if let Some(expr) = expr {
+                    let child = self.expr(expr);
+                    self.edge(id, child);
+                }
+                id
+            }
+            Statement::Asm { template, .. } => {
+                self.node(&format!("asm {}", pretty::quote(template)))
+            }
+        }
+    }
+
+    fn expr(&mut self, expr: &Expr) -> usize {
+        match expr {
+            Expr::Integer { value, .. } => self.node(&format!("integer {value}")),
+            Expr::Boolean { value, .. } => self.node(&format!("boolean {value}")),
+            Expr::Unary {
+                operator, operand, ..
+            } => {
+                let id = self.node(&format!("unary {}", unary_spelling(*operator)));
+                let child = self.expr(operand);
+                self.edge(id, child);
+                id
+            }
+            Expr::Binary {
+                operator,
+                left,
+                right,
+                ..
+            } => {
+                let id = self.node(&format!("binary {}", binary_spelling(*operator)));
+                let left = self.expr(left);
+                let right = self.expr(right);
+                self.edge(id, left);
+                self.edge(id, right);
+                id
+            }
+            Expr::Generic {
+                controlling,
+                associations,
+                ..
+            } => {
+                let id = self.node("_Generic");
+                let child = self.expr(controlling);
+                self.edge(id, child);
+                for association in associations {
+                    let label = match &association.ty {
+                        Some(ty) => format!("{}:", association.bool_keyword.spell(ty)),
+                        None => "default:".to_owned(),
+                    };
+                    let association_id = self.node(&label);
+                    let child = self.expr(&association.expr);
+                    self.edge(id, association_id);
+                    self.edge(association_id, child);
+                }
+                id
+            }
+        }
+    }
+
+    fn finish(self) -> String {
+        format!("digraph ast {{\n    node [shape=box];\n{}}}\n", self.body)
+    }
+}
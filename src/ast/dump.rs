@@ -0,0 +1,196 @@
+//! A compact, stable textual dump of the abstract syntax tree.
+//!
+//! The dump is an S-expression. Unlike the `Debug` output, its format is part of the compiler's
+//! interface: it is what `--emit=ast` prints, and tests can compare against it without breaking
+//! whenever a field is added to a node. Nodes whose children are all simple are printed on one
+//! line, and every other node puts each complex child on its own line, indented by two spaces.
+//!
+//! ```text
+//! (program
+//!   (function main
+//!     (return
+//!       (binary + (integer 1) (unary - (integer 2))))))
+//! ```
+
+use super::pretty;
+use super::{BinaryOp, Expr, Function, Program, Statement, UnaryOp};
+
+/// Dump a whole program.
+///
+/// # Examples
+///
+/// ```
+/// use ecc::{ast, lexer, parser};
+///
+/// let tokens = lexer::tokenize("int main(void) { return 1 + -2; }").unwrap();
+/// let program = parser::parse_token_stream(tokens).unwrap();
+///
+/// assert_eq!(
+///     ast::dump::dump_program(&program),
+///     "(program\n  (function main\n    (return\n      (binary + (integer 1) (unary - (integer 2))))))\n"
+/// );
+/// ```
+pub fn dump_program(program: &Program) -> String {
+    render(&program_to_sexp(program))
+}
+
+/// Dump a function.
+pub fn dump_function(function: &Function) -> String {
+    render(&function_to_sexp(function))
+}
+
+/// Dump a statement.
+pub fn dump_statement(statement: &Statement) -> String {
+    render(&statement_to_sexp(statement))
+}
+
+/// Dump an expression.
+pub fn dump_expr(expr: &Expr) -> String {
+    render(&expr_to_sexp(expr))
+}
+
+/// An S-expression.
+enum Sexp {
+    Atom(String),
+    List(Vec<Sexp>),
+}
+
+impl Sexp {
+    fn list(head: &str, children: impl IntoIterator<Item = Sexp>) -> Self {
+        let mut items = vec![Sexp::Atom(head.to_string())];
+        items.extend(children);
+        Sexp::List(items)
+    }
+
+    fn atom(text: impl ToString) -> Self {
+        Sexp::Atom(text.to_string())
+    }
+
+    /// Return true if this S-expression should be printed on one line.
+    ///
+    /// That is the case for anything that does not nest more than three lists deep, which is
+    /// enough for small expressions like `(binary + (integer 1) (integer 2))`.
+    fn is_simple(&self) -> bool {
+        self.depth() <= 3
+    }
+
+    fn depth(&self) -> usize {
+        match self {
+            Sexp::Atom(_) => 0,
+            Sexp::List(items) => 1 + items.iter().map(Sexp::depth).max().unwrap_or(0),
+        }
+    }
+}
+
+fn program_to_sexp(program: &Program) -> Sexp {
+    Sexp::list("program", [function_to_sexp(&program.function)])
+}
+
+fn function_to_sexp(function: &Function) -> Sexp {
+    let mut children = vec![Sexp::atom(&function.name)];
+    children.extend(function.specifiers().map(Sexp::atom));
+    children.extend(function.attributes().map(Sexp::atom));
+    children.extend(function.body.iter().map(statement_to_sexp));
+    Sexp::list("function", children)
+}
+
+fn statement_to_sexp(statement: &Statement) -> Sexp {
+    match statement {
+        Statement::Return(expr) => Sexp::list("return", expr.iter().map(expr_to_sexp)),
+        Statement::Asm { template, .. } => Sexp::list("asm", [Sexp::atom(pretty::quote(template))]),
+    }
+}
+
+fn expr_to_sexp(expr: &Expr) -> Sexp {
+    match expr {
+        Expr::Integer { value, .. } => Sexp::list("integer", [Sexp::atom(value)]),
+        Expr::Boolean { value, .. } => Sexp::list("boolean", [Sexp::atom(value)]),
+        Expr::Unary {
+            operator, operand, ..
+        } => {
+            let operator = match operator {
+                UnaryOp::Compliment => "~",
+                UnaryOp::NegateArith => "-",
+                UnaryOp::NegateLogical => "!",
+            };
+
+            Sexp::list("unary", [Sexp::atom(operator), expr_to_sexp(operand)])
+        }
+        Expr::Binary {
+            operator,
+            left,
+            right,
+            ..
+        } => {
+            let operator = match operator {
+                BinaryOp::Plus => "+",
+                BinaryOp::Minus => "-",
+                BinaryOp::Times => "*",
+                BinaryOp::Divide => "/",
+                BinaryOp::Mod => "%",
+            };
+
+            Sexp::list(
+                "binary",
+                [
+                    Sexp::atom(operator),
+                    expr_to_sexp(left),
+                    expr_to_sexp(right),
+                ],
+            )
+        }
+        Expr::Generic {
+            controlling,
+            associations,
+            ..
+        } => {
+            let associations = associations.iter().map(|association| {
+                let ty = match &association.ty {
+                    Some(ty) => association.bool_keyword.spell(ty),
+                    None => "default".to_owned(),
+                };
+                Sexp::list(
+                    "association",
+                    [Sexp::atom(ty), expr_to_sexp(&association.expr)],
+                )
+            });
+
+            Sexp::list(
+                "generic",
+                std::iter::once(expr_to_sexp(controlling)).chain(associations),
+            )
+        }
+    }
+}
+
+/// Render an S-expression, followed by a newline.
+fn render(sexp: &Sexp) -> String {
+    let mut output = String::new();
+    render_at(&mut output, sexp, 0);
+    output.push('\n');
+    output
+}
+
+fn render_at(output: &mut String, sexp: &Sexp, indent: usize) {
+    let items = match sexp {
+        Sexp::Atom(text) => return output.push_str(text),
+        Sexp::List(items) => items,
+    };
+
+    output.push('(');
+
+    // Atoms stay on the same line as the head, since they are things like names and operators.
+    // Unless the whole list is simple, every list inside it goes on its own line.
+    for (i, item) in items.iter().enumerate() {
+        if !sexp.is_simple() && matches!(item, Sexp::List(_)) {
+            output.push('\n');
+            output.push_str(&" ".repeat(indent + 2));
+        } else if i > 0 {
+            output.push(' ');
+        }
+
+        render_at(output, item, indent + 2);
+    }
+
+    output.push(')');
+}
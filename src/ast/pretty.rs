@@ -0,0 +1,255 @@
+//! Rendering the abstract syntax tree back into C source code.
+//!
+//! The printer produces canonically formatted code: one statement per line, a configurable
+//! indent, single spaces around binary operators, and only the parentheses needed to keep the
+//! meaning of the tree. Parsing the printed code gives back the same tree.
+
+use std::fmt::Write;
+
+use super::{BinaryOp, Expr, Function, Program, Statement, UnaryOp};
+
+/// A pretty printer.
+///
+/// # Examples
+///
+/// ```
+/// use ecc::ast::pretty::PrettyPrinter;
+/// use ecc::{lexer, parser};
+///
+/// let tokens = lexer::tokenize("int main(void){return (1+2)*3;}").unwrap();
+/// let program = parser::parse_token_stream(tokens).unwrap();
+/// let printer = PrettyPrinter::new().with_indent("  ");
+///
+/// assert_eq!(
+///     printer.print_program(&program),
+///     "int main(void) {\n  return (1 + 2) * 3;\n}\n"
+/// );
+/// ```
+#[derive(Clone, Debug)]
+pub struct PrettyPrinter {
+    indent: String,
+}
+
+impl Default for PrettyPrinter {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl PrettyPrinter {
+    /// Create a printer that indents with four spaces.
+    pub fn new() -> Self {
+        Self {
+            indent: "    ".to_string(),
+        }
+    }
+
+    /// Use the given string for each level of indentation, like `"\t"` or `"  "`.
+    pub fn with_indent(mut self, indent: impl Into<String>) -> Self {
+        self.indent = indent.into();
+        self
+    }
+
+    /// Print a whole program.
+    pub fn print_program(&self, program: &Program) -> String {
+        self.print_function(&program.function)
+    }
+
+    /// Print a function definition, ending with a newline.
+    pub fn print_function(&self, function: &Function) -> String {
+        let mut output = String::new();
+
+        writeln!(output, "{}", self.print_function_header(function)).unwrap();
+        for statement in &function.body {
+            writeln!(output, "{}{}", self.indent, self.print_statement(statement)).unwrap();
+        }
+        writeln!(output, "}}").unwrap();
+
+        output
+    }
+
+    /// Print the line that opens a function definition, up to and including the brace, without a
+    /// trailing newline.
+    pub fn print_function_header(&self, function: &Function) -> String {
+        let attributes: Vec<_> = function
+            .attributes()
+            .chain(function.ignored_attributes.iter().map(ToString::to_string))
+            .collect();
+        let attributes = if attributes.is_empty() {
+            String::new()
+        } else {
+            format!("__attribute__(({})) ", attributes.join(", "))
+        };
+        let specifiers: String = function
+            .specifiers()
+            .map(|specifier| format!("{specifier} "))
+            .collect();
+        format!(
+            "{attributes}{specifiers}{} {}(void) {{",
+            function.bool_keyword.spell(&function.return_type),
+            function.name
+        )
+    }
+
+    /// Print a statement without any indentation or trailing newline.
+    pub fn print_statement(&self, statement: &Statement) -> String {
+        match statement {
+            Statement::Return(Some(expr)) => format!("return {};", self.print_expr(expr)),
+            Statement::Return(None) => "return;".to_owned(),
+            Statement::Asm { template, .. } => format!("asm({});", quote(template)),
+        }
+    }
+
+    /// Print an expression.
+    pub fn print_expr(&self, expr: &Expr) -> String {
+        let mut output = String::new();
+        write_expr(&mut output, expr);
+        output
+    }
+}
+
+/// Print a whole program with the default settings.
+pub fn print_program(program: &Program) -> String {
+    PrettyPrinter::new().print_program(program)
+}
+
+/// Write text as a C string literal, quotes included.
+///
+/// Quotes, backslashes, and control characters that have a simple escape sequence are escaped,
+/// and everything else is written as it is.
+///
+/// # Examples
+///
+/// ```
+/// use ecc::ast::pretty;
+///
+/// assert_eq!(pretty::quote("nop\n\tnop"), r#""nop\n\tnop""#);
+/// assert_eq!(pretty::quote(r#"say "hi""#), r#""say \"hi\"""#);
+/// ```
+pub fn quote(text: &str) -> String {
+    let mut output = String::from('"');
+    for c in text.chars() {
+        match c {
+            '"' => output.push_str("\\\""),
+            '\\' => output.push_str("\\\\"),
+            '\x07' => output.push_str("\\a"),
+            '\x08' => output.push_str("\\b"),
+            '\x0c' => output.push_str("\\f"),
+            '\n' => output.push_str("\\n"),
+            '\r' => output.push_str("\\r"),
+            '\t' => output.push_str("\\t"),
+            '\x0b' => output.push_str("\\v"),
+            _ => output.push(c),
+        }
+    }
+    output.push('"');
+    output
+}
+
+/// How tightly an operator binds. Higher numbers bind tighter.
+fn binary_precedence(op: BinaryOp) -> u8 {
+    match op {
+        BinaryOp::Plus | BinaryOp::Minus => 1,
+        BinaryOp::Times | BinaryOp::Divide | BinaryOp::Mod => 2,
+    }
+}
+
+/// The precedence of unary operators, which bind tighter than any binary operator.
+const UNARY_PRECEDENCE: u8 = 3;
+
+/// The precedence of an expression, as far as deciding on parentheses goes.
+fn expr_precedence(expr: &Expr) -> u8 {
+    match expr {
+        Expr::Integer { .. } | Expr::Boolean { .. } | Expr::Generic { .. } => u8::MAX,
+        Expr::Unary { .. } => UNARY_PRECEDENCE,
+        Expr::Binary { operator, .. } => binary_precedence(*operator),
+    }
+}
+
+pub(super) fn unary_spelling(op: UnaryOp) -> &'static str {
+    match op {
+        UnaryOp::Compliment => "~",
+        UnaryOp::NegateArith => "-",
+        UnaryOp::NegateLogical => "!",
+    }
+}
+
+pub(crate) fn binary_spelling(op: BinaryOp) -> &'static str {
+    match op {
+        BinaryOp::Plus => "+",
+        BinaryOp::Minus => "-",
+        BinaryOp::Times => "*",
+        BinaryOp::Divide => "/",
+        BinaryOp::Mod => "%",
+    }
+}
+
+/// Write an expression, wrapped in parentheses if `parenthesize` is set.
+fn write_operand(output: &mut String, expr: &Expr, parenthesize: bool) {
+    if parenthesize {
+        output.push('(');
+        write_expr(output, expr);
+        output.push(')');
+    } else {
+        write_expr(output, expr);
+    }
+}
+
+fn write_expr(output: &mut String, expr: &Expr) {
+    match expr {
+        Expr::Integer { value, .. } => write!(output, "{value}").unwrap(),
+        Expr::Boolean { value, .. } => write!(output, "{value}").unwrap(),
+
+        Expr::Unary {
+            operator, operand, ..
+        } => {
+            output.push_str(unary_spelling(*operator));
+
+            // Writing `-(-1)` as `--1` would turn two negations into a decrement.
+            let is_negative_negation = *operator == UnaryOp::NegateArith
+                && matches!(
+                    **operand,
+                    Expr::Unary {
+                        operator: UnaryOp::NegateArith,
+                        ..
+                    }
+                );
+            let parenthesize = is_negative_negation || expr_precedence(operand) < UNARY_PRECEDENCE;
+
+            write_operand(output, operand, parenthesize);
+        }
+
+        Expr::Binary {
+            operator,
+            left,
+            right,
+            ..
+        } => {
+            // Binary operators are left associative, so an operand on the right with the same
+            // precedence needs parentheses, but one on the left does not.
+            let precedence = binary_precedence(*operator);
+            write_operand(output, left, expr_precedence(left) < precedence);
+            write!(output, " {} ", binary_spelling(*operator)).unwrap();
+            write_operand(output, right, expr_precedence(right) <= precedence);
+        }
+
+        Expr::Generic {
+            controlling,
+            associations,
+            ..
+        } => {
+            output.push_str("_Generic(");
+            write_expr(output, controlling);
+            for association in associations {
+                match &association.ty {
+                    Some(ty) => {
+                        write!(output, ", {}: ", association.bool_keyword.spell(ty)).unwrap()
+                    }
+                    None => output.push_str(", default: "),
+                }
+                write_expr(output, &association.expr);
+            }
+            output.push(')');
+        }
+    }
+}
@@ -0,0 +1,197 @@
+//! Traversal of the abstract syntax tree.
+//!
+//! Passes over the tree usually only care about a few kinds of nodes. Implementing [`Visitor`] (or
+//! [`VisitorMut`], to change the tree in place) lets a pass override just the methods for those
+//! nodes. Every method defaults to calling the matching `walk_*` function, which visits the node's
+//! children. An overriding method can call the `walk_*` function itself to keep descending.
+
+use super::{Expr, Function, Program, Statement};
+
+/// A read-only traversal of the tree.
+///
+/// # Examples
+///
+/// ```
+/// use ecc::ast::{Expr, Visitor, visit};
+/// use ecc::{lexer, parser};
+///
+/// struct CountIntegers(usize);
+///
+/// impl Visitor for CountIntegers {
+///     fn visit_expr(&mut self, expr: &Expr) {
+///         if let Expr::Integer { .. } = expr {
+///             self.0 += 1;
+///         }
+///
+///         visit::walk_expr(self, expr);
+///     }
+/// }
+///
+/// let tokens = lexer::tokenize("int main(void) { return -(1 + 2) * 3; }").unwrap();
+/// let program = parser::parse_token_stream(tokens).unwrap();
+///
+/// let mut counter = CountIntegers(0);
+/// counter.visit_program(&program);
+///
+/// assert_eq!(counter.0, 3);
+/// ```
+pub trait Visitor {
+    /// Visit a program.
+    fn visit_program(&mut self, program: &Program) {
+        walk_program(self, program);
+    }
+
+    /// Visit a function.
+    fn visit_function(&mut self, function: &Function) {
+        walk_function(self, function);
+    }
+
+    /// Visit a statement.
+    fn visit_statement(&mut self, statement: &Statement) {
+        walk_statement(self, statement);
+    }
+
+    /// Visit an expression.
+    fn visit_expr(&mut self, expr: &Expr) {
+        walk_expr(self, expr);
+    }
+}
+
+/// Visit the function of a program.
+pub fn walk_program<V: Visitor + ?Sized>(visitor: &mut V, program: &Program) {
+    visitor.visit_function(&program.function);
+}
+
+/// Visit every statement in the body of a function.
+pub fn walk_function<V: Visitor + ?Sized>(visitor: &mut V, function: &Function) {
+    for statement in &function.body {
+        visitor.visit_statement(statement);
+    }
+}
+
+/// Visit the expressions of a statement.
+pub fn walk_statement<V: Visitor + ?Sized>(visitor: &mut V, statement: &Statement) {
+    match statement {
+        Statement::Return(Some(expr)) => visitor.visit_expr(expr),
+        Statement::Return(None) | Statement::Asm { .. } => {}
+    }
+}
+
+/// Visit the operands of an expression, from left to right.
+///
+/// Every part of a `_Generic` selection is visited, even though only the chosen association is
+/// ever evaluated.
+pub fn walk_expr<V: Visitor + ?Sized>(visitor: &mut V, expr: &Expr) {
+    match expr {
+        Expr::Integer { .. } | Expr::Boolean { .. } => {}
+        Expr::Unary { operand, .. } => visitor.visit_expr(operand),
+        Expr::Binary { left, right, .. } => {
+            visitor.visit_expr(left);
+            visitor.visit_expr(right);
+        }
+        Expr::Generic {
+            controlling,
+            associations,
+            ..
+        } => {
+            visitor.visit_expr(controlling);
+            for association in associations {
+                visitor.visit_expr(&association.expr);
+            }
+        }
+    }
+}
+
+/// A traversal of the tree that can change it in place.
+///
+/// # Examples
+///
+/// ```
+/// use ecc::ast::{Expr, VisitorMut, visit};
+/// use ecc::{lexer, parser};
+///
+/// struct Double;
+///
+/// impl VisitorMut for Double {
+///     fn visit_expr_mut(&mut self, expr: &mut Expr) {
+///         if let Expr::Integer { value, .. } = expr {
+///             *value *= 2;
+///         }
+///
+///         visit::walk_expr_mut(self, expr);
+///     }
+/// }
+///
+/// let tokens = lexer::tokenize("21").unwrap();
+/// let mut expr = parser::parse_expression(tokens).unwrap();
+///
+/// Double.visit_expr_mut(&mut expr);
+///
+/// assert!(matches!(expr, Expr::Integer { value: 42, .. }));
+/// ```
+pub trait VisitorMut {
+    /// Visit a program, possibly changing it.
+    fn visit_program_mut(&mut self, program: &mut Program) {
+        walk_program_mut(self, program);
+    }
+
+    /// Visit a function, possibly changing it.
+    fn visit_function_mut(&mut self, function: &mut Function) {
+        walk_function_mut(self, function);
+    }
+
+    /// Visit a statement, possibly changing it.
+    fn visit_statement_mut(&mut self, statement: &mut Statement) {
+        walk_statement_mut(self, statement);
+    }
+
+    /// Visit an expression, possibly changing it.
+    fn visit_expr_mut(&mut self, expr: &mut Expr) {
+        walk_expr_mut(self, expr);
+    }
+}
+
+/// Visit the function of a program.
+pub fn walk_program_mut<V: VisitorMut + ?Sized>(visitor: &mut V, program: &mut Program) {
+    visitor.visit_function_mut(&mut program.function);
+}
+
+/// Visit every statement in the body of a function.
+pub fn walk_function_mut<V: VisitorMut + ?Sized>(visitor: &mut V, function: &mut Function) {
+    for statement in &mut function.body {
+        visitor.visit_statement_mut(statement);
+    }
+}
+
+/// Visit the expressions of a statement.
+pub fn walk_statement_mut<V: VisitorMut + ?Sized>(visitor: &mut V, statement: &mut Statement) {
+    match statement {
+        Statement::Return(Some(expr)) => visitor.visit_expr_mut(expr),
+        Statement::Return(None) | Statement::Asm { .. } => {}
+    }
+}
+
+/// Visit the operands of an expression, from left to right.
+///
+/// Every part of a `_Generic` selection is visited, even though only the chosen association is
+/// ever evaluated.
+pub fn walk_expr_mut<V: VisitorMut + ?Sized>(visitor: &mut V, expr: &mut Expr) {
+    match expr {
+        Expr::Integer { .. } | Expr::Boolean { .. } => {}
+        Expr::Unary { operand, .. } => visitor.visit_expr_mut(operand),
+        Expr::Binary { left, right, .. } => {
+            visitor.visit_expr_mut(left);
+            visitor.visit_expr_mut(right);
+        }
+        Expr::Generic {
+            controlling,
+            associations,
+            ..
+        } => {
+            visitor.visit_expr_mut(controlling);
+            for association in associations {
+                visitor.visit_expr_mut(&mut association.expr);
+            }
+        }
+    }
+}
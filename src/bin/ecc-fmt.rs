@@ -0,0 +1,240 @@
+//! Format C source code the way `ecc` prints it.
+//!
+//! Each file is parsed and printed back out with [`PrettyPrinter`], so the result is laid out
+//! canonically no matter how the input was. The printer only knows about the syntax tree, so
+//! comments are put back in afterwards, as close to where they were as the new layout allows:
+//! comments that ended a line still end the same line, and comments anywhere else go on their own
+//! line before the statement they were in or in front of.
+//!
+//! ```text
+//! ecc-fmt [--check] FILE...
+//! ```
+//!
+//! Files are rewritten in place. With `--check`, nothing is written, the files that are not
+//! formatted are listed instead, and the exit code says whether there were any, which is what CI
+//! wants.
+
+// A diagnostic is only returned when a file cannot be formatted at all, so its size does not
+// matter.
+#![allow(clippy::result_large_err)]
+
+use std::fs;
+use std::path::Path;
+
+use ecc::ast::Program;
+use ecc::ast::pretty::PrettyPrinter;
+use ecc::diagnostics::{self, Diagnostic};
+use ecc::lexer::Lexer;
+use ecc::parser;
+use ecc::token::{Token, TokenKind};
+
+/// What every level of indentation is printed as.
+const INDENT: &str = "    ";
+
+fn main() {
+    let mut args = std::env::args();
+    let program_name = args.next().unwrap(); // This should never panic
+
+    let mut check = false;
+    let mut files = Vec::new();
+
+    for arg in args {
+        if arg == "--check" {
+            check = true;
+        } else if arg.starts_with('-') {
+            fail(&program_name, &format!("unknown option '{arg}'"));
+        } else {
+            files.push(arg);
+        }
+    }
+
+    if files.is_empty() {
+        fail(&program_name, "no input files");
+    }
+
+    let mut had_errors = false;
+    let mut unformatted = 0;
+
+    for file_name in &files {
+        let source = match fs::read_to_string(file_name) {
+            Ok(source) => source,
+            Err(e) => {
+                eprintln!("{program_name}: could not read '{file_name}': {e}");
+                had_errors = true;
+                continue;
+            }
+        };
+
+        let formatted = match format_source(&source) {
+            Ok(formatted) => formatted,
+            Err(diagnostic) => {
+                eprint!("{}", diagnostics::render(&diagnostic, file_name, &source));
+                had_errors = true;
+                continue;
+            }
+        };
+
+        if formatted == source {
+            continue;
+        }
+
+        if check {
+            println!("{file_name}");
+            unformatted += 1;
+        } else if let Err(e) = fs::write(Path::new(file_name), formatted) {
+            eprintln!("{program_name}: could not write '{file_name}': {e}");
+            had_errors = true;
+        }
+    }
+
+    if had_errors || unformatted > 0 {
+        std::process::exit(1);
+    }
+}
+
+/// Parse source code and print it back out canonically, comments included.
+fn format_source(source: &str) -> Result<String, Diagnostic> {
+    let mut tokens = Vec::new();
+    for token in Lexer::new(source).with_comments() {
+        tokens.push(token.map_err(Diagnostic::from)?);
+    }
+
+    let code = tokens
+        .iter()
+        .filter(|token| !token.kind.is_trivia())
+        .copied();
+    let program = parser::parse_token_stream(code)?;
+
+    Ok(print_with_comments(&program, &place_comments(&tokens)))
+}
+
+/// Where a comment goes in the formatted program.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+enum Place {
+    /// On its own line before the function.
+    BeforeFunction,
+
+    /// At the end of the line that opens the function.
+    AfterHeader,
+
+    /// On its own line before the statement with the given index, or before the closing brace if
+    /// there is no such statement.
+    BeforeStatement(usize),
+
+    /// At the end of the line of the statement with the given index.
+    AfterStatement(usize),
+
+    /// At the end of the line that closes the function.
+    AfterFunction,
+
+    /// On its own line after the function.
+    EndOfFile,
+}
+
+/// A comment or line directive, and where it goes.
+#[derive(Clone, Copy, Debug)]
+struct Trivia<'a> {
+    place: Place,
+    text: &'a str,
+
+    /// Whether it is a line directive, which is never indented.
+    directive: bool,
+}
+
+/// Decide where each comment and line directive goes, in order.
+///
+/// Every statement ends with a semicolon, so counting them tells which statement a comment is in
+/// or in front of. Line directives always start a line, so they always go on a line of their own.
+fn place_comments<'a>(tokens: &[Token<'a>]) -> Vec<Trivia<'a>> {
+    let mut comments = Vec::new();
+    let mut previous: Option<&Token> = None;
+    let mut in_body = false;
+    let mut after_body = false;
+    let mut statement = 0;
+
+    for token in tokens {
+        if !token.kind.is_trivia() {
+            match token.kind {
+                TokenKind::DelimBraceLeft => in_body = true,
+                TokenKind::DelimBraceRight => after_body = true,
+                TokenKind::DelimSemicolon => statement += 1,
+                _ => {}
+            }
+            previous = Some(token);
+            continue;
+        }
+
+        let ends_line_of = previous
+            .filter(|previous| previous.line == token.line)
+            .map(|previous| previous.kind);
+
+        let place = match ends_line_of {
+            Some(TokenKind::DelimBraceRight) if after_body => Place::AfterFunction,
+            _ if after_body => Place::EndOfFile,
+            Some(TokenKind::DelimBraceLeft) => Place::AfterHeader,
+            Some(TokenKind::DelimSemicolon) => Place::AfterStatement(statement - 1),
+            _ if in_body => Place::BeforeStatement(statement),
+            _ => Place::BeforeFunction,
+        };
+
+        comments.push(Trivia {
+            place,
+            text: token.lexeme,
+            directive: token.kind == TokenKind::SpecialLineDirective,
+        });
+    }
+
+    comments
+}
+
+/// Print a program with the comments and line directives put back in.
+fn print_with_comments(program: &Program, comments: &[Trivia]) -> String {
+    let printer = PrettyPrinter::new().with_indent(INDENT);
+    let function = &program.function;
+    let mut output = String::new();
+
+    let own_lines = |output: &mut String, place: Place, indent: &str| {
+        for comment in comments.iter().filter(|comment| comment.place == place) {
+            if !comment.directive {
+                output.push_str(indent);
+            }
+            output.push_str(comment.text);
+            output.push('\n');
+        }
+    };
+    let line_end = |output: &mut String, place: Place| {
+        for comment in comments.iter().filter(|comment| comment.place == place) {
+            output.push(' ');
+            output.push_str(comment.text);
+        }
+        output.push('\n');
+    };
+
+    own_lines(&mut output, Place::BeforeFunction, "");
+    output.push_str(&printer.print_function_header(function));
+    line_end(&mut output, Place::AfterHeader);
+
+    for (index, statement) in function.body.iter().enumerate() {
+        own_lines(&mut output, Place::BeforeStatement(index), INDENT);
+        output.push_str(INDENT);
+        output.push_str(&printer.print_statement(statement));
+        line_end(&mut output, Place::AfterStatement(index));
+    }
+
+    own_lines(
+        &mut output,
+        Place::BeforeStatement(function.body.len()),
+        INDENT,
+    );
+    output.push('}');
+    line_end(&mut output, Place::AfterFunction);
+    own_lines(&mut output, Place::EndOfFile, "");
+
+    output
+}
+
+/// Report an error with the command line and exit.
+fn fail(program_name: &str, message: &str) -> ! {
+    eprintln!("{program_name}: error: {message}");
+    std::process::exit(2);
+}
@@ -0,0 +1,368 @@
+//! Look for programs that `ecc` compiles differently from `gcc`.
+//!
+//! Random programs are generated, compiled with both compilers, and run. When the two executables
+//! exit differently, the program is reduced to the smallest one that still shows the difference,
+//! which is printed along with the flags it was compiled with.
+//!
+//! Generators like csmith write programs that use far more of C than `ecc` supports, so the
+//! programs come from a generator of its own, which only uses what `ecc` can compile. For now,
+//! that is a `main` that returns an expression. Signed overflow is undefined behavior, so both
+//! compilers are given `-fwrapv` to define it, and programs that divide by zero or divide
+//! `INT_MIN` by -1 are never tried, since nothing defines those.
+//!
+//! ```text
+//! ecc-fuzz [--runs N] [--seed N]
+//! ```
+//!
+//! Without `--runs`, 100 programs are tried. The seed is picked from the clock unless it is given,
+//! and is printed, so that a run can be repeated.
+
+use std::fs;
+use std::panic::{self, AssertUnwindSafe};
+use std::path::{Path, PathBuf};
+use std::process::Command;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use ecc::CompileOptions;
+use ecc::ast::pretty::PrettyPrinter;
+use ecc::ast::{BinaryOp, Expr, UnaryOp};
+use ecc::diagnostics::Diagnostic;
+use ecc::interp;
+use ecc::options::OptLevel;
+use ecc::sema;
+use ecc::span::Span;
+
+/// How deeply expressions are nested, at most.
+const MAX_DEPTH: u32 = 6;
+
+const UNARY_OPERATORS: &[UnaryOp] = &[
+    UnaryOp::Compliment,
+    UnaryOp::NegateArith,
+    UnaryOp::NegateLogical,
+];
+
+const BINARY_OPERATORS: &[BinaryOp] = &[
+    BinaryOp::Plus,
+    BinaryOp::Minus,
+    BinaryOp::Times,
+    BinaryOp::Divide,
+    BinaryOp::Mod,
+];
+
+fn main() {
+    let mut args = std::env::args();
+    let program_name = args.next().unwrap(); // This should never panic
+
+    let mut runs = 100;
+    let mut seed = None;
+
+    while let Some(arg) = args.next() {
+        if arg == "--runs" || arg == "--seed" {
+            let value = args.next().and_then(|value| value.parse().ok());
+            let Some(value) = value else {
+                fail(&program_name, &format!("'{arg}' needs a number"));
+            };
+
+            if arg == "--runs" {
+                runs = value;
+            } else {
+                seed = Some(value);
+            }
+        } else {
+            fail(&program_name, &format!("unknown option '{arg}'"));
+        }
+    }
+
+    if Command::new("gcc").arg("--version").output().is_err() {
+        fail(&program_name, "could not run gcc");
+    }
+
+    let seed = seed.unwrap_or_else(|| {
+        let now = SystemTime::now().duration_since(UNIX_EPOCH).unwrap();
+        now.as_nanos() as u64
+    });
+    println!("seed {seed}");
+
+    let work_dir = std::env::temp_dir().join(format!("ecc-fuzz-{}", std::process::id()));
+    if let Err(e) = fs::create_dir_all(&work_dir) {
+        fail(
+            &program_name,
+            &format!("could not create the work directory: {e}"),
+        );
+    }
+
+    let mut rng = Rng::new(seed);
+    let mut differed = 0;
+    for run in 0..runs {
+        let expr = loop {
+            let expr = generate_expr(&mut rng, MAX_DEPTH);
+            if interp::eval_expr(&expr).is_ok() {
+                break expr;
+            }
+        };
+        let opt_level = if rng.below(2) == 0 {
+            OptLevel::O0
+        } else {
+            OptLevel::O2
+        };
+
+        let fuzzer = Fuzzer {
+            work_dir: &work_dir,
+            opt_level,
+        };
+        if fuzzer.difference(&expr).is_none() {
+            continue;
+        }
+
+        differed += 1;
+        let expr = fuzzer.reduce(expr);
+        let difference = fuzzer.difference(&expr).unwrap_or_default();
+        println!();
+        println!("program {run}, with {}: {difference}", fuzzer.flags());
+        print!("{}", source_for(&expr));
+    }
+
+    let _ = fs::remove_dir_all(&work_dir);
+
+    println!();
+    println!("{runs} programs, {differed} behaved differently");
+    if differed > 0 {
+        std::process::exit(1);
+    }
+}
+
+/// A small xorshift random number generator, which is all that generating programs needs.
+struct Rng(u64);
+
+impl Rng {
+    fn new(seed: u64) -> Self {
+        // The state must never be zero, or it stays zero forever.
+        Self(seed.max(1))
+    }
+
+    fn next(&mut self) -> u64 {
+        self.0 ^= self.0 << 13;
+        self.0 ^= self.0 >> 7;
+        self.0 ^= self.0 << 17;
+        self.0
+    }
+
+    /// A number that is less than `n`.
+    fn below(&mut self, n: u64) -> u64 {
+        self.next() % n
+    }
+}
+
+/// Generate an expression nested at most `depth` deep.
+fn generate_expr(rng: &mut Rng, depth: u32) -> Expr {
+    if depth == 0 || rng.below(4) == 0 {
+        return integer(generate_literal(rng));
+    }
+
+    if rng.below(3) == 0 {
+        let operator = UNARY_OPERATORS[rng.below(UNARY_OPERATORS.len() as u64) as usize];
+        unary(operator, generate_expr(rng, depth - 1))
+    } else {
+        let operator = BINARY_OPERATORS[rng.below(BINARY_OPERATORS.len() as u64) as usize];
+        let left = generate_expr(rng, depth - 1);
+        binary(operator, left, generate_expr(rng, depth - 1))
+    }
+}
+
+/// Generate the value of an integer literal.
+///
+/// Small values and the largest `int` come up much more often than they would by chance, since
+/// that is where the interesting edge cases are.
+fn generate_literal(rng: &mut Rng) -> i32 {
+    match rng.below(4) {
+        0 => rng.below(3) as i32,
+        1 => i32::MAX,
+        2 => rng.below(256) as i32,
+        // Keep 31 bits, since C has no negative literals.
+        _ => (rng.next() >> 33) as i32,
+    }
+}
+
+fn integer(value: i32) -> Expr {
+    Expr::Integer {
+        value,
+        span: Span::default(),
+    }
+}
+
+fn unary(operator: UnaryOp, operand: Expr) -> Expr {
+    Expr::Unary {
+        operator,
+        operand: Box::new(operand),
+        span: Span::default(),
+    }
+}
+
+fn binary(operator: BinaryOp, left: Expr, right: Expr) -> Expr {
+    Expr::Binary {
+        operator,
+        left: Box::new(left),
+        right: Box::new(right),
+        span: Span::default(),
+    }
+}
+
+/// Write a program whose `main` returns the given expression.
+fn source_for(expr: &Expr) -> String {
+    let expr = PrettyPrinter::new().print_expr(expr);
+    format!("int main(void) {{\n    return {expr};\n}}\n")
+}
+
+/// Every expression that is one step smaller than the given one.
+///
+/// An expression can be replaced by one of its operands, or have one of its operands made
+/// smaller, and a literal can be made closer to zero. Each step makes the expression strictly
+/// smaller, so reducing one always finishes.
+fn smaller_exprs(expr: &Expr) -> Vec<Expr> {
+    match expr {
+        Expr::Integer { value, .. } => {
+            let mut values = vec![0, 1, value / 2];
+            values.dedup();
+            values
+                .into_iter()
+                .filter(|smaller| smaller < value)
+                .map(integer)
+                .collect()
+        }
+        Expr::Boolean { value: true, span } => vec![Expr::Boolean {
+            value: false,
+            span: *span,
+        }],
+        Expr::Boolean { .. } => Vec::new(),
+        Expr::Unary {
+            operator, operand, ..
+        } => {
+            let mut smaller = vec![(**operand).clone()];
+            for operand in smaller_exprs(operand) {
+                smaller.push(unary(*operator, operand));
+            }
+            smaller
+        }
+        Expr::Binary {
+            operator,
+            left,
+            right,
+            ..
+        } => {
+            let mut smaller = vec![(**left).clone(), (**right).clone()];
+            for left in smaller_exprs(left) {
+                smaller.push(binary(*operator, left, (**right).clone()));
+            }
+            for right in smaller_exprs(right) {
+                smaller.push(binary(*operator, (**left).clone(), right));
+            }
+            smaller
+        }
+        // Selections are never generated, but the chosen association is always smaller.
+        Expr::Generic {
+            controlling,
+            associations,
+            ..
+        } => sema::select(controlling, associations)
+            .cloned()
+            .into_iter()
+            .collect(),
+    }
+}
+
+/// How programs are compiled and compared.
+struct Fuzzer<'a> {
+    work_dir: &'a Path,
+    opt_level: OptLevel,
+}
+
+impl Fuzzer<'_> {
+    /// The flags that both compilers are given.
+    fn flags(&self) -> &'static str {
+        match self.opt_level {
+            OptLevel::O0 => "-O0 -fwrapv",
+            OptLevel::O1 => "-O1 -fwrapv",
+            OptLevel::O2 => "-O2 -fwrapv",
+        }
+    }
+
+    /// Shrink a program that behaves differently, for as long as a smaller one still does.
+    fn reduce(&self, mut expr: Expr) -> Expr {
+        'shrink: loop {
+            for smaller in smaller_exprs(&expr) {
+                if interp::eval_expr(&smaller).is_ok() && self.difference(&smaller).is_some() {
+                    expr = smaller;
+                    continue 'shrink;
+                }
+            }
+
+            return expr;
+        }
+    }
+
+    /// Compile and run a program with both compilers, describing how they differed, if they did.
+    fn difference(&self, expr: &Expr) -> Option<String> {
+        let source = self.work_dir.join("fuzz.c");
+        if let Err(e) = fs::write(&source, source_for(expr)) {
+            return Some(format!("could not write the program: {e}"));
+        }
+
+        // A program `gcc` cannot compile is not `ecc`'s problem.
+        let expected = exit_code(&self.compile_with_gcc(&source)?);
+        let actual = match self.compile_with_ecc(&source) {
+            Ok(executable) => exit_code(&executable),
+            Err(reason) => return Some(reason),
+        };
+
+        if expected == actual {
+            None
+        } else {
+            Some(format!("expected exit code {expected:?}, got {actual:?}"))
+        }
+    }
+
+    /// Compile a program with `ecc`, returning the path to the executable, or why there is none.
+    fn compile_with_ecc(&self, source: &Path) -> Result<PathBuf, String> {
+        let options = CompileOptions::new()
+            .with_opt_level(self.opt_level)
+            .with_wrapv(true);
+
+        let mut diagnostics: Vec<Diagnostic> = Vec::new();
+        let result = panic::catch_unwind(AssertUnwindSafe(|| {
+            ecc::compile_file(source, &options, &mut diagnostics).map_err(|e| e.to_string())
+        }));
+
+        match result {
+            Ok(Ok(())) => Ok(source.with_extension("")),
+            Ok(Err(reason)) => Err(format!("ecc rejected it: {reason}")),
+            Err(_) => Err("the compiler panicked".to_string()),
+        }
+    }
+
+    /// Compile a program with `gcc`, returning the path to the executable.
+    fn compile_with_gcc(&self, source: &Path) -> Option<PathBuf> {
+        let executable = source.with_extension("gcc");
+        let status = Command::new("gcc")
+            .args(self.flags().split(' '))
+            .arg("-w")
+            .arg("-o")
+            .arg(&executable)
+            .arg(source)
+            .status()
+            .ok()?;
+
+        status.success().then_some(executable)
+    }
+}
+
+/// Run an executable, returning its exit code, or `None` if it was killed by a signal or could
+/// not be run.
+fn exit_code(executable: &Path) -> Option<i32> {
+    Command::new(executable).status().ok()?.code()
+}
+
+/// Report an error with the command line and exit.
+fn fail(program_name: &str, message: &str) -> ! {
+    eprintln!("{program_name}: error: {message}");
+    std::process::exit(2);
+}
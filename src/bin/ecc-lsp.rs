@@ -0,0 +1,407 @@
+//! A language server for the subset of C that `ecc` understands.
+//!
+//! The server speaks the Language Server Protocol over stdin and stdout. Whenever a document is
+//! opened or changed, it is run through the front end, and every diagnostic is published. Hovering
+//! over a name shows its declaration, and going to the definition of a name jumps to where it was
+//! declared. Semantic tokens from [`ecc::highlight`] are offered for highlighting.
+
+use std::collections::HashMap;
+use std::error::Error;
+
+use ecc::diagnostics::{self, Level};
+use ecc::highlight::{self, SemanticKind};
+use ecc::sema::{Symbol, SymbolTable};
+use ecc::span::Span;
+use ecc::types::Type;
+use lsp_server::{Connection, ErrorCode, Message, Notification, Request, Response};
+use lsp_types::notification::{
+    DidChangeTextDocument, DidCloseTextDocument, DidOpenTextDocument,
+    Notification as NotificationTrait, PublishDiagnostics,
+};
+use lsp_types::request::{
+    GotoDefinition, HoverRequest, Request as RequestTrait, SemanticTokensFullRequest,
+};
+use lsp_types::{
+    DiagnosticRelatedInformation, DiagnosticSeverity, GotoDefinitionParams, GotoDefinitionResponse,
+    Hover, HoverContents, HoverParams, HoverProviderCapability, Location, MarkupContent,
+    MarkupKind, NumberOrString, OneOf, Position, PublishDiagnosticsParams, Range, SemanticToken,
+    SemanticTokenType, SemanticTokens, SemanticTokensFullOptions, SemanticTokensLegend,
+    SemanticTokensOptions, SemanticTokensParams, SemanticTokensServerCapabilities,
+    ServerCapabilities, TextDocumentSyncCapability, TextDocumentSyncKind, Url,
+};
+
+type ServerResult<T> = Result<T, Box<dyn Error + Send + Sync>>;
+
+/// The semantic token types the server uses, in the order the client is told about them.
+const TOKEN_TYPES: [SemanticTokenType; 7] = [
+    SemanticTokenType::KEYWORD,
+    SemanticTokenType::VARIABLE,
+    SemanticTokenType::FUNCTION,
+    SemanticTokenType::NUMBER,
+    SemanticTokenType::STRING,
+    SemanticTokenType::COMMENT,
+    SemanticTokenType::OPERATOR,
+];
+
+fn main() -> ServerResult<()> {
+    let (connection, io_threads) = Connection::stdio();
+
+    let capabilities = ServerCapabilities {
+        text_document_sync: Some(TextDocumentSyncCapability::Kind(TextDocumentSyncKind::FULL)),
+        hover_provider: Some(HoverProviderCapability::Simple(true)),
+        definition_provider: Some(OneOf::Left(true)),
+        semantic_tokens_provider: Some(SemanticTokensServerCapabilities::SemanticTokensOptions(
+            SemanticTokensOptions {
+                legend: SemanticTokensLegend {
+                    token_types: TOKEN_TYPES.to_vec(),
+                    token_modifiers: Vec::new(),
+                },
+                full: Some(SemanticTokensFullOptions::Bool(true)),
+                ..Default::default()
+            },
+        )),
+        ..Default::default()
+    };
+    connection.initialize(serde_json::to_value(capabilities)?)?;
+
+    let mut server = Server {
+        connection: &connection,
+        documents: HashMap::new(),
+    };
+    server.run()?;
+
+    io_threads.join()?;
+    Ok(())
+}
+
+/// The state of the server.
+struct Server<'a> {
+    connection: &'a Connection,
+
+    /// The text of every open document.
+    documents: HashMap<Url, String>,
+}
+
+impl Server<'_> {
+    /// Handle messages until the client asks the server to shut down.
+    fn run(&mut self) -> ServerResult<()> {
+        for message in &self.connection.receiver {
+            match message {
+                Message::Request(request) => {
+                    if self.connection.handle_shutdown(&request)? {
+                        return Ok(());
+                    }
+                    self.handle_request(request)?;
+                }
+                Message::Notification(notification) => self.handle_notification(notification)?,
+                Message::Response(_) => (),
+            }
+        }
+
+        Ok(())
+    }
+
+    fn handle_request(&mut self, request: Request) -> ServerResult<()> {
+        let response = match request.method.as_str() {
+            HoverRequest::METHOD => self.respond::<HoverRequest>(request, Self::hover),
+            GotoDefinition::METHOD => self.respond::<GotoDefinition>(request, Self::definition),
+            SemanticTokensFullRequest::METHOD => self
+                .respond::<SemanticTokensFullRequest>(request, |server, params| {
+                    server.semantic_tokens(params).map(Into::into)
+                }),
+            _ => Response::new_err(
+                request.id,
+                ErrorCode::MethodNotFound as i32,
+                format!("unsupported request '{}'", request.method),
+            ),
+        };
+
+        self.connection.sender.send(response.into())?;
+        Ok(())
+    }
+
+    /// Answer a request with the given handler, or with an error if its parameters do not make
+    /// sense.
+    fn respond<R: RequestTrait>(
+        &self,
+        request: Request,
+        handle: impl FnOnce(&Self, R::Params) -> R::Result,
+    ) -> Response {
+        match serde_json::from_value(request.params) {
+            Ok(params) => Response::new_ok(request.id, handle(self, params)),
+            Err(e) => Response::new_err(
+                request.id,
+                ErrorCode::InvalidParams as i32,
+                format!("invalid parameters for '{}': {e}", request.method),
+            ),
+        }
+    }
+
+    /// Handle a notification.
+    ///
+    /// Notifications cannot be answered, so one whose parameters do not make sense is logged and
+    /// otherwise ignored.
+    fn handle_notification(&mut self, notification: Notification) -> ServerResult<()> {
+        match notification.method.as_str() {
+            DidOpenTextDocument::METHOD => {
+                let Some(params) = notification_params::<DidOpenTextDocument>(notification) else {
+                    return Ok(());
+                };
+                let document = params.text_document;
+                self.documents.insert(document.uri.clone(), document.text);
+                self.publish_diagnostics(document.uri)?;
+            }
+            DidChangeTextDocument::METHOD => {
+                let Some(params) = notification_params::<DidChangeTextDocument>(notification)
+                else {
+                    return Ok(());
+                };
+
+                // The whole document is synchronized, so the last change is the new text.
+                if let Some(change) = params.content_changes.into_iter().last() {
+                    let uri = params.text_document.uri;
+                    self.documents.insert(uri.clone(), change.text);
+                    self.publish_diagnostics(uri)?;
+                }
+            }
+            DidCloseTextDocument::METHOD => {
+                let Some(params) = notification_params::<DidCloseTextDocument>(notification) else {
+                    return Ok(());
+                };
+                let uri = params.text_document.uri;
+                self.documents.remove(&uri);
+                self.send_diagnostics(uri, Vec::new())?;
+            }
+            _ => (),
+        }
+
+        Ok(())
+    }
+
+    /// Check a document and send the client everything that is wrong with it.
+    fn publish_diagnostics(&self, uri: Url) -> ServerResult<()> {
+        let Some(text) = self.documents.get(&uri) else {
+            return Ok(());
+        };
+
+        let mut emitted = Vec::new();
+        let _ = ecc::analyze_source(text, &mut emitted);
+
+        let diagnostics = emitted
+            .iter()
+            .map(|diagnostic| to_lsp_diagnostic(diagnostic, &uri, text))
+            .collect();
+
+        self.send_diagnostics(uri, diagnostics)
+    }
+
+    fn send_diagnostics(
+        &self,
+        uri: Url,
+        diagnostics: Vec<lsp_types::Diagnostic>,
+    ) -> ServerResult<()> {
+        let params = PublishDiagnosticsParams::new(uri, diagnostics, None);
+        let notification = Notification::new(PublishDiagnostics::METHOD.to_string(), params);
+        self.connection.sender.send(notification.into())?;
+        Ok(())
+    }
+
+    /// Analyze an open document, and find the byte offset of a position in it.
+    fn analyze_at(&self, uri: &Url, position: Position) -> Option<(&str, SymbolTable, usize)> {
+        let text = self.documents.get(uri)?;
+        let symbols = ecc::analyze_source(text, &mut Vec::new()).ok()?;
+        let offset = position_to_offset(text, position);
+        Some((text, symbols, offset))
+    }
+
+    fn hover(&self, params: HoverParams) -> Option<Hover> {
+        let document = params.text_document_position_params;
+        let (text, symbols, offset) =
+            self.analyze_at(&document.text_document.uri, document.position)?;
+        let symbol = symbols.symbol_at(offset)?;
+
+        Some(Hover {
+            contents: HoverContents::Markup(MarkupContent {
+                kind: MarkupKind::Markdown,
+                value: format!("```c\n{}\n```", declaration(symbol)),
+            }),
+            range: Some(span_to_range(text, symbol.span)),
+        })
+    }
+
+    fn definition(&self, params: GotoDefinitionParams) -> Option<GotoDefinitionResponse> {
+        let document = params.text_document_position_params;
+        let uri = document.text_document.uri;
+        let (text, symbols, offset) = self.analyze_at(&uri, document.position)?;
+        let symbol = symbols.symbol_at(offset)?;
+
+        Some(GotoDefinitionResponse::Scalar(Location::new(
+            uri.clone(),
+            span_to_range(text, symbol.span),
+        )))
+    }
+
+    fn semantic_tokens(&self, params: SemanticTokensParams) -> Option<SemanticTokens> {
+        let text = self.documents.get(&params.text_document.uri)?;
+
+        let mut data = Vec::new();
+        let mut previous = Position::new(0, 0);
+        for token in highlight::classify(text) {
+            let token_type = match token.kind {
+                SemanticKind::Keyword => 0,
+                SemanticKind::Identifier => 1,
+                SemanticKind::Function => 2,
+                SemanticKind::Number => 3,
+                SemanticKind::String => 4,
+                SemanticKind::Comment => 5,
+                SemanticKind::Operator => 6,
+            };
+
+            // Clients are not required to handle tokens that span lines, so block comments are
+            // split into a token per line.
+            let mut offset = token.span.start;
+            for line in text[token.span.start..token.span.end].split('\n') {
+                let start = offset_to_position(text, offset);
+                let length = line.encode_utf16().count() as u32;
+                offset += line.len() + 1;
+
+                if length == 0 {
+                    continue;
+                }
+
+                let delta_line = start.line - previous.line;
+                data.push(SemanticToken {
+                    delta_line,
+                    delta_start: if delta_line == 0 {
+                        start.character - previous.character
+                    } else {
+                        start.character
+                    },
+                    length,
+                    token_type,
+                    token_modifiers_bitset: 0,
+                });
+                previous = start;
+            }
+        }
+
+        Some(SemanticTokens {
+            result_id: None,
+            data,
+        })
+    }
+}
+
+/// Get the parameters of a notification, logging them and giving up if they do not make sense.
+fn notification_params<N: NotificationTrait>(notification: Notification) -> Option<N::Params> {
+    match serde_json::from_value(notification.params) {
+        Ok(params) => Some(params),
+        Err(e) => {
+            eprintln!("ignoring '{}' notification: {e}", notification.method);
+            None
+        }
+    }
+}
+
+/// Write out how a symbol was declared, the way it would look in C.
+fn declaration(symbol: &Symbol) -> String {
+    match &symbol.ty {
+        Type::Function {
+            return_type,
+            params,
+        } => {
+            let params = if params.is_empty() {
+                "void".to_string()
+            } else {
+                params
+                    .iter()
+                    .map(Type::to_string)
+                    .collect::<Vec<_>>()
+                    .join(", ")
+            };
+            format!("{return_type} {}({params})", symbol.name)
+        }
+        ty => format!("{ty} {}", symbol.name),
+    }
+}
+
+fn to_lsp_diagnostic(
+    diagnostic: &diagnostics::Diagnostic,
+    uri: &Url,
+    text: &str,
+) -> lsp_types::Diagnostic {
+    let severity = match diagnostic.level {
+        Level::Error => DiagnosticSeverity::ERROR,
+        Level::Warning => DiagnosticSeverity::WARNING,
+        Level::Note => DiagnosticSeverity::INFORMATION,
+        Level::Help => DiagnosticSeverity::HINT,
+    };
+
+    // Editors show the message on its own, so notes and help messages go along with it.
+    let mut message = diagnostic.message.clone();
+    for child in &diagnostic.children {
+        message.push_str(&format!("\n{}: {}", child.level, child.message));
+    }
+
+    let related_information: Vec<_> = diagnostic
+        .secondary
+        .iter()
+        .filter_map(|label| {
+            Some(DiagnosticRelatedInformation {
+                location: Location::new(uri.clone(), span_to_range(text, label.span)),
+                message: label.message.clone()?,
+            })
+        })
+        .collect();
+
+    lsp_types::Diagnostic {
+        range: span_to_range(text, diagnostic.primary.unwrap_or_default()),
+        severity: Some(severity),
+        code: diagnostic
+            .code
+            .map(|code| NumberOrString::String(code.to_string())),
+        source: Some("ecc".to_string()),
+        message,
+        related_information: (!related_information.is_empty()).then_some(related_information),
+        ..Default::default()
+    }
+}
+
+fn span_to_range(text: &str, span: Span) -> Range {
+    Range::new(
+        offset_to_position(text, span.start),
+        offset_to_position(text, span.end),
+    )
+}
+
+/// Convert a byte offset into a position, whose columns count UTF-16 code units.
+fn offset_to_position(text: &str, offset: usize) -> Position {
+    let before = &text[..offset.min(text.len())];
+    let line_start = before.rfind('\n').map_or(0, |i| i + 1);
+    let line = before.matches('\n').count();
+    let character = before[line_start..].encode_utf16().count();
+
+    Position::new(line as u32, character as u32)
+}
+
+/// Convert a position into a byte offset. Positions past the end of a line or of the text are
+/// clamped to the end.
+fn position_to_offset(text: &str, position: Position) -> usize {
+    let mut line_start = 0;
+    for _ in 0..position.line {
+        match text[line_start..].find('\n') {
+            Some(i) => line_start += i + 1,
+            None => return text.len(),
+        }
+    }
+
+    let mut character = 0;
+    for (i, c) in text[line_start..].char_indices() {
+        if character >= position.character as usize || c == '\n' {
+            return line_start + i;
+        }
+        character += c.len_utf16();
+    }
+
+    text.len()
+}
@@ -0,0 +1,276 @@
+//! Run the test suite from *Writing a C Compiler* against `ecc`.
+//!
+//! Both versions of the suite are understood. The one that goes with the blog posts
+//! (<https://github.com/nlsandler/write_a_c_compiler>) has a `stage_N` directory per chapter, each
+//! with a `valid` and an `invalid` directory of programs. The one that goes with the book
+//! (<https://github.com/nlsandler/writing-a-c-compiler-tests>) has `tests/chapter_N` directories,
+//! which split the invalid programs further into `invalid_lex`, `invalid_parse`, and so on. Only
+//! programs directly inside those directories are run, so extra credit and multi-file tests are
+//! skipped.
+//!
+//! Every valid program must compile and exit with the same code as when it is compiled with
+//! `gcc`, and every invalid program must be rejected.
+//!
+//! ```text
+//! ecc-suite path/to/suite [--stage N]
+//! ```
+//!
+//! Only stages up to and including `N` are run, which is how far the compiler is expected to get.
+//! Without `--stage`, every stage in the suite is run.
+
+use std::fs;
+use std::panic::{self, AssertUnwindSafe};
+use std::path::{Path, PathBuf};
+use std::process::Command;
+
+use ecc::CompileOptions;
+use ecc::diagnostics::Diagnostic;
+
+fn main() {
+    let mut args = std::env::args();
+    let program_name = args.next().unwrap(); // This should never panic
+
+    let mut suite_dir = None;
+    let mut last_stage = None;
+
+    while let Some(arg) = args.next() {
+        if arg == "--stage" {
+            let stage = args.next().and_then(|stage| stage.parse().ok());
+            let Some(stage) = stage else {
+                fail(&program_name, "'--stage' needs a stage number");
+            };
+
+            last_stage = Some(stage);
+        } else if arg.starts_with('-') {
+            fail(&program_name, &format!("unknown option '{arg}'"));
+        } else {
+            suite_dir = Some(PathBuf::from(arg));
+        }
+    }
+
+    let Some(suite_dir) = suite_dir else {
+        fail(&program_name, "no test suite directory given");
+    };
+
+    let stages = find_stages(&suite_dir, last_stage);
+    if stages.is_empty() {
+        fail(
+            &program_name,
+            &format!("no stages found in '{}'", suite_dir.display()),
+        );
+    }
+
+    let work_dir = std::env::temp_dir().join(format!("ecc-suite-{}", std::process::id()));
+    let mut results = Results::default();
+
+    for (number, stage_dir) in stages {
+        println!("stage {number}");
+        for source in programs_in(&stage_dir.join("valid")) {
+            results.record(&source, check_valid(&source, &work_dir));
+        }
+        for invalid_dir in invalid_dirs(&stage_dir) {
+            for source in programs_in(&invalid_dir) {
+                results.record(&source, check_invalid(&source, &work_dir));
+            }
+        }
+    }
+
+    let _ = fs::remove_dir_all(&work_dir);
+
+    println!();
+    println!("{} passed, {} failed", results.passed, results.failed);
+    if results.failed > 0 {
+        std::process::exit(1);
+    }
+}
+
+/// Tally of how the programs did.
+#[derive(Default)]
+struct Results {
+    passed: usize,
+    failed: usize,
+}
+
+impl Results {
+    /// Record and print the outcome of a single program.
+    fn record(&mut self, source: &Path, outcome: Result<(), String>) {
+        match outcome {
+            Ok(()) => {
+                self.passed += 1;
+                println!("    ok      {}", source.display());
+            }
+            Err(reason) => {
+                self.failed += 1;
+                println!("    FAILED  {}: {reason}", source.display());
+            }
+        }
+    }
+}
+
+/// Find the stage directories of the suite, up to and including `last_stage`, in order.
+///
+/// Stages are called `stage_N` in the blog's suite and `tests/chapter_N` in the book's.
+fn find_stages(suite_dir: &Path, last_stage: Option<u32>) -> Vec<(u32, PathBuf)> {
+    let tests_dir = suite_dir.join("tests");
+    let suite_dir = if tests_dir.is_dir() {
+        &tests_dir
+    } else {
+        suite_dir
+    };
+
+    let Ok(entries) = fs::read_dir(suite_dir) else {
+        return Vec::new();
+    };
+
+    let mut stages: Vec<_> = entries
+        .filter_map(|entry| {
+            let path = entry.ok()?.path();
+            let name = path.file_name()?.to_str()?;
+            let number = name
+                .strip_prefix("stage_")
+                .or_else(|| name.strip_prefix("chapter_"))?
+                .parse()
+                .ok()?;
+            Some((number, path))
+        })
+        .filter(|&(number, _)| last_stage.is_none_or(|last| number <= last))
+        .collect();
+
+    stages.sort();
+    stages
+}
+
+/// Find the directories of invalid programs in a stage, in order.
+fn invalid_dirs(stage_dir: &Path) -> Vec<PathBuf> {
+    let Ok(entries) = fs::read_dir(stage_dir) else {
+        return Vec::new();
+    };
+
+    let mut dirs: Vec<_> = entries
+        .filter_map(|entry| Some(entry.ok()?.path()))
+        .filter(|path| {
+            path.file_name()
+                .and_then(|name| name.to_str())
+                .is_some_and(|name| name.starts_with("invalid"))
+        })
+        .collect();
+
+    dirs.sort();
+    dirs
+}
+
+/// List the C files in a directory, in order. A missing directory has no programs.
+fn programs_in(dir: &Path) -> Vec<PathBuf> {
+    let Ok(entries) = fs::read_dir(dir) else {
+        return Vec::new();
+    };
+
+    let mut programs: Vec<_> = entries
+        .filter_map(|entry| Some(entry.ok()?.path()))
+        .filter(|path| path.extension().is_some_and(|extension| extension == "c"))
+        .collect();
+
+    programs.sort();
+    programs
+}
+
+/// Compile a program with `ecc`, returning the path to the executable.
+///
+/// The program is copied into the work directory first, since `ecc` puts the executable next to
+/// the source. Panics are caught and reported like any other failure, so that one crash does not
+/// stop the whole run.
+fn compile_with_ecc(source: &Path, work_dir: &Path) -> Result<PathBuf, EccFailure> {
+    let copy = work_dir.join("ecc").join(source.file_name().unwrap());
+    fs::create_dir_all(copy.parent().unwrap()).map_err(|e| EccFailure::Io(e.to_string()))?;
+    fs::copy(source, &copy).map_err(|e| EccFailure::Io(e.to_string()))?;
+
+    let mut diagnostics: Vec<Diagnostic> = Vec::new();
+    let result = panic::catch_unwind(AssertUnwindSafe(|| {
+        ecc::compile_file(&copy, &CompileOptions::new(), &mut diagnostics)
+            .map_err(|e| e.to_string())
+    }));
+
+    match result {
+        Ok(Ok(())) => Ok(copy.with_extension("")),
+        Ok(Err(reason)) => Err(EccFailure::Rejected(reason)),
+        Err(_) => Err(EccFailure::Panicked),
+    }
+}
+
+/// Why `ecc` did not produce an executable.
+enum EccFailure {
+    /// The program was rejected, for the given reason.
+    Rejected(String),
+
+    /// The compiler crashed, which is never the right answer.
+    Panicked,
+
+    /// The program could not be copied into the work directory.
+    Io(String),
+}
+
+impl std::fmt::Display for EccFailure {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Rejected(reason) => write!(f, "{reason}"),
+            Self::Panicked => write!(f, "the compiler panicked"),
+            Self::Io(reason) => write!(f, "could not set up the program: {reason}"),
+        }
+    }
+}
+
+/// Compile a program with `gcc`, returning the path to the executable.
+fn compile_with_gcc(source: &Path, work_dir: &Path) -> Result<PathBuf, String> {
+    let executable = work_dir.join("gcc").join(source.file_stem().unwrap());
+    fs::create_dir_all(executable.parent().unwrap()).map_err(|e| e.to_string())?;
+
+    let status = Command::new("gcc")
+        .arg("-w")
+        .arg("-o")
+        .arg(&executable)
+        .arg(source)
+        .status()
+        .map_err(|e| format!("could not run gcc: {e}"))?;
+
+    if status.success() {
+        Ok(executable)
+    } else {
+        Err("gcc could not compile it either".to_string())
+    }
+}
+
+/// Run an executable, returning its exit code.
+fn exit_code(executable: &Path) -> Result<Option<i32>, String> {
+    Command::new(executable)
+        .status()
+        .map(|status| status.code())
+        .map_err(|e| format!("could not run '{}': {e}", executable.display()))
+}
+
+/// Check that a valid program compiles and behaves the way it does with `gcc`.
+fn check_valid(source: &Path, work_dir: &Path) -> Result<(), String> {
+    let expected = exit_code(&compile_with_gcc(source, work_dir)?)?;
+    let executable = compile_with_ecc(source, work_dir).map_err(|e| e.to_string())?;
+    let actual = exit_code(&executable)?;
+
+    if expected == actual {
+        Ok(())
+    } else {
+        Err(format!("expected exit code {expected:?}, got {actual:?}"))
+    }
+}
+
+/// Check that an invalid program is rejected without crashing the compiler.
+fn check_invalid(source: &Path, work_dir: &Path) -> Result<(), String> {
+    match compile_with_ecc(source, work_dir) {
+        Ok(_) => Err("the program was accepted".to_string()),
+        Err(EccFailure::Rejected(_)) => Ok(()),
+        Err(e) => Err(e.to_string()),
+    }
+}
+
+/// Report an error with the command line and exit.
+fn fail(program_name: &str, message: &str) -> ! {
+    eprintln!("{program_name}: error: {message}");
+    std::process::exit(2);
+}
@@ -0,0 +1,248 @@
+//! Compilation databases, the `compile_commands.json` files that tools like `clangd` read.
+//!
+//! A database lists every translation unit of a project along with the command that compiles it.
+//! `ecc` can build every file in a database using the flags recorded for it, and can add the files
+//! it compiles to a database so that editors know how they are built.
+//!
+//! The commands in a database were usually recorded for some other compiler, so only the flags
+//! that `ecc` understands are taken from them, and everything else is ignored.
+
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+
+use serde_json::{Value, json};
+
+use crate::lint;
+use crate::options::{CompileOptions, Emit, Extensions, OptLevel, Standard};
+use crate::{CompileError, CompileResult};
+
+/// One entry of a compilation database.
+#[derive(Clone, PartialEq, Eq, Debug)]
+pub struct CompileCommand {
+    /// The working directory of the compilation. Relative paths are relative to it.
+    pub directory: PathBuf,
+
+    /// The source file being compiled.
+    pub file: PathBuf,
+
+    /// The command line, starting with the compiler itself.
+    pub arguments: Vec<String>,
+}
+
+impl CompileCommand {
+    /// Where the source file is, with the working directory taken into account.
+    pub fn source_path(&self) -> PathBuf {
+        self.directory.join(&self.file)
+    }
+
+    /// Work out the options the recorded command line asks for.
+    ///
+    /// Both `ecc`'s own flags and the common ones of other compilers are understood. Flags that
+    /// do not mean anything to `ecc`, like `-c` or `-D`, are skipped, along with the values of the
+    /// ones that take an argument.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use ecc::compdb::CompileCommand;
+    /// use ecc::options::{Emit, OptLevel};
+    ///
+    /// let command = CompileCommand {
+    ///     directory: "/project".into(),
+    ///     file: "main.c".into(),
+    ///     arguments: ["cc", "-O2", "-DDEBUG", "-Iinclude", "-S", "-o", "main.s", "main.c"]
+    ///         .map(String::from)
+    ///         .to_vec(),
+    /// };
+    /// let options = command.options();
+    ///
+    /// assert_eq!(options.opt_level, OptLevel::O2);
+    /// assert_eq!(options.emit, Emit::Assembly);
+    /// assert_eq!(options.include_paths, ["/project/include"].map(std::path::PathBuf::from));
+    /// ```
+    pub fn options(&self) -> CompileOptions {
+        let mut options = CompileOptions::new();
+        let mut args = self.arguments.iter().skip(1);
+
+        while let Some(arg) = args.next() {
+            match arg.as_str() {
+                "-O" | "-O1" | "-Os" | "-Og" => options.opt_level = OptLevel::O1,
+                "-O0" => options.opt_level = OptLevel::O0,
+                "-O2" | "-O3" | "-Ofast" => options.opt_level = OptLevel::O2,
+                "-g" => options.debug_info = true,
+                "-fverbose-asm" => options.source_comments = true,
+                "-fsanitize=undefined" => options.ub_checks = true,
+                "-pg" => options.profiling = true,
+                "-nostdlib" => options.freestanding = true,
+                "-fstack-usage" => options.stack_usage = true,
+                "-fwrapv" => options.wrapv = true,
+                "-fno-wrapv" => options.wrapv = false,
+                "-trigraphs" => options.trigraphs = true,
+                "-pedantic" | "-Wpedantic" => options.extensions = Extensions::Warn,
+                "-pedantic-errors" => options.extensions = Extensions::Deny,
+                "-Wall" => options.warnings.all = true,
+                "-Werror" => options.warnings.as_errors = true,
+                "-S" | "--emit=asm" => options.emit = Emit::Assembly,
+                "--emit=exe" => options.emit = Emit::Executable,
+                "-I" => {
+                    if let Some(dir) = args.next() {
+                        options.include_paths.push(self.directory.join(dir));
+                    }
+                }
+                "--sysroot" => {
+                    if let Some(dir) = args.next() {
+                        options.sysroot = Some(self.directory.join(dir));
+                    }
+                }
+                "-o" | "-D" | "-U" | "-include" | "-isystem" | "-x" | "-MF" | "-MT" => {
+                    args.next();
+                }
+                _ => {
+                    if let Some(dir) = arg.strip_prefix("-I") {
+                        options.include_paths.push(self.directory.join(dir));
+                    } else if let Some(dir) = arg.strip_prefix("--sysroot=") {
+                        options.sysroot = Some(self.directory.join(dir));
+                    } else if let Some(name) = arg.strip_prefix("-std=") {
+                        if let Some(standard) = Standard::from_name(name) {
+                            options.standard = standard;
+                        }
+                    } else if let Some(name) = arg.strip_prefix("-W") {
+                        let (name, on) = match name.strip_prefix("no-") {
+                            Some(name) => (name, false),
+                            None => (name, true),
+                        };
+
+                        if lint::find(name).is_some() {
+                            options.warnings.overrides.push((name.to_owned(), on));
+                        }
+                    }
+                }
+            }
+        }
+
+        options
+    }
+
+    fn from_json(entry: &Value) -> Option<Self> {
+        let directory = PathBuf::from(entry.get("directory")?.as_str()?);
+        let file = PathBuf::from(entry.get("file")?.as_str()?);
+
+        let arguments = match entry.get("arguments") {
+            Some(arguments) => arguments
+                .as_array()?
+                .iter()
+                .map(|arg| arg.as_str().map(str::to_owned))
+                .collect::<Option<_>>()?,
+            None => split_command(entry.get("command")?.as_str()?),
+        };
+
+        Some(Self {
+            directory,
+            file,
+            arguments,
+        })
+    }
+
+    fn to_json(&self) -> Value {
+        json!({
+            "directory": self.directory.display().to_string(),
+            "file": self.file.display().to_string(),
+            "arguments": self.arguments,
+        })
+    }
+}
+
+/// Read every entry of a compilation database.
+pub fn read(path: impl AsRef<Path>) -> CompileResult<Vec<CompileCommand>> {
+    let path = path.as_ref();
+    let text = fs::read_to_string(path)?;
+    let entries = serde_json::from_str::<Value>(&text).map_err(|e| invalid(path, e))?;
+
+    entries
+        .as_array()
+        .ok_or_else(|| invalid(path, "expected a list of entries"))?
+        .iter()
+        .map(|entry| {
+            CompileCommand::from_json(entry).ok_or_else(|| {
+                invalid(path, "every entry needs a directory, a file, and a command")
+            })
+        })
+        .collect()
+}
+
+/// Add an entry to a compilation database, creating the database if it does not exist yet.
+///
+/// An entry that was already there for the same file is replaced, so compiling a file again keeps
+/// the database up to date instead of growing it.
+pub fn record(path: impl AsRef<Path>, command: &CompileCommand) -> CompileResult<()> {
+    let path = path.as_ref();
+    let mut commands = if path.exists() {
+        read(path)?
+    } else {
+        Vec::new()
+    };
+
+    commands.retain(|existing| existing.source_path() != command.source_path());
+    commands.push(command.clone());
+
+    let entries: Vec<_> = commands.iter().map(CompileCommand::to_json).collect();
+    let mut text = serde_json::to_string_pretty(&entries).map_err(|e| invalid(path, e))?;
+    text.push('\n');
+    fs::write(path, text)?;
+
+    Ok(())
+}
+
+/// Make the error for a database that could not be understood.
+fn invalid(path: &Path, reason: impl std::fmt::Display) -> CompileError {
+    CompileError::Io(io::Error::new(
+        io::ErrorKind::InvalidData,
+        format!(
+            "'{}' is not a valid compilation database: {reason}",
+            path.display()
+        ),
+    ))
+}
+
+/// Split a command line into arguments the way a shell would, as far as quotes and backslashes
+/// go.
+fn split_command(command: &str) -> Vec<String> {
+    let mut arguments = Vec::new();
+    let mut current = String::new();
+    let mut in_argument = false;
+    let mut quote = None;
+    let mut chars = command.chars();
+
+    while let Some(c) = chars.next() {
+        match (c, quote) {
+            ('\\', Some('\'')) => current.push(c),
+            ('\\', _) => {
+                current.extend(chars.next());
+                in_argument = true;
+            }
+            (c, Some(q)) if c == q => quote = None,
+            (c, Some(_)) => current.push(c),
+            ('"' | '\'', None) => {
+                quote = Some(c);
+                in_argument = true;
+            }
+            (c, None) if c.is_whitespace() => {
+                if in_argument {
+                    arguments.push(std::mem::take(&mut current));
+                    in_argument = false;
+                }
+            }
+            (c, None) => {
+                current.push(c);
+                in_argument = true;
+            }
+        }
+    }
+
+    if in_argument {
+        arguments.push(current);
+    }
+
+    arguments
+}
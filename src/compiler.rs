@@ -1,6 +1,33 @@
-use std::fmt::Write;
+pub mod asm;
+pub mod cfg;
+pub mod passes;
+pub mod verify;
+
+use std::io;
 
 use crate::ast;
+use crate::diagnostics::{self, LineMap};
+use crate::hooks::Hooks;
+use crate::options::OptLevel;
+use crate::sema;
+use crate::span::Span;
+use crate::types::Type;
+use asm::{Condition, Imm, Mem, Operand, Reg, Size};
+
+const AL: Operand = Operand::Reg(Reg::Ax, Size::Byte);
+const EAX: Operand = Operand::Reg(Reg::Ax, Size::Long);
+const ECX: Operand = Operand::Reg(Reg::Cx, Size::Long);
+const EDX: Operand = Operand::Reg(Reg::Dx, Size::Long);
+const EDI: Operand = Operand::Reg(Reg::Di, Size::Long);
+const RSI: Operand = Operand::Reg(Reg::Si, Size::Quad);
+const RBP: Operand = Operand::Reg(Reg::Bp, Size::Quad);
+const RSP: Operand = Operand::Reg(Reg::Sp, Size::Quad);
+
+/// The routine that undefined behavior checks jump to, to print what went wrong and abort.
+const UB_REPORT: &str = ".Lubsan_report";
+
+/// Where a program without the C library starts running.
+const START: &str = "_start";
 
 /// Compile a program to assembly.
 ///
@@ -13,22 +40,93 @@ pub fn compile_ast(program: ast::Program) -> String {
     compiler.finish()
 }
 
-macro_rules! writeln_unwrap {
-    ($dst:expr, $($arg:tt)*) => {
-        writeln!($dst, $($arg)*).unwrap()
-    }
-}
-
 /// The compiler.
 ///
 /// This class is responsible for turining an abstract syntax tree into
-/// assembly.
+/// assembly. Every node it is given is appended to the same list of [`asm::Line`]s, so code can be
+/// generated incrementally, e.g. one function at a time.
+///
+/// # Examples
+///
+/// ```
+/// use ecc::compiler::Compiler;
+/// use ecc::{lexer, parser};
+///
+/// let mut compiler = Compiler::new();
+/// for source in ["int one(void) { return 1; }", "int two(void) { return 2; }"] {
+///     let tokens = lexer::tokenize(source).unwrap();
+///     compiler.compile_function(parser::parse_function(tokens).unwrap());
+/// }
+///
+/// assert!(compiler.get_code().contains("one:"));
+/// assert!(compiler.get_code().contains("two:"));
+/// ```
+#[derive(Default)]
 pub struct Compiler {
-    assembly: String,
+    assembly: Vec<asm::Line>,
+
+    /// The source code being compiled, if the generated code should say where in it each
+    /// instruction came from.
+    source: Option<Source>,
+
+    /// Whether to say where the code came from with debug information.
+    debug_info: bool,
+
+    /// The files that `.loc` directives have referred to so far. File `n` is at index `n - 1`.
+    ///
+    /// There is more than one if `#line` directives moved the source into other files.
+    debug_files: Vec<String>,
+
+    /// Whether to say where the code came from by quoting the source in comments.
+    source_comments: bool,
+
+    /// The last line of the source that was quoted in a comment.
+    last_commented_line: Option<usize>,
+
+    /// How many bytes the current function has pushed onto the stack.
+    ///
+    /// The SysV ABI wants `%rsp` to be a multiple of 16 at every `call`, and the return address
+    /// pushed by the caller leaves it 8 bytes off on entry. Knowing the depth is what lets a call
+    /// be padded to line it back up.
+    stack_depth: usize,
+
+    /// Whether the function being compiled returns `bool`, whose values have to be 0 or 1.
+    returns_bool: bool,
+
+    /// How hard to try to make the generated code fast.
+    opt_level: OptLevel,
+
+    /// Whether to check for undefined behavior when the program runs.
+    ub_checks: bool,
+
+    /// Whether signed arithmetic is defined to wrap around on overflow.
+    wrapv: bool,
+
+    /// Whether every function calls `mcount` when it starts, for `gprof`.
+    profiling: bool,
+
+    /// Whether the program brings its own `_start`, because it is linked without the C library.
+    freestanding: bool,
+
+    /// What each undefined behavior check reports when it fails, in the order they were
+    /// generated.
+    ub_reports: Vec<String>,
+
+    /// How many of the reports the code they jump to has been compiled for.
+    ub_reports_compiled: usize,
+}
+
+/// The source code being compiled, and the name of the file it came from.
+struct Source {
+    file_name: String,
+    text: String,
+
+    /// Where the lines of the text are, which every instruction's location is looked up in.
+    lines: LineMap,
 }
 
 impl Compiler {
-    /// Create a new compiler with empty assembly buffer.
+    /// Create a new compiler that has not generated anything yet.
     ///
     /// # Examples
     ///
@@ -37,16 +135,242 @@ impl Compiler {
     ///
     /// let compiler = Compiler::new();
     ///
-    /// assert_eq!(compiler.get_code(), String::new());
+    /// assert!(compiler.lines().is_empty());
     /// ```
-    fn new() -> Self {
+    pub fn new() -> Self {
         Self {
-            assembly: String::new(),
+            assembly: Vec::new(),
+            source: None,
+            debug_info: false,
+            debug_files: Vec::new(),
+            source_comments: false,
+            last_commented_line: None,
+            stack_depth: 0,
+            returns_bool: false,
+            opt_level: OptLevel::O0,
+            ub_checks: false,
+            wrapv: false,
+            profiling: false,
+            freestanding: false,
+            ub_reports: Vec::new(),
+            ub_reports_compiled: 0,
         }
     }
 
-    fn finish(self) -> String {
-        self.assembly
+    /// Set how hard to try to make the generated code fast.
+    pub fn with_opt_level(mut self, opt_level: OptLevel) -> Self {
+        self.opt_level = opt_level;
+        self
+    }
+
+    /// Generate debug information, so that debuggers can step through the C code.
+    ///
+    /// The generated code says which line of the source each statement came from, using the
+    /// assembler's `.file` and `.loc` directives. The assembler turns those into DWARF line
+    /// tables, which is all that `gdb` needs to show where a program is. Lines that a `#line`
+    /// directive moved are given the line and file it says they are from.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use ecc::compiler::Compiler;
+    /// use ecc::{lexer, parser};
+    ///
+    /// let source = "int main(void) {\n    return 0;\n}\n";
+    /// let program = parser::parse_token_stream(lexer::tokenize(source).unwrap()).unwrap();
+    ///
+    /// let mut compiler = Compiler::new().with_debug_info("main.c", source);
+    /// compiler.compile_program(program);
+    ///
+    /// assert!(compiler.get_code().starts_with("\t.file 1 \"main.c\"\n"));
+    /// assert!(compiler.get_code().contains("\t.loc 1 2 12\n"));
+    /// ```
+    pub fn with_debug_info(mut self, file_name: &str, source: &str) -> Self {
+        self.emit_directive(asm::Directive::File {
+            number: 1,
+            name: file_name.to_owned(),
+        });
+        self.debug_files.push(file_name.to_owned());
+        self.debug_info = true;
+        self.with_source(file_name, source)
+    }
+
+    /// Quote the line of source code that each group of instructions came from in a comment
+    /// above it, which makes the assembly much easier to follow.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use ecc::compiler::Compiler;
+    /// use ecc::{lexer, parser};
+    ///
+    /// let source = "int main(void) {\n    return 0;\n}\n";
+    /// let program = parser::parse_token_stream(lexer::tokenize(source).unwrap()).unwrap();
+    ///
+    /// let mut compiler = Compiler::new().with_source_comments("main.c", source);
+    /// compiler.compile_program(program);
+    ///
+    /// assert!(compiler.get_code().contains("\t# main.c:2: return 0;\n"));
+    /// ```
+    pub fn with_source_comments(mut self, file_name: &str, source: &str) -> Self {
+        self.source_comments = true;
+        self.with_source(file_name, source)
+    }
+
+    /// Check for undefined behavior while the program runs, like `-fsanitize=undefined`.
+    ///
+    /// Arithmetic that overflows and division by zero make the program print where in the source
+    /// it happened and abort, instead of silently giving a wrong answer or crashing with
+    /// `SIGFPE`.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use ecc::compiler::Compiler;
+    /// use ecc::{lexer, parser};
+    ///
+    /// let source = "int main(void) { return 1 / 0; }";
+    /// let program = parser::parse_token_stream(lexer::tokenize(source).unwrap()).unwrap();
+    ///
+    /// let mut compiler = Compiler::new().with_ub_checks("main.c", source);
+    /// compiler.compile_program(program);
+    /// let assembly = compiler.finish();
+    ///
+    /// assert!(assembly.contains("main.c:1:25: runtime error: division by zero"));
+    /// ```
+    pub fn with_ub_checks(mut self, file_name: &str, source: &str) -> Self {
+        self.ub_checks = true;
+        self.with_source(file_name, source)
+    }
+
+    /// Define signed arithmetic to wrap around on overflow, like `-fwrapv`.
+    ///
+    /// The generated instructions wrap anyway, so this only matters to what the compiler may
+    /// assume about them: overflow is no longer undefined, so it is not checked for, and any
+    /// optimization has to keep the wrapped answer.
+    pub fn with_wrapv(mut self, wrapv: bool) -> Self {
+        self.wrapv = wrapv;
+        self
+    }
+
+    /// Generate a program that runs without the C library, like `-nostdlib`.
+    ///
+    /// The C library normally provides `_start`, which sets up the process and calls `main`. A
+    /// freestanding program gets a minimal one of its own instead, which only calls `main` and
+    /// exits with the status it returns. Only a program that defines `main` gets one, so this
+    /// should only be set when building an executable.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use ecc::compiler::Compiler;
+    /// use ecc::{lexer, parser};
+    ///
+    /// let source = "int main(void) { return 0; }";
+    /// let program = parser::parse_token_stream(lexer::tokenize(source).unwrap()).unwrap();
+    ///
+    /// let mut compiler = Compiler::new().with_freestanding(true);
+    /// compiler.compile_program(program);
+    ///
+    /// assert!(compiler.get_code().contains("_start:\n\tcall\tmain\n"));
+    ///
+    /// let source = "int helper(void) { return 0; }";
+    /// let program = parser::parse_token_stream(lexer::tokenize(source).unwrap()).unwrap();
+    ///
+    /// let mut compiler = Compiler::new().with_freestanding(true);
+    /// compiler.compile_program(program);
+    ///
+    /// assert!(!compiler.get_code().contains("_start"));
+    /// ```
+    pub fn with_freestanding(mut self, freestanding: bool) -> Self {
+        self.freestanding = freestanding;
+        self
+    }
+
+    /// Make every function call `mcount` when it starts, like `-pg`.
+    ///
+    /// `mcount` finds out which function called the one being profiled through the frame
+    /// pointer, so each function also sets up `%rbp`. The program has to be linked with `-pg` too,
+    /// which adds the code that writes `gmon.out` when it exits.
+    pub fn with_profiling(mut self, profiling: bool) -> Self {
+        self.profiling = profiling;
+        self
+    }
+
+    fn with_source(mut self, file_name: &str, source: &str) -> Self {
+        self.source = Some(Source {
+            file_name: file_name.to_owned(),
+            text: source.to_owned(),
+            lines: LineMap::new(source),
+        });
+        self
+    }
+
+    /// Get the assembly generated so far, printed.
+    pub fn get_code(&self) -> String {
+        asm::print(&self.assembly)
+    }
+
+    /// Get the assembly generated so far.
+    pub fn lines(&self) -> &[asm::Line] {
+        &self.assembly
+    }
+
+    /// Run some passes over the assembly generated so far, with hooks to call after each one.
+    ///
+    /// The code that undefined behavior checks jump to is compiled first, so that the passes can
+    /// see where every jump goes.
+    pub fn run_passes(&mut self, passes: &mut passes::PassManager, hooks: &mut dyn Hooks) {
+        self.compile_ub_reports();
+        passes.run(&mut self.assembly, hooks);
+    }
+
+    /// Consume the compiler, returning the assembly it generated.
+    ///
+    /// The assembly ends by saying that the program does not need an executable stack. Without
+    /// that, the linker assumes it does, warns about it, and marks the stack executable.
+    pub fn finish(mut self) -> String {
+        self.finish_code();
+        asm::print(&self.assembly)
+    }
+
+    /// Consume the compiler, writing the assembly it generated to the given sink.
+    ///
+    /// This is otherwise the same as [`Compiler::finish`], but the text is streamed to the sink
+    /// line by line instead of being collected into a string first.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use ecc::compiler::Compiler;
+    /// use ecc::{lexer, parser};
+    ///
+    /// let tokens = lexer::tokenize("int main(void) { return 0; }").unwrap();
+    /// let mut compiler = Compiler::new();
+    /// compiler.compile_function(parser::parse_function(tokens).unwrap());
+    ///
+    /// let mut out = Vec::new();
+    /// compiler.finish_into(&mut out).unwrap();
+    /// assert!(String::from_utf8(out).unwrap().contains("main:"));
+    /// ```
+    pub fn finish_into(mut self, out: impl io::Write) -> io::Result<()> {
+        self.finish_code();
+        asm::write(&self.assembly, out)
+    }
+
+    /// Add what has to come after every function, and make sure that the result is valid.
+    fn finish_code(&mut self) {
+        self.compile_ub_reports();
+        self.emit_directive(asm::Directive::NoExecStack);
+
+        if cfg!(debug_assertions)
+            && let Err(e) = verify::verify(&self.assembly)
+        {
+            panic!(
+                "generated invalid code:\n{}",
+                diagnostics::render(&e, "<generated>", "")
+            );
+        }
     }
 
     /// Compile a program.
@@ -54,22 +378,94 @@ impl Compiler {
     /// This method compiles a C program down to assembly. For now, a program consists of a single
     /// function declaration. That function's name can be anything and the compiler will work, but
     /// if the name is not `main` then the linker will complain.
-    fn compile_program(&mut self, program: ast::Program) {
+    pub fn compile_program(&mut self, program: ast::Program) {
+        // Without a `main` to call, the program is a library, and whatever links it brings its
+        // own `_start`.
+        let defines_main = program.function.name == "main";
         self.compile_function(program.function);
+        if self.freestanding && defines_main {
+            self.compile_start();
+        }
+    }
+
+    /// Compile the `_start` that a program without the C library begins at.
+    ///
+    /// It calls `main`, and exits with whatever that returns. The stack is 16-byte aligned when
+    /// the kernel starts the program, so the `call` leaves it the way `main` expects.
+    fn compile_start(&mut self) {
+        self.emit_directive(asm::Directive::Text);
+        self.emit_directive(asm::Directive::Globl(START.to_owned()));
+        self.emit_directive(asm::Directive::Function(START.to_owned()));
+        self.assembly.push(asm::Line::Label(START.to_owned()));
+        self.emit(asm::Instruction::Call("main".to_owned()));
+
+        // exit(status)
+        self.emit(asm::Instruction::Mov(EAX, EDI));
+        self.emit(asm::Instruction::Mov(Operand::Imm(Imm(60)), EAX));
+        self.emit(asm::Instruction::Syscall);
+
+        // The system call does not return.
+        self.emit(asm::Instruction::Ud2);
+        self.emit_directive(asm::Directive::Size(START.to_owned()));
     }
 
     /// Compile a function.
     ///
-    /// This method generates a global instruction to expose the function's label to the linker.
-    /// Then it generates a label corresponding to the function's name, followed by all of the code
-    /// for the function.
-    fn compile_function(&mut self, function: ast::Function) {
-        writeln_unwrap!(self.assembly, "\t.globl {}", function.name);
-        writeln_unwrap!(self.assembly, "{}:", function.name);
+    /// This method generates a global instruction to expose the function's label to the linker,
+    /// unless the function is `static`, and aligns it if it asked to be. Then it generates a label
+    /// corresponding to the function's name, followed by all of the code for the function. The
+    /// function is put in the `.text` section and marked as a function of the size it ended up
+    /// being, which is what tools like `objdump` and `perf` go by.
+    pub fn compile_function(&mut self, function: ast::Function) {
+        // A `static inline` function only has to exist for the calls that were not inlined, and
+        // functions cannot be called yet.
+        if function.is_static && function.inline {
+            return;
+        }
 
-        for statement in function.body {
+        self.emit_directive(asm::Directive::Text);
+        if let Some(align) = function.aligned {
+            self.emit_directive(asm::Directive::Align(align));
+        }
+        if !function.is_static {
+            self.emit_directive(asm::Directive::Globl(function.name.clone()));
+        }
+        self.emit_directive(asm::Directive::Function(function.name.clone()));
+        self.assembly.push(asm::Line::Label(function.name.clone()));
+        self.compile_location(function.name_span);
+
+        if self.profiling {
+            self.compile_push(Reg::Bp);
+            self.emit(asm::Instruction::Mov(RSP, RBP));
+            self.emit(asm::Instruction::Call("mcount".to_owned()));
+        }
+
+        self.returns_bool = function.return_type == Type::Bool;
+        let falls_off_end = function.can_fall_off_end();
+
+        // Nothing after the first `return` can run. Compiling it anyway would leave code after the
+        // last `ret`, and an `asm` statement there would run off the end of the function.
+        let reachable = function
+            .body
+            .iter()
+            .position(|statement| matches!(statement, ast::Statement::Return(_)))
+            .map_or(function.body.len(), |i| i + 1);
+        for statement in function.body.into_iter().take(reachable) {
             self.compile_statement(statement);
         }
+
+        // Reaching the closing brace of `main` returns 0. Any other function returns garbage,
+        // which the `return-type` lint warns about, but it still must not run into whatever code
+        // comes next.
+        if falls_off_end {
+            if function.name == "main" && function.return_type == Type::Int {
+                self.compile_integer(0);
+            }
+            self.compile_epilogue();
+        }
+
+        self.emit_directive(asm::Directive::Size(function.name));
+        self.stack_depth = 0;
     }
 
     /// Compile a statement.
@@ -77,8 +473,54 @@ impl Compiler {
     /// This method compiles a single statement. The generated assembly (obviously) depends greatly
     /// on the type of statement being compiled.
     fn compile_statement(&mut self, statement: ast::Statement) {
+        match &statement {
+            ast::Statement::Return(Some(expr)) => self.compile_location(expr.span()),
+            ast::Statement::Return(None) => {}
+            ast::Statement::Asm { span, .. } => self.compile_location(*span),
+        }
+
         match statement {
             ast::Statement::Return(expr) => self.compile_return(expr),
+            ast::Statement::Asm { template, .. } => {
+                self.assembly.push(asm::Line::Verbatim(template));
+            }
+        }
+    }
+
+    /// Say which line and column of the source the following instructions came from, if the
+    /// generated code should.
+    fn compile_location(&mut self, span: Span) {
+        let Some(source) = &self.source else {
+            return;
+        };
+
+        let (physical_line, _) = source.lines.line_and_column(&source.text, span.start);
+        let (file, line, column) = source.lines.presumed_location(&source.text, span.start);
+        let file_name = file.unwrap_or(&source.file_name).to_owned();
+
+        // A line that spans several groups of instructions is only quoted above the first.
+        if self.source_comments && self.last_commented_line != Some(physical_line) {
+            let text = source.lines.line(&source.text, physical_line);
+            let comment = format!("{file_name}:{line}: {}", text.trim());
+            self.assembly.push(asm::Line::Comment(comment));
+            self.last_commented_line = Some(physical_line);
+        }
+
+        if self.debug_info {
+            let file = match self.debug_files.iter().position(|name| *name == file_name) {
+                Some(index) => index + 1,
+                None => {
+                    self.debug_files.push(file_name.clone());
+                    let number = self.debug_files.len();
+                    self.emit_directive(asm::Directive::File {
+                        number,
+                        name: file_name,
+                    });
+                    number
+                }
+            };
+
+            self.emit_directive(asm::Directive::Loc { file, line, column });
         }
     }
 
@@ -86,11 +528,31 @@ impl Compiler {
     ///
     /// This method generates a `movl` instruction, passing it the integer return value and the
     /// `%eax` register. In the future, functions will be able to return more than 32-bit integer
-    /// values, but this is how it is for now. Naturally, the return statement is terminated with a
-    /// `ret` instruction.
-    fn compile_return(&mut self, return_value: ast::Expr) {
-        self.compile_expression(return_value);
-        writeln_unwrap!(self.assembly, "\tret");
+    /// values, but this is how it is for now. A `void` function returns nothing, so `%eax` is left
+    /// alone. A `bool` function returns 1 for any value other than 0. Naturally, the return
+    /// statement is terminated with a `ret` instruction.
+    fn compile_return(&mut self, return_value: Option<ast::Expr>) {
+        if let Some(return_value) = return_value {
+            self.compile_expression(return_value);
+            if self.returns_bool {
+                self.emit(asm::Instruction::Cmp(Operand::Imm(Imm(0)), EAX));
+                self.emit(asm::Instruction::Mov(Operand::Imm(Imm(0)), EAX));
+                self.emit(asm::Instruction::Setne(AL));
+            }
+        }
+        self.compile_epilogue();
+    }
+
+    /// Return from the current function, taking down the frame that profiling sets up.
+    fn compile_epilogue(&mut self) {
+        let frame = if self.profiling { 8 } else { 0 };
+        debug_assert_eq!(self.stack_depth, frame, "the stack must be empty at `ret`");
+
+        // Not `compile_pop`, since code after the `ret` still has the frame.
+        if self.profiling {
+            self.emit(asm::Instruction::Pop(RBP));
+        }
+        self.emit(asm::Instruction::Ret);
     }
 
     /// Compile an expression.
@@ -101,13 +563,28 @@ impl Compiler {
     /// register to use for operations.
     fn compile_expression(&mut self, expr: ast::Expr) {
         match expr {
-            ast::Expr::Integer(value) => self.compile_integer(value),
-            ast::Expr::Unary { operator, operand } => self.compile_unary(operator, *operand),
+            ast::Expr::Integer { value, .. } => self.compile_integer(value),
+            ast::Expr::Boolean { value, .. } => self.compile_integer(value as i32),
+            ast::Expr::Unary {
+                operator,
+                operand,
+                span,
+            } => self.compile_unary(operator, *operand, span),
             ast::Expr::Binary {
                 operator,
                 left,
                 right,
-            } => self.compile_binary(operator, *left, *right),
+                span,
+            } => self.compile_binary(operator, *left, *right, span),
+            ast::Expr::Generic {
+                controlling,
+                associations,
+                ..
+            } => {
+                let selected = sema::select(&controlling, &associations)
+                    .expect("semantic analysis makes sure that an association is selected");
+                self.compile_expression(selected.clone());
+            }
         }
     }
 
@@ -115,54 +592,283 @@ impl Compiler {
     ///
     /// This method loads the given integer into the `eax` register.
     fn compile_integer(&mut self, value: i32) {
-        writeln_unwrap!(self.assembly, "\tmovl\t${}, %eax", value);
+        self.emit(asm::Instruction::Mov(Operand::Imm(Imm(value)), EAX));
     }
 
     /// Compile a unary expression.
-    fn compile_unary(&mut self, op: ast::UnaryOp, operand: ast::Expr) {
+    fn compile_unary(&mut self, op: ast::UnaryOp, operand: ast::Expr, span: Span) {
         self.compile_expression(operand);
 
         use ast::UnaryOp as UO; // 'Sco Ducks
 
         match op {
-            UO::Compliment => writeln_unwrap!(self.assembly, "\tnot\t%eax"),
-            UO::NegateArith => writeln_unwrap!(self.assembly, "\tneg\t%eax"),
+            UO::Compliment => self.emit(asm::Instruction::Not(EAX)),
+            UO::NegateArith => {
+                self.emit(asm::Instruction::Neg(EAX));
+                self.compile_overflow_check(span, "negation overflowed");
+            }
             UO::NegateLogical => {
-                writeln_unwrap!(self.assembly, "\tcmpl\t$0, %eax");
-                writeln_unwrap!(self.assembly, "\tmovl\t$0, %eax");
-                writeln_unwrap!(self.assembly, "\tsete\t%al");
+                self.emit(asm::Instruction::Cmp(Operand::Imm(Imm(0)), EAX));
+                self.emit(asm::Instruction::Mov(Operand::Imm(Imm(0)), EAX));
+                self.emit(asm::Instruction::Sete(AL));
             }
         }
     }
 
-    fn compile_binary(&mut self, op: ast::BinaryOp, left: ast::Expr, right: ast::Expr) {
+    fn compile_binary(&mut self, op: ast::BinaryOp, left: ast::Expr, right: ast::Expr, span: Span) {
+        // `lea` does not say whether it overflowed, so it cannot be used when that is checked.
+        let checks_overflow = self.ub_checks && !self.wrapv;
+        let (left, right) = if self.opt_level >= OptLevel::O1 && !checks_overflow {
+            match self.compile_lea(op, left, right) {
+                Ok(()) => return,
+                Err(operands) => operands,
+            }
+        } else {
+            (left, right)
+        };
+
         // Stupid hack because I can't link in 32 bit mode for some reason...
         self.compile_expression(right);
-        writeln_unwrap!(self.assembly, "\tpush\t%rax");
+        self.compile_push(Reg::Ax);
         self.compile_expression(left);
-        writeln_unwrap!(self.assembly, "\tpop\t%rcx");
+        self.compile_pop(Reg::Cx);
 
         use ast::BinaryOp as BO;
 
         match op {
-            BO::Plus => writeln_unwrap!(self.assembly, "\taddl\t%ecx, %eax"),
-            BO::Times => writeln_unwrap!(self.assembly, "\timul\t%ecx, %eax"),
-            BO::Minus => writeln_unwrap!(self.assembly, "\tsubl\t%ecx, %eax"),
+            BO::Plus => self.emit(asm::Instruction::Add(ECX, EAX)),
+            BO::Times => self.emit(asm::Instruction::Imul(ECX, EAX)),
+            BO::Minus => self.emit(asm::Instruction::Sub(ECX, EAX)),
+            BO::Divide | BO::Mod => self.compile_division_checks(span),
+        }
+
+        match op {
+            BO::Plus | BO::Times | BO::Minus => {
+                let message = format!(
+                    "signed integer overflow in `{}`",
+                    ast::pretty::binary_spelling(op)
+                );
+                self.compile_overflow_check(span, &message);
+            }
 
             // The `idivl` instruction interprets `[edx:eax]` as a single 64-bit register, so
             // before we execute `idivl`, we must sign extend `eax` into `edx`, which is exactly
             // what `cdq` does.
             BO::Divide => {
-                writeln_unwrap!(self.assembly, "\tcdq");
-                writeln_unwrap!(self.assembly, "\tidivl\t%ecx");
+                self.emit(asm::Instruction::Cdq);
+                self.emit(asm::Instruction::Idiv(ECX));
             }
 
             // Same story here as for division, but the remainder is stored in the `edx` register.
             BO::Mod => {
-                writeln_unwrap!(self.assembly, "\tcdq");
-                writeln_unwrap!(self.assembly, "\tidivl\t%ecx");
-                writeln_unwrap!(self.assembly, "\tmovl\t%edx, %eax");
+                self.emit(asm::Instruction::Cdq);
+                self.emit(asm::Instruction::Idiv(ECX));
+                self.emit(asm::Instruction::Mov(EDX, EAX));
+            }
+        }
+    }
+
+    /// Check that `%eax` can be divided by `%ecx`, if undefined behavior is being checked for.
+    ///
+    /// Dividing by zero is undefined, and so is dividing `INT_MIN` by -1, since the answer is one
+    /// more than `INT_MAX`.
+    fn compile_division_checks(&mut self, span: Span) {
+        if !self.ub_checks {
+            return;
+        }
+
+        self.emit(asm::Instruction::Test(ECX, ECX));
+        self.compile_ub_check(Condition::Equal, span, "division by zero");
+
+        let divisible = format!(".Ldivisible{}", self.ub_reports.len());
+        self.emit(asm::Instruction::Cmp(Operand::Imm(Imm(-1)), ECX));
+        self.emit(asm::Instruction::Jcc(
+            Condition::NotEqual,
+            divisible.clone(),
+        ));
+        self.emit(asm::Instruction::Cmp(Operand::Imm(Imm(i32::MIN)), EAX));
+        self.compile_ub_check(
+            Condition::Equal,
+            span,
+            "division of -2147483648 by -1 overflowed",
+        );
+        self.assembly.push(asm::Line::Label(divisible));
+    }
+
+    /// Check that the last arithmetic did not overflow, if that is undefined behavior and it is
+    /// being checked for.
+    fn compile_overflow_check(&mut self, span: Span, what: &str) {
+        if !self.wrapv {
+            self.compile_ub_check(Condition::Overflow, span, what);
+        }
+    }
+
+    /// Jump to a report of undefined behavior if the flags meet the condition, if undefined
+    /// behavior is being checked for.
+    fn compile_ub_check(&mut self, condition: Condition, span: Span, what: &str) {
+        let Some(source) = self.source.as_ref().filter(|_| self.ub_checks) else {
+            return;
+        };
+
+        let (file, line, column) = source.lines.presumed_location(&source.text, span.start);
+        let report = format!(
+            "{}:{}:{}: runtime error: {}\n",
+            file.unwrap_or(&source.file_name),
+            line,
+            column,
+            what
+        );
+
+        let label = format!(".Lubsan{}", self.ub_reports.len());
+        self.ub_reports.push(report);
+        self.emit(asm::Instruction::Jcc(condition, label));
+    }
+
+    /// Compile the code that the undefined behavior checks jump to.
+    ///
+    /// Each check gets a few instructions that pick its report and go on to a routine shared by
+    /// all of them, which writes the report to stderr with a system call and calls `abort`. The
+    /// stack could be in any state at that point, so it is lined up for the call first.
+    fn compile_ub_reports(&mut self) {
+        let first = self.ub_reports_compiled;
+        if first == self.ub_reports.len() {
+            return;
+        }
+
+        self.emit_directive(asm::Directive::Text);
+        for (i, report) in self.ub_reports.iter().enumerate().skip(first) {
+            let line = asm::Line::Label(format!(".Lubsan{i}"));
+            let message = Operand::Label(format!(".Lubsan_message{i}"));
+            let length = Operand::Imm(Imm(report.len() as i32));
+            self.assembly.extend([
+                line,
+                asm::Line::Instruction(asm::Instruction::Lea(message, RSI)),
+                asm::Line::Instruction(asm::Instruction::Mov(length, EDX)),
+                asm::Line::Instruction(asm::Instruction::Jmp(UB_REPORT.to_owned())),
+            ]);
+        }
+
+        // write(2, message, length)
+        if first == 0 {
+            self.assembly.push(asm::Line::Label(UB_REPORT.to_owned()));
+            self.emit(asm::Instruction::Mov(Operand::Imm(Imm(2)), EDI));
+            self.emit(asm::Instruction::Mov(Operand::Imm(Imm(1)), EAX));
+            self.emit(asm::Instruction::Syscall);
+            self.emit(asm::Instruction::And(Operand::Imm(Imm(-16)), RSP));
+            self.emit(asm::Instruction::Call("abort".to_owned()));
+        }
+
+        self.emit_directive(asm::Directive::Rodata);
+        for i in first..self.ub_reports.len() {
+            self.assembly
+                .push(asm::Line::Label(format!(".Lubsan_message{i}")));
+            self.emit_directive(asm::Directive::Ascii(self.ub_reports[i].clone()));
+        }
+        self.ub_reports_compiled = self.ub_reports.len();
+    }
+
+    /// Compile a binary expression with a single `lea`, if it has the right shape.
+    ///
+    /// `lea` adds two registers, one of them multiplied by 1, 2, 4 or 8, in one instruction. That
+    /// covers `x + y * 4`, and also `x * 3`, `x * 5` and `x * 9` as `x + x * 2`, `x + x * 4` and
+    /// `x + x * 8`. Expressions of any other shape are given back untouched.
+    fn compile_lea(
+        &mut self,
+        op: ast::BinaryOp,
+        left: ast::Expr,
+        right: ast::Expr,
+    ) -> Result<(), (ast::Expr, ast::Expr)> {
+        match op {
+            ast::BinaryOp::Plus => {
+                let (base, scaled) = if scale_of(&right).is_some() {
+                    (left, right)
+                } else if scale_of(&left).is_some() {
+                    (right, left)
+                } else {
+                    return Err((left, right));
+                };
+
+                let Some(scale) = scale_of(&scaled) else {
+                    unreachable!()
+                };
+                let ast::Expr::Binary { left: index, .. } = scaled else {
+                    unreachable!()
+                };
+
+                self.compile_expression(*index);
+                self.compile_push(Reg::Ax);
+                self.compile_expression(base);
+                self.compile_pop(Reg::Cx);
+                self.compile_lea_of(Reg::Ax, Reg::Cx, scale);
+                Ok(())
             }
+
+            ast::BinaryOp::Times => match right {
+                ast::Expr::Integer {
+                    value: value @ (3 | 5 | 9),
+                    ..
+                } => {
+                    self.compile_expression(left);
+                    self.compile_lea_of(Reg::Ax, Reg::Ax, value as u8 - 1);
+                    Ok(())
+                }
+                right => Err((left, right)),
+            },
+
+            _ => Err((left, right)),
         }
     }
+
+    /// Compute `base + index * scale` into `%eax`.
+    fn compile_lea_of(&mut self, base: Reg, index: Reg, scale: u8) {
+        let address = Mem {
+            base,
+            index: Some((index, scale)),
+            offset: 0,
+        };
+        self.emit(asm::Instruction::Lea(Operand::Mem(address), EAX));
+    }
+
+    /// Push a 64-bit register onto the stack, keeping track of how deep the stack is.
+    fn compile_push(&mut self, register: Reg) {
+        self.emit(asm::Instruction::Push(Operand::Reg(register, Size::Quad)));
+        self.stack_depth += 8;
+    }
+
+    /// Pop the top of the stack into a 64-bit register.
+    fn compile_pop(&mut self, register: Reg) {
+        self.emit(asm::Instruction::Pop(Operand::Reg(register, Size::Quad)));
+        self.stack_depth -= 8;
+    }
+
+    /// Add an instruction to the generated code.
+    fn emit(&mut self, instruction: asm::Instruction) {
+        self.assembly.push(asm::Line::Instruction(instruction));
+    }
+
+    /// Add a directive to the generated code.
+    fn emit_directive(&mut self, directive: asm::Directive) {
+        self.assembly.push(asm::Line::Directive(directive));
+    }
+}
+
+/// If the expression is `x * 1`, `x * 2`, `x * 4` or `x * 8`, the constant that `x` is multiplied
+/// by, which is what `lea` can scale a register by.
+fn scale_of(expr: &ast::Expr) -> Option<u8> {
+    let ast::Expr::Binary {
+        operator: ast::BinaryOp::Times,
+        right,
+        ..
+    } = expr
+    else {
+        return None;
+    };
+
+    match **right {
+        ast::Expr::Integer {
+            value: value @ (1 | 2 | 4 | 8),
+            ..
+        } => Some(value as u8),
+        _ => None,
+    }
 }
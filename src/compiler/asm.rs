@@ -0,0 +1,455 @@
+//! The assembly that the compiler generates, before it is printed.
+//!
+//! Code generation produces a list of [`Line`]s instead of text, so that the generated code can
+//! still be looked at and rewritten, e.g. by a peephole pass, before [`print`] turns it into the
+//! AT&T syntax that `as` reads. Another printer could just as well produce Intel syntax, or an
+//! object file.
+//!
+//! The lines are the compiler's intermediate representation, and `--emit=ir` prints them before
+//! anything is added for the assembler's sake. [`parse`] reads the text back, so the code that a
+//! pass works on can be written by hand.
+
+mod parse;
+
+use std::{fmt, io};
+
+pub use parse::parse;
+
+/// One line of assembly.
+#[derive(Clone, PartialEq, Eq, Debug)]
+pub enum Line {
+    /// A label, which names the address of whatever comes after it.
+    Label(String),
+
+    /// A directive, which tells the assembler something rather than producing code.
+    Directive(Directive),
+
+    /// A machine instruction.
+    Instruction(Instruction),
+
+    /// A comment, for whoever reads the assembly.
+    Comment(String),
+
+    /// Assembly from an `asm` statement, copied out as it is.
+    ///
+    /// Nothing looks inside it, so a pass cannot know what it does and must not move code across
+    /// it. [`parse`] cannot tell it apart from generated code, and reads it back as whatever it
+    /// looks like.
+    Verbatim(String),
+}
+
+/// An assembler directive.
+#[derive(Clone, PartialEq, Eq, Debug)]
+pub enum Directive {
+    /// `.file number "name"`: a source file that `.loc` directives refer to by its number.
+    File { number: usize, name: String },
+
+    /// `.loc file line column`: where in the source the following instructions came from.
+    Loc {
+        file: usize,
+        line: usize,
+        column: usize,
+    },
+
+    /// `.text`: put what follows in the section for code.
+    Text,
+
+    /// `.section .rodata`: put what follows in the section for constants.
+    Rodata,
+
+    /// `.ascii "text"`: the bytes of some text, without a terminating zero.
+    Ascii(String),
+
+    /// `.align bytes`: start what follows at the next multiple of the given power of two.
+    Align(u32),
+
+    /// `.globl name`: make a symbol visible to the linker.
+    Globl(String),
+
+    /// `.type name, @function`: mark a symbol as a function.
+    Function(String),
+
+    /// `.size name, .-name`: record the size of a symbol, which ends here.
+    Size(String),
+
+    /// `.section .note.GNU-stack,"",@progbits`: say that the stack need not be executable.
+    NoExecStack,
+}
+
+/// A machine instruction.
+///
+/// Instructions with two operands take them in the order AT&T syntax writes them: the source
+/// first and the destination second.
+#[derive(Clone, PartialEq, Eq, Debug)]
+pub enum Instruction {
+    Mov(Operand, Operand),
+    Push(Operand),
+    Pop(Operand),
+    Add(Operand, Operand),
+    Sub(Operand, Operand),
+    Imul(Operand, Operand),
+    Neg(Operand),
+    Not(Operand),
+    Cmp(Operand, Operand),
+    Sete(Operand),
+    Setne(Operand),
+    Cdq,
+    Idiv(Operand),
+    Lea(Operand, Operand),
+    Test(Operand, Operand),
+    And(Operand, Operand),
+    Jmp(String),
+    Jcc(Condition, String),
+    Call(String),
+    Syscall,
+    Ret,
+
+    /// Crash the program with `SIGILL`, which marks code that is never meant to be reached.
+    Ud2,
+}
+
+/// A condition that a conditional jump tests the flags for.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum Condition {
+    /// The last comparison found its operands equal, or the last result was zero.
+    Equal,
+
+    /// The opposite of [`Condition::Equal`].
+    NotEqual,
+
+    /// The last signed arithmetic overflowed.
+    Overflow,
+}
+
+impl Instruction {
+    /// How the instruction is spelled in AT&T syntax.
+    fn mnemonic(&self) -> &'static str {
+        match self {
+            Self::Lea(_, Operand::Reg(_, Size::Quad)) => "leaq",
+            Self::And(_, Operand::Reg(_, Size::Quad)) => "andq",
+            Self::Mov(_, Operand::Reg(_, Size::Quad)) => "movq",
+            Self::Mov(..) => "movl",
+            Self::Push(_) => "push",
+            Self::Pop(_) => "pop",
+            Self::Add(..) => "addl",
+            Self::Sub(..) => "subl",
+            Self::Imul(..) => "imul",
+            Self::Neg(_) => "neg",
+            Self::Not(_) => "not",
+            Self::Cmp(..) => "cmpl",
+            Self::Sete(_) => "sete",
+            Self::Setne(_) => "setne",
+            Self::Cdq => "cdq",
+            Self::Idiv(_) => "idivl",
+            Self::Lea(..) => "leal",
+            Self::Test(..) => "testl",
+            Self::And(..) => "andl",
+            Self::Jmp(_) => "jmp",
+            Self::Jcc(Condition::Equal, _) => "je",
+            Self::Jcc(Condition::NotEqual, _) => "jne",
+            Self::Jcc(Condition::Overflow, _) => "jo",
+            Self::Call(_) => "call",
+            Self::Syscall => "syscall",
+            Self::Ret => "ret",
+            Self::Ud2 => "ud2",
+        }
+    }
+
+    /// The instruction's operands, in the order they are written.
+    fn operands(&self) -> Vec<&Operand> {
+        match self {
+            Self::Mov(src, dst)
+            | Self::Add(src, dst)
+            | Self::Sub(src, dst)
+            | Self::Imul(src, dst)
+            | Self::Cmp(src, dst)
+            | Self::Lea(src, dst)
+            | Self::Test(src, dst)
+            | Self::And(src, dst) => vec![src, dst],
+            Self::Push(operand)
+            | Self::Pop(operand)
+            | Self::Neg(operand)
+            | Self::Not(operand)
+            | Self::Sete(operand)
+            | Self::Setne(operand)
+            | Self::Idiv(operand) => vec![operand],
+            Self::Cdq
+            | Self::Syscall
+            | Self::Ret
+            | Self::Ud2
+            | Self::Jmp(_)
+            | Self::Jcc(..)
+            | Self::Call(_) => Vec::new(),
+        }
+    }
+
+    /// The label that the instruction jumps to or calls, if any.
+    fn target(&self) -> Option<&str> {
+        match self {
+            Self::Jmp(label) | Self::Jcc(_, label) | Self::Call(label) => Some(label),
+            _ => None,
+        }
+    }
+}
+
+/// Something an instruction operates on.
+#[derive(Clone, PartialEq, Eq, Debug)]
+pub enum Operand {
+    /// A constant that is part of the instruction.
+    Imm(Imm),
+
+    /// The part of a register that holds a value of the given size.
+    Reg(Reg, Size),
+
+    /// A value in memory.
+    Mem(Mem),
+
+    /// The value at a label, which is found relative to the instruction pointer so that the code
+    /// works wherever it is loaded.
+    Label(String),
+}
+
+/// A constant that is part of an instruction, written `$value`.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub struct Imm(pub i32);
+
+/// A value in memory, written `offset(%base,%index,scale)`.
+///
+/// Its address is `base + index * scale + offset`. `lea` works the address out without loading
+/// anything, which makes it a handy way to do that arithmetic on values that are not addresses.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub struct Mem {
+    /// The register holding the address that the offset is from.
+    pub base: Reg,
+
+    /// A register to add to the base, and what to multiply it by first, which must be 1, 2, 4,
+    /// or 8.
+    pub index: Option<(Reg, u8)>,
+
+    /// How many bytes past the base address the value is.
+    pub offset: i32,
+}
+
+/// How many bytes of a register or of memory an operand is.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum Size {
+    Byte,
+    Word,
+    Long,
+    Quad,
+}
+
+impl Size {
+    /// Every size, from smallest to largest.
+    pub const ALL: [Self; 4] = [Self::Byte, Self::Word, Self::Long, Self::Quad];
+}
+
+/// A general purpose register.
+///
+/// Each register can be used as a whole or in part, and every part has its own name: `%rax` is all
+/// 64 bits, `%eax` the low 32, `%ax` the low 16, and `%al` the low 8. [`Reg::name`] picks the right
+/// one for the size of the value.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum Reg {
+    Ax,
+    Bx,
+    Cx,
+    Dx,
+    Si,
+    Di,
+    Bp,
+    Sp,
+    R8,
+    R9,
+    R10,
+    R11,
+    R12,
+    R13,
+    R14,
+    R15,
+}
+
+impl Reg {
+    /// Every register.
+    pub const ALL: [Self; 16] = [
+        Self::Ax,
+        Self::Bx,
+        Self::Cx,
+        Self::Dx,
+        Self::Si,
+        Self::Di,
+        Self::Bp,
+        Self::Sp,
+        Self::R8,
+        Self::R9,
+        Self::R10,
+        Self::R11,
+        Self::R12,
+        Self::R13,
+        Self::R14,
+        Self::R15,
+    ];
+
+    /// The name of the part of the register that holds a value of the given size, without the
+    /// `%`.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use ecc::compiler::asm::{Reg, Size};
+    ///
+    /// assert_eq!(Reg::Ax.name(Size::Byte), "al");
+    /// assert_eq!(Reg::Ax.name(Size::Long), "eax");
+    /// assert_eq!(Reg::R8.name(Size::Long), "r8d");
+    /// ```
+    pub fn name(self, size: Size) -> &'static str {
+        let [byte, word, long, quad] = match self {
+            Self::Ax => ["al", "ax", "eax", "rax"],
+            Self::Bx => ["bl", "bx", "ebx", "rbx"],
+            Self::Cx => ["cl", "cx", "ecx", "rcx"],
+            Self::Dx => ["dl", "dx", "edx", "rdx"],
+            Self::Si => ["sil", "si", "esi", "rsi"],
+            Self::Di => ["dil", "di", "edi", "rdi"],
+            Self::Bp => ["bpl", "bp", "ebp", "rbp"],
+            Self::Sp => ["spl", "sp", "esp", "rsp"],
+            Self::R8 => ["r8b", "r8w", "r8d", "r8"],
+            Self::R9 => ["r9b", "r9w", "r9d", "r9"],
+            Self::R10 => ["r10b", "r10w", "r10d", "r10"],
+            Self::R11 => ["r11b", "r11w", "r11d", "r11"],
+            Self::R12 => ["r12b", "r12w", "r12d", "r12"],
+            Self::R13 => ["r13b", "r13w", "r13d", "r13"],
+            Self::R14 => ["r14b", "r14w", "r14d", "r14"],
+            Self::R15 => ["r15b", "r15w", "r15d", "r15"],
+        };
+
+        match size {
+            Size::Byte => byte,
+            Size::Word => word,
+            Size::Long => long,
+            Size::Quad => quad,
+        }
+    }
+}
+
+/// Print assembly in AT&T syntax, one line of text per line.
+///
+/// # Examples
+///
+/// ```
+/// use ecc::compiler::asm::{self, Imm, Instruction, Line, Operand, Reg, Size};
+///
+/// let eax = Operand::Reg(Reg::Ax, Size::Long);
+/// let lines = [
+///     Line::Label("main".to_owned()),
+///     Line::Instruction(Instruction::Mov(Operand::Imm(Imm(0)), eax)),
+///     Line::Instruction(Instruction::Ret),
+/// ];
+///
+/// assert_eq!(asm::print(&lines), "main:\n\tmovl\t$0, %eax\n\tret\n");
+/// ```
+pub fn print(lines: &[Line]) -> String {
+    lines.iter().map(|line| format!("{line}\n")).collect()
+}
+
+/// Write assembly in AT&T syntax to the given sink, the same way [`print`] prints it.
+///
+/// Each line is written as soon as it is printed, so the whole text never has to be held in memory
+/// at once. A buffered sink, like [`io::BufWriter`], keeps that from taking a system call per line.
+///
+/// # Examples
+///
+/// ```
+/// use ecc::compiler::asm::{self, Instruction, Line};
+///
+/// let lines = [Line::Label("main".to_owned()), Line::Instruction(Instruction::Ret)];
+/// let mut out = Vec::new();
+/// asm::write(&lines, &mut out).unwrap();
+///
+/// assert_eq!(out, asm::print(&lines).as_bytes());
+/// ```
+pub fn write(lines: &[Line], mut out: impl io::Write) -> io::Result<()> {
+    for line in lines {
+        writeln!(out, "{line}")?;
+    }
+    Ok(())
+}
+
+impl fmt::Display for Line {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Label(name) => write!(f, "{name}:"),
+            Self::Directive(directive) => write!(f, "\t{directive}"),
+            Self::Instruction(instruction) => write!(f, "\t{instruction}"),
+            Self::Comment(text) => write!(f, "\t# {text}"),
+            Self::Verbatim(text) => write!(f, "\t{text}"),
+        }
+    }
+}
+
+impl fmt::Display for Directive {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::File { number, name } => {
+                let escaped = name.replace('\\', "\\\\").replace('"', "\\\"");
+                write!(f, ".file {number} \"{escaped}\"")
+            }
+            Self::Loc { file, line, column } => write!(f, ".loc {file} {line} {column}"),
+            Self::Text => write!(f, ".text"),
+            Self::Rodata => write!(f, ".section .rodata"),
+            Self::Ascii(text) => {
+                let escaped = text
+                    .replace('\\', "\\\\")
+                    .replace('"', "\\\"")
+                    .replace('\n', "\\n");
+                write!(f, ".ascii \"{escaped}\"")
+            }
+            Self::Align(bytes) => write!(f, ".align {bytes}"),
+            Self::Globl(name) => write!(f, ".globl {name}"),
+            Self::Function(name) => write!(f, ".type {name}, @function"),
+            Self::Size(name) => write!(f, ".size {name}, .-{name}"),
+            Self::NoExecStack => write!(f, ".section .note.GNU-stack,\"\",@progbits"),
+        }
+    }
+}
+
+impl fmt::Display for Instruction {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.mnemonic())?;
+
+        for (i, operand) in self.operands().into_iter().enumerate() {
+            let separator = if i == 0 { "\t" } else { ", " };
+            write!(f, "{separator}{operand}")?;
+        }
+
+        if let Some(label) = self.target() {
+            write!(f, "\t{label}")?;
+        }
+
+        Ok(())
+    }
+}
+
+impl fmt::Display for Operand {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Imm(Imm(value)) => write!(f, "${value}"),
+            Self::Reg(reg, size) => write!(f, "%{}", reg.name(*size)),
+            Self::Mem(mem) => write!(f, "{mem}"),
+            Self::Label(label) => write!(f, "{label}(%rip)"),
+        }
+    }
+}
+
+impl fmt::Display for Mem {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if self.offset != 0 {
+            write!(f, "{}", self.offset)?;
+        }
+
+        write!(f, "(%{}", self.base.name(Size::Quad))?;
+        if let Some((index, scale)) = self.index {
+            write!(f, ",%{},{scale}", index.name(Size::Quad))?;
+        }
+        write!(f, ")")
+    }
+}
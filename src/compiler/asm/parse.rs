@@ -0,0 +1,274 @@
+//! Reading assembly text back into [`Line`]s.
+//!
+//! Only what [`super::print`] writes is understood, which is enough to write the code that a
+//! pass is given by hand, instead of having to find C that compiles to it.
+
+use super::{Condition, Directive, Imm, Instruction, Line, Mem, Operand, Reg, Size};
+use crate::diagnostics::Diagnostic;
+use crate::span::Span;
+
+/// Parse assembly in the syntax that [`super::print`] writes.
+///
+/// # Examples
+///
+/// ```
+/// use ecc::compiler::asm::{self, Instruction, Line};
+///
+/// let lines = asm::parse("main:\n\tmovl\t$0, %eax\n\tret\n").unwrap();
+///
+/// assert_eq!(lines[0], Line::Label("main".to_owned()));
+/// assert_eq!(lines[2], Line::Instruction(Instruction::Ret));
+/// assert_eq!(asm::print(&lines), "main:\n\tmovl\t$0, %eax\n\tret\n");
+/// ```
+pub fn parse(text: &str) -> Result<Vec<Line>, Diagnostic> {
+    let mut lines = Vec::new();
+    let mut start = 0;
+
+    for line in text.split_inclusive('\n') {
+        let span = Span {
+            start,
+            end: start + line.trim_end().len(),
+        };
+        start += line.len();
+
+        let line = line.trim();
+        if line.is_empty() {
+            continue;
+        }
+
+        let parsed = parse_line(line).map_err(|message| {
+            Diagnostic::error(format!("{message} in `{line}`")).with_span(span)
+        })?;
+        lines.push(parsed);
+    }
+
+    Ok(lines)
+}
+
+fn parse_line(line: &str) -> Result<Line, String> {
+    if let Some(comment) = line.strip_prefix('#') {
+        Ok(Line::Comment(comment.trim_start().to_owned()))
+    } else if let Some(label) = line.strip_suffix(':') {
+        Ok(Line::Label(label.to_owned()))
+    } else if line.starts_with('.') {
+        parse_directive(line).map(Line::Directive)
+    } else {
+        parse_instruction(line).map(Line::Instruction)
+    }
+}
+
+fn parse_directive(line: &str) -> Result<Directive, String> {
+    let (name, rest) = line.split_once(char::is_whitespace).unwrap_or((line, ""));
+    let rest = rest.trim();
+
+    let directive = match name {
+        ".text" => Directive::Text,
+        ".section" if rest == ".rodata" => Directive::Rodata,
+        ".section" if rest == ".note.GNU-stack,\"\",@progbits" => Directive::NoExecStack,
+        ".file" => {
+            let (number, name) = rest.split_once(' ').ok_or("expected a file number")?;
+            Directive::File {
+                number: number.parse().map_err(|_| "expected a file number")?,
+                name: parse_string(name)?,
+            }
+        }
+        ".loc" => {
+            let numbers: Vec<_> = rest.split_whitespace().collect();
+            let [file, line, column] = numbers[..] else {
+                return Err("expected a file, a line, and a column".to_owned());
+            };
+            Directive::Loc {
+                file: file.parse().map_err(|_| "expected a file number")?,
+                line: line.parse().map_err(|_| "expected a line number")?,
+                column: column.parse().map_err(|_| "expected a column number")?,
+            }
+        }
+        ".ascii" => Directive::Ascii(parse_string(rest)?),
+        ".align" => Directive::Align(rest.parse().map_err(|_| "expected an alignment")?),
+        ".globl" => Directive::Globl(rest.to_owned()),
+        ".type" => {
+            let name = rest
+                .strip_suffix(", @function")
+                .ok_or("only functions can be given a type")?;
+            Directive::Function(name.to_owned())
+        }
+        ".size" => {
+            let (name, size) = rest.split_once(", ").ok_or("expected a size")?;
+            if size != format!(".-{name}") {
+                return Err("only sizes ending here are supported".to_owned());
+            }
+            Directive::Size(name.to_owned())
+        }
+        _ => return Err("unknown directive".to_owned()),
+    };
+
+    Ok(directive)
+}
+
+/// Parse a double quoted string, undoing the escapes that [`super::print`] adds.
+fn parse_string(text: &str) -> Result<String, String> {
+    let inner = text
+        .strip_prefix('"')
+        .and_then(|text| text.strip_suffix('"'))
+        .ok_or("expected a string")?;
+
+    let mut string = String::new();
+    let mut chars = inner.chars();
+    while let Some(c) = chars.next() {
+        string.push(match c {
+            '\\' => match chars.next() {
+                Some('n') => '\n',
+                Some(c @ ('\\' | '"')) => c,
+                _ => return Err("unknown escape in string".to_owned()),
+            },
+            c => c,
+        });
+    }
+
+    Ok(string)
+}
+
+fn parse_instruction(line: &str) -> Result<Instruction, String> {
+    let (mnemonic, rest) = line.split_once(char::is_whitespace).unwrap_or((line, ""));
+    let rest = rest.trim();
+
+    // Jumps and calls take a label rather than operands.
+    let target = || {
+        if rest.is_empty() {
+            Err("expected a label".to_owned())
+        } else {
+            Ok(rest.to_owned())
+        }
+    };
+    match mnemonic {
+        "jmp" => return Ok(Instruction::Jmp(target()?)),
+        "je" => return Ok(Instruction::Jcc(Condition::Equal, target()?)),
+        "jne" => return Ok(Instruction::Jcc(Condition::NotEqual, target()?)),
+        "jo" => return Ok(Instruction::Jcc(Condition::Overflow, target()?)),
+        "call" => return Ok(Instruction::Call(target()?)),
+        _ => {}
+    }
+
+    let mut operands = split_operands(rest)
+        .into_iter()
+        .map(parse_operand)
+        .collect::<Result<Vec<_>, _>>()?
+        .into_iter();
+    let count = operands.len();
+    let mut operand = || {
+        operands
+            .next()
+            .ok_or(format!("`{mnemonic}` needs more operands"))
+    };
+
+    let instruction = match mnemonic {
+        "movl" | "movq" => Instruction::Mov(operand()?, operand()?),
+        "push" => Instruction::Push(operand()?),
+        "pop" => Instruction::Pop(operand()?),
+        "addl" => Instruction::Add(operand()?, operand()?),
+        "subl" => Instruction::Sub(operand()?, operand()?),
+        "imul" => Instruction::Imul(operand()?, operand()?),
+        "neg" => Instruction::Neg(operand()?),
+        "not" => Instruction::Not(operand()?),
+        "cmpl" => Instruction::Cmp(operand()?, operand()?),
+        "sete" => Instruction::Sete(operand()?),
+        "setne" => Instruction::Setne(operand()?),
+        "cdq" => Instruction::Cdq,
+        "idivl" => Instruction::Idiv(operand()?),
+        "leal" | "leaq" => Instruction::Lea(operand()?, operand()?),
+        "testl" => Instruction::Test(operand()?, operand()?),
+        "andl" | "andq" => Instruction::And(operand()?, operand()?),
+        "syscall" => Instruction::Syscall,
+        "ret" => Instruction::Ret,
+        "ud2" => Instruction::Ud2,
+        _ => return Err(format!("unknown instruction `{mnemonic}`")),
+    };
+
+    if operand().is_ok() {
+        return Err(format!("`{mnemonic}` takes fewer than {count} operands"));
+    }
+
+    Ok(instruction)
+}
+
+/// Split operands at the commas that are not inside parentheses.
+fn split_operands(text: &str) -> Vec<&str> {
+    if text.is_empty() {
+        return Vec::new();
+    }
+
+    let mut operands = Vec::new();
+    let mut depth = 0;
+    let mut start = 0;
+    for (i, c) in text.char_indices() {
+        match c {
+            '(' => depth += 1,
+            ')' => depth -= 1,
+            ',' if depth == 0 => {
+                operands.push(text[start..i].trim());
+                start = i + 1;
+            }
+            _ => {}
+        }
+    }
+    operands.push(text[start..].trim());
+    operands
+}
+
+fn parse_operand(text: &str) -> Result<Operand, String> {
+    if let Some(value) = text.strip_prefix('$') {
+        let value = value.parse().map_err(|_| "expected an integer")?;
+        return Ok(Operand::Imm(Imm(value)));
+    }
+
+    if text.starts_with('%') {
+        let (reg, size) = parse_register(text)?;
+        return Ok(Operand::Reg(reg, size));
+    }
+
+    let (offset, address) = text.split_once('(').ok_or("expected an operand")?;
+    let address = address.strip_suffix(')').ok_or("expected `)`")?;
+
+    if address == "%rip" {
+        return Ok(Operand::Label(offset.to_owned()));
+    }
+
+    let offset = if offset.is_empty() {
+        0
+    } else {
+        offset.parse().map_err(|_| "expected an offset")?
+    };
+
+    let parts: Vec<_> = address.split(',').collect();
+    let (base, index) = match parts[..] {
+        [base] => (base, None),
+        [base, index, scale] => {
+            let scale = scale.parse().map_err(|_| "expected a scale")?;
+            (base, Some((parse_address_register(index)?, scale)))
+        }
+        _ => return Err("expected a base, and maybe an index and a scale".to_owned()),
+    };
+
+    Ok(Operand::Mem(Mem {
+        base: parse_address_register(base)?,
+        index,
+        offset,
+    }))
+}
+
+fn parse_register(text: &str) -> Result<(Reg, Size), String> {
+    let name = text.strip_prefix('%').ok_or("expected a register")?;
+    Reg::ALL
+        .iter()
+        .flat_map(|&reg| Size::ALL.map(|size| (reg, size)))
+        .find(|&(reg, size)| reg.name(size) == name)
+        .ok_or(format!("unknown register `{text}`"))
+}
+
+/// Parse a register used in an address, which is always the whole register.
+fn parse_address_register(text: &str) -> Result<Reg, String> {
+    match parse_register(text)? {
+        (reg, Size::Quad) => Ok(reg),
+        _ => Err(format!("addresses need 64-bit registers, not `{text}`")),
+    }
+}
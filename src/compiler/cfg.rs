@@ -0,0 +1,297 @@
+//! Control flow graphs of the generated code.
+//!
+//! A function's code is split into basic blocks: runs of lines that are always executed from the
+//! first to the last, since only the first can be jumped to and only the last can jump. The
+//! edges between blocks say where control can go next. Analyses like liveness work block by
+//! block along the edges, and a block without predecessors is dead code.
+//!
+//! This is also what `--emit=cfg-dot` draws:
+//!
+//! ```text
+//! ecc --emit=cfg-dot program.c | dot -Tsvg > program.svg
+//! ```
+
+use std::fmt::Write;
+use std::ops::Range;
+
+use super::asm::{Directive, Instruction, Line};
+
+/// The control flow graph of one function.
+#[derive(Clone, PartialEq, Eq, Debug)]
+pub struct Cfg {
+    /// The name of the function.
+    pub name: String,
+
+    blocks: Vec<BasicBlock>,
+}
+
+/// A run of lines that always executes from start to finish.
+#[derive(Clone, PartialEq, Eq, Debug)]
+pub struct BasicBlock {
+    /// The lines of the block, starting with its label if it has one.
+    pub lines: Vec<Line>,
+
+    successors: Vec<usize>,
+    predecessors: Vec<usize>,
+}
+
+impl BasicBlock {
+    /// The label that jumps to the block go to, if it has one.
+    pub fn label(&self) -> Option<&str> {
+        match self.lines.first() {
+            Some(Line::Label(label)) => Some(label),
+            _ => None,
+        }
+    }
+}
+
+/// Build the control flow graph of every function in some assembly.
+///
+/// A function is everything between its `.type name, @function` and `.size name` directives, so
+/// code outside of any function, like the reports that undefined behavior checks jump to, is left
+/// out. A jump out of the function is treated like returning.
+///
+/// # Examples
+///
+/// ```
+/// use ecc::compiler::{Compiler, cfg};
+/// use ecc::{lexer, parser};
+///
+/// let source = "int main(void) { return 1 / 1; }";
+/// let program = parser::parse_token_stream(lexer::tokenize(source).unwrap()).unwrap();
+///
+/// let mut compiler = Compiler::new().with_ub_checks("main.c", source);
+/// compiler.compile_program(program);
+/// let graphs = cfg::build(compiler.lines());
+///
+/// // The function is split after each check, and where the division is known to be safe.
+/// let main = &graphs[0];
+/// assert_eq!(main.name, "main");
+/// assert_eq!(main.len(), 4);
+/// assert_eq!(main.successors(0).collect::<Vec<_>>(), [1]);
+/// assert_eq!(main.predecessors(3).collect::<Vec<_>>(), [1, 2]);
+/// ```
+pub fn build(lines: &[Line]) -> Vec<Cfg> {
+    functions(lines)
+        .into_iter()
+        .map(|(name, range)| Cfg::build(&name, &lines[range]))
+        .collect()
+}
+
+/// Find every function in some assembly, with the range of lines that is its code.
+///
+/// The range leaves out the `.type` and `.size` directives around the code.
+pub fn functions(lines: &[Line]) -> Vec<(String, Range<usize>)> {
+    let mut functions = Vec::new();
+    let mut start = None;
+
+    for (i, line) in lines.iter().enumerate() {
+        match line {
+            Line::Directive(Directive::Function(name)) => start = Some((name, i + 1)),
+            Line::Directive(Directive::Size(name)) => {
+                if let Some((function, start)) = start.take()
+                    && function == name
+                {
+                    functions.push((name.clone(), start..i));
+                }
+            }
+            _ => {}
+        }
+    }
+
+    functions
+}
+
+impl Cfg {
+    /// Build the control flow graph of a function's code.
+    pub fn build(name: &str, lines: &[Line]) -> Self {
+        let mut blocks: Vec<BasicBlock> = Vec::new();
+        let mut ends_block = true;
+
+        for line in lines {
+            if ends_block || matches!(line, Line::Label(_)) {
+                blocks.push(BasicBlock {
+                    lines: Vec::new(),
+                    successors: Vec::new(),
+                    predecessors: Vec::new(),
+                });
+            }
+
+            blocks.last_mut().unwrap().lines.push(line.clone());
+            ends_block = matches!(
+                line,
+                Line::Instruction(
+                    Instruction::Jmp(_)
+                        | Instruction::Jcc(..)
+                        | Instruction::Ret
+                        | Instruction::Ud2
+                )
+            );
+        }
+
+        let mut cfg = Self {
+            name: name.to_owned(),
+            blocks,
+        };
+        cfg.connect();
+        cfg
+    }
+
+    /// Work out the edges between the blocks.
+    fn connect(&mut self) {
+        for from in 0..self.blocks.len() {
+            let last = self.blocks[from]
+                .lines
+                .iter()
+                .rev()
+                .find_map(|line| match line {
+                    Line::Instruction(instruction) => Some(instruction),
+                    _ => None,
+                })
+                .cloned();
+
+            let falls_through = from + 1 < self.blocks.len();
+            let successors = match last {
+                Some(Instruction::Ret | Instruction::Ud2) => Vec::new(),
+                Some(Instruction::Jmp(label)) => self.find(&label).into_iter().collect(),
+                Some(Instruction::Jcc(_, label)) => {
+                    let mut successors: Vec<_> = self.find(&label).into_iter().collect();
+                    if falls_through {
+                        successors.push(from + 1);
+                    }
+                    successors
+                }
+                _ if falls_through => vec![from + 1],
+                _ => Vec::new(),
+            };
+
+            for &to in &successors {
+                if !self.blocks[to].predecessors.contains(&from) {
+                    self.blocks[to].predecessors.push(from);
+                }
+            }
+            self.blocks[from].successors = successors;
+        }
+
+        for block in &mut self.blocks {
+            block.predecessors.sort_unstable();
+        }
+    }
+
+    /// Find the block that a label starts.
+    fn find(&self, label: &str) -> Option<usize> {
+        self.blocks
+            .iter()
+            .position(|block| block.label() == Some(label))
+    }
+
+    /// Every block, in the order they appear in the code. The first one is where the function
+    /// starts.
+    pub fn blocks(&self) -> &[BasicBlock] {
+        &self.blocks
+    }
+
+    /// The number of blocks.
+    pub fn len(&self) -> usize {
+        self.blocks.len()
+    }
+
+    /// Return true if the function has no code at all.
+    pub fn is_empty(&self) -> bool {
+        self.blocks.is_empty()
+    }
+
+    /// The blocks that control can go to after the given one.
+    pub fn successors(&self, block: usize) -> impl Iterator<Item = usize> + '_ {
+        self.blocks[block].successors.iter().copied()
+    }
+
+    /// The blocks that control can come to the given one from.
+    pub fn predecessors(&self, block: usize) -> impl Iterator<Item = usize> + '_ {
+        self.blocks[block].predecessors.iter().copied()
+    }
+
+    /// The most bytes of stack the function ever uses, counting the return address its caller
+    /// pushed, like `gcc -fstack-usage` does.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use ecc::compiler::{asm, cfg};
+    ///
+    /// let lines = asm::parse(
+    ///     "\t.type f, @function\nf:\n\tpush\t%rax\n\tpush\t%rax\n\tpop\t%rcx\n\tpop\t%rcx\n\tret\n\t.size f, .-f\n",
+    /// );
+    /// let graphs = cfg::build(&lines.unwrap());
+    ///
+    /// assert_eq!(graphs[0].stack_usage(), 24);
+    /// ```
+    pub fn stack_usage(&self) -> usize {
+        // Every path into a block has the same depth in valid code, so the first one found is
+        // used.
+        let mut depths: Vec<Option<usize>> = vec![None; self.blocks.len()];
+        let mut work = Vec::new();
+        if !self.blocks.is_empty() {
+            depths[0] = Some(0);
+            work.push(0);
+        }
+
+        let mut deepest = 0;
+        while let Some(block) = work.pop() {
+            let mut depth = depths[block].unwrap();
+            for line in &self.blocks[block].lines {
+                match line {
+                    Line::Instruction(Instruction::Push(_)) => depth += 8,
+                    Line::Instruction(Instruction::Pop(_)) => depth = depth.saturating_sub(8),
+                    _ => {}
+                }
+                deepest = deepest.max(depth);
+            }
+
+            for next in self.successors(block) {
+                if depths[next].is_none() {
+                    depths[next] = Some(depth);
+                    work.push(next);
+                }
+            }
+        }
+
+        deepest + 8
+    }
+}
+
+/// Draw control flow graphs as a `digraph`, with each function in its own cluster.
+pub fn dot(graphs: &[Cfg]) -> String {
+    let mut output = String::from("digraph cfg {\n    node [shape=box, fontname=monospace];\n");
+
+    for (f, cfg) in graphs.iter().enumerate() {
+        writeln!(output, "    subgraph cluster_{f} {{").unwrap();
+        writeln!(output, "        label=\"{}\";", escape(&cfg.name)).unwrap();
+
+        for (b, block) in cfg.blocks.iter().enumerate() {
+            // `\l` ends a line and aligns it to the left.
+            let text: String = block
+                .lines
+                .iter()
+                .map(|line| format!("{}\\l", escape(&line.to_string().trim().replace('\t', " "))))
+                .collect();
+            writeln!(output, "        f{f}b{b} [label=\"{text}\"];").unwrap();
+        }
+
+        for (b, block) in cfg.blocks.iter().enumerate() {
+            for to in &block.successors {
+                writeln!(output, "        f{f}b{b} -> f{f}b{to};").unwrap();
+            }
+        }
+
+        writeln!(output, "    }}").unwrap();
+    }
+
+    output.push_str("}\n");
+    output
+}
+
+/// Escape text for a double quoted GraphViz string.
+fn escape(text: &str) -> String {
+    text.replace('\\', "\\\\").replace('"', "\\\"")
+}
@@ -0,0 +1,404 @@
+//! Optimization passes over the generated code.
+//!
+//! A pass is anything that implements [`Pass`], and [`PassManager`] runs a list of them in order.
+//! The passes built into the compiler are in [`PASSES`]. Which of them run depends on the
+//! optimization level, unless they are picked by name with `--passes=fold,dce`.
+//! `--print-after-all` prints the code after each pass, which shows exactly what each one did.
+//!
+//! Passes from outside the compiler are added to the pipeline with
+//! [`Hooks::before_passes`](crate::hooks::Hooks::before_passes).
+
+use std::fmt;
+
+use super::asm::{Condition, Imm, Instruction, Line, Mem, Operand, Reg};
+use super::{EAX, ECX, EDX, asm, cfg, diagnostics, verify};
+use crate::hooks::Hooks;
+use crate::options::OptLevel;
+
+/// A rewrite of the generated code.
+///
+/// A pass has to leave the code valid, as checked by [`verify::verify`], and has to keep the
+/// program doing what it did before.
+///
+/// # Examples
+///
+/// ```
+/// use ecc::CompileOptions;
+/// use ecc::compiler::asm::Line;
+/// use ecc::compiler::passes::{Pass, PassManager};
+/// use ecc::diagnostics::Diagnostic;
+/// use ecc::hooks::Hooks;
+///
+/// /// Put a comment at the top of the code.
+/// struct Signature;
+///
+/// impl Pass for Signature {
+///     fn name(&self) -> &str {
+///         "signature"
+///     }
+///
+///     fn run(&mut self, lines: &mut Vec<Line>) {
+///         lines.insert(0, Line::Comment("signed".to_owned()));
+///     }
+/// }
+///
+/// struct AddSignature;
+///
+/// impl Hooks for AddSignature {
+///     fn before_passes(&mut self, passes: &mut PassManager) {
+///         passes.add(Signature);
+///     }
+/// }
+///
+/// let mut diagnostics: Vec<Diagnostic> = Vec::new();
+/// let assembly = ecc::compile_source_with_hooks(
+///     "int main(void) { return 0; }",
+///     &CompileOptions::new(),
+///     &mut diagnostics,
+///     &mut AddSignature,
+/// )
+/// .unwrap();
+///
+/// assert!(assembly.starts_with("\t# signed\n"));
+/// ```
+pub trait Pass {
+    /// The name of the pass, which `--print-after-all` prints.
+    fn name(&self) -> &str;
+
+    /// Rewrite the code.
+    fn run(&mut self, lines: &mut Vec<Line>);
+}
+
+/// A pass built into the compiler.
+#[derive(Clone, Copy, Debug)]
+pub struct BuiltinPass {
+    /// The name used to pick the pass, as in `--passes=name`.
+    pub name: &'static str,
+
+    /// A short description of what the pass does.
+    pub description: &'static str,
+
+    /// The lowest optimization level that runs the pass.
+    pub opt_level: OptLevel,
+
+    run: fn(&mut Vec<Line>),
+}
+
+impl Pass for BuiltinPass {
+    fn name(&self) -> &str {
+        self.name
+    }
+
+    fn run(&mut self, lines: &mut Vec<Line>) {
+        (self.run)(lines);
+    }
+}
+
+/// Every pass built into the compiler, in the order they run.
+pub const PASSES: &[BuiltinPass] = &[
+    BuiltinPass {
+        name: "fold",
+        description: "work out arithmetic on constants while compiling",
+        opt_level: OptLevel::O1,
+        run: fold,
+    },
+    BuiltinPass {
+        name: "dce",
+        description: "delete code that can never run",
+        opt_level: OptLevel::O1,
+        run: eliminate_dead_code,
+    },
+];
+
+/// Find a built-in pass by name.
+///
+/// # Examples
+///
+/// ```
+/// use ecc::compiler::passes;
+///
+/// assert!(passes::find("fold").is_some());
+/// assert!(passes::find("no-such-pass").is_none());
+/// ```
+pub fn find(name: &str) -> Option<&'static BuiltinPass> {
+    PASSES.iter().find(|pass| pass.name == name)
+}
+
+/// Runs passes over generated code, one after another.
+///
+/// # Examples
+///
+/// ```
+/// use ecc::compiler::asm;
+/// use ecc::compiler::passes::PassManager;
+///
+/// let mut lines = asm::parse(
+///     "main:\n\tmovl\t$2, %eax\n\tpush\t%rax\n\tmovl\t$3, %eax\n\tpop\t%rcx\n\taddl\t%ecx, %eax\n\tret\n",
+/// )
+/// .unwrap();
+/// PassManager::with_names(["fold"]).run(&mut lines, &mut ());
+///
+/// assert_eq!(asm::print(&lines), "main:\n\tmovl\t$5, %eax\n\tret\n");
+/// ```
+#[derive(Default)]
+pub struct PassManager {
+    passes: Vec<Box<dyn Pass>>,
+    print_after_all: bool,
+}
+
+impl PassManager {
+    /// Create a pass manager that runs no passes at all.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Create a pass manager that runs every built-in pass the optimization level calls for.
+    pub fn for_opt_level(opt_level: OptLevel) -> Self {
+        let mut manager = Self::new();
+        for pass in PASSES.iter().filter(|pass| pass.opt_level <= opt_level) {
+            manager.add(*pass);
+        }
+        manager
+    }
+
+    /// Create a pass manager that runs the named built-in passes, in the order given. Names that
+    /// are not passes are ignored.
+    pub fn with_names<S: AsRef<str>>(names: impl IntoIterator<Item = S>) -> Self {
+        let mut manager = Self::new();
+        for pass in names.into_iter().filter_map(|name| find(name.as_ref())) {
+            manager.add(*pass);
+        }
+        manager
+    }
+
+    /// Set whether to print the code to stderr after every pass.
+    pub fn with_print_after_all(mut self, print_after_all: bool) -> Self {
+        self.print_after_all = print_after_all;
+        self
+    }
+
+    /// Add a pass to the end of the pipeline.
+    pub fn add<P: Pass + 'static>(&mut self, pass: P) {
+        self.passes.push(Box::new(pass));
+    }
+
+    /// Add a pass to the pipeline, so that it runs at the given position.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the position is past the end of the pipeline.
+    pub fn insert<P: Pass + 'static>(&mut self, index: usize, pass: P) {
+        self.passes.insert(index, Box::new(pass));
+    }
+
+    /// Find where in the pipeline the pass with the given name runs.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use ecc::compiler::passes::{self, PassManager};
+    /// use ecc::options::OptLevel;
+    ///
+    /// let mut manager = PassManager::for_opt_level(OptLevel::O2);
+    /// assert_eq!(manager.position("dce"), Some(1));
+    ///
+    /// // Run constant folding again before dead code is deleted.
+    /// manager.insert(1, *passes::find("fold").unwrap());
+    /// assert_eq!(manager.names().collect::<Vec<_>>(), ["fold", "fold", "dce"]);
+    /// ```
+    pub fn position(&self, name: &str) -> Option<usize> {
+        self.passes.iter().position(|pass| pass.name() == name)
+    }
+
+    /// The names of the passes that will run, in order.
+    pub fn names(&self) -> impl Iterator<Item = &str> {
+        self.passes.iter().map(|pass| pass.name())
+    }
+
+    /// Run every pass over the code, calling [`Hooks::after_pass`] after each one.
+    ///
+    /// In debug builds, the code is verified after every pass, so a pass that breaks it is caught
+    /// right away.
+    pub fn run(&mut self, lines: &mut Vec<Line>, hooks: &mut dyn Hooks) {
+        for pass in &mut self.passes {
+            pass.run(lines);
+
+            if cfg!(debug_assertions)
+                && let Err(e) = verify::verify(lines)
+            {
+                panic!(
+                    "the `{}` pass generated invalid code:\n{}",
+                    pass.name(),
+                    diagnostics::render(&e, "<generated>", "")
+                );
+            }
+
+            if self.print_after_all {
+                eprint!("# after {}\n{}", pass.name(), asm::print(lines));
+            }
+            hooks.after_pass(pass.name(), lines);
+        }
+    }
+}
+
+impl fmt::Debug for PassManager {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("PassManager")
+            .field("passes", &self.names().collect::<Vec<_>>())
+            .field("print_after_all", &self.print_after_all)
+            .finish()
+    }
+}
+
+/// Work out arithmetic on constants.
+///
+/// An operation on constants is compiled to code that loads both of them and does the operation
+/// at run time. The answer is always the same, so this loads the answer instead. Operations whose
+/// answer is undefined, like dividing by zero, are left alone, and so are overflow checks that
+/// would fail.
+fn fold(lines: &mut Vec<Line>) {
+    let mut i = 0;
+    while i < lines.len() {
+        match fold_at(&lines[i..]) {
+            Some((len, value)) => {
+                lines.splice(i..i + len, [load(value)]);
+                // The answer might be an operand of an operation that started a few lines back.
+                i = i.saturating_sub(4);
+            }
+            None => i += 1,
+        }
+    }
+}
+
+/// If the code starts with an operation on constants, how many lines it is and its answer.
+fn fold_at(lines: &[Line]) -> Option<(usize, i32)> {
+    use Instruction as I;
+
+    let instructions: Vec<_> = lines
+        .iter()
+        .take(7)
+        .map_while(|line| match line {
+            Line::Instruction(instruction) => Some(instruction),
+            _ => None,
+        })
+        .collect();
+
+    let constant = |i: usize| match instructions.get(i) {
+        Some(I::Mov(Operand::Imm(Imm(value)), dst)) if *dst == EAX => Some(*value),
+        _ => None,
+    };
+    let is = |i: usize, instruction: &Instruction| instructions.get(i) == Some(&instruction);
+    let overflow_check =
+        |i: usize| matches!(instructions.get(i), Some(I::Jcc(Condition::Overflow, _)));
+
+    // An overflow check right after the operation goes too, if it would pass.
+    let checked = |len: usize, value: Option<i32>, wrapped: i32| {
+        if overflow_check(len) {
+            value.map(|value| (len + 1, value))
+        } else {
+            Some((len, wrapped))
+        }
+    };
+
+    let value = constant(0)?;
+
+    // A unary operation.
+    match instructions.get(1) {
+        Some(I::Neg(operand)) if *operand == EAX => {
+            return checked(2, value.checked_neg(), value.wrapping_neg());
+        }
+        Some(I::Not(operand)) if *operand == EAX => return Some((2, !value)),
+        Some(I::Cmp(Operand::Imm(Imm(0)), operand))
+            if *operand == EAX
+                && is(2, &I::Mov(Operand::Imm(Imm(0)), EAX))
+                && is(3, &I::Sete(super::AL)) =>
+        {
+            return Some((4, (value == 0) as i32));
+        }
+        Some(I::Cmp(Operand::Imm(Imm(0)), operand))
+            if *operand == EAX
+                && is(2, &I::Mov(Operand::Imm(Imm(0)), EAX))
+                && is(3, &I::Setne(super::AL)) =>
+        {
+            return Some((4, (value != 0) as i32));
+        }
+        Some(I::Lea(
+            Operand::Mem(Mem {
+                base: Reg::Ax,
+                index: Some((Reg::Ax, scale)),
+                offset: 0,
+            }),
+            dst,
+        )) if *dst == EAX => return Some((2, value.wrapping_mul(*scale as i32 + 1))),
+        _ => {}
+    }
+
+    // A binary operation, whose right operand is pushed while the left one is worked out.
+    let right = value;
+    if !is(1, &I::Push(Operand::Reg(Reg::Ax, asm::Size::Quad)))
+        || !is(3, &I::Pop(Operand::Reg(Reg::Cx, asm::Size::Quad)))
+    {
+        return None;
+    }
+    let left = constant(2)?;
+
+    match instructions.get(4)? {
+        I::Add(src, dst) if *src == ECX && *dst == EAX => {
+            checked(5, left.checked_add(right), left.wrapping_add(right))
+        }
+        I::Sub(src, dst) if *src == ECX && *dst == EAX => {
+            checked(5, left.checked_sub(right), left.wrapping_sub(right))
+        }
+        I::Imul(src, dst) if *src == ECX && *dst == EAX => {
+            checked(5, left.checked_mul(right), left.wrapping_mul(right))
+        }
+        I::Lea(
+            Operand::Mem(Mem {
+                base: Reg::Ax,
+                index: Some((Reg::Cx, scale)),
+                offset: 0,
+            }),
+            dst,
+        ) if *dst == EAX => Some((5, left.wrapping_add(right.wrapping_mul(*scale as i32)))),
+        I::Cdq if is(5, &I::Idiv(ECX)) => {
+            if is(6, &I::Mov(EDX, EAX)) {
+                Some((7, left.checked_rem(right)?))
+            } else {
+                Some((6, left.checked_div(right)?))
+            }
+        }
+        _ => None,
+    }
+}
+
+/// The line that loads a constant into `%eax`.
+fn load(value: i32) -> Line {
+    Line::Instruction(Instruction::Mov(Operand::Imm(Imm(value)), EAX))
+}
+
+/// Delete the parts of each function that control can never reach, like the code after a
+/// `return`.
+fn eliminate_dead_code(lines: &mut Vec<Line>) {
+    // Functions are rewritten from the last one back, so that the positions of the ones before
+    // stay where they were.
+    for (name, range) in cfg::functions(lines).into_iter().rev() {
+        let graph = cfg::Cfg::build(&name, &lines[range.clone()]);
+
+        let mut reachable = vec![false; graph.len()];
+        let mut work = vec![0];
+        while let Some(block) = work.pop() {
+            if block < graph.len() && !reachable[block] {
+                reachable[block] = true;
+                work.extend(graph.successors(block));
+            }
+        }
+
+        let live = graph
+            .blocks()
+            .iter()
+            .zip(reachable)
+            .filter(|&(_, reachable)| reachable)
+            .flat_map(|(block, _)| block.lines.iter().cloned());
+        lines.splice(range, live.collect::<Vec<_>>());
+    }
+}
@@ -0,0 +1,225 @@
+//! Checking that generated code makes sense.
+//!
+//! Code generation and the passes that rewrite its output have to keep some promises: every jump
+//! goes to a label that exists, every function returns instead of running off the end of its
+//! code, the stack is back where it started at every `ret`, and every instruction is given
+//! operands of the right kind and size. A pass that breaks one of them usually makes a program
+//! that still assembles and then crashes or gives the wrong answer, far away from the bug.
+//! [`verify`] catches that as soon as it happens instead, and debug builds run it after every
+//! pass.
+
+use std::collections::HashSet;
+
+use super::asm::{Instruction, Line, Operand, Size};
+use super::cfg::{self, Cfg};
+use crate::diagnostics::Diagnostic;
+
+/// Check that some code keeps every promise described in the [module documentation](self).
+///
+/// # Examples
+///
+/// ```
+/// use ecc::compiler::{asm, verify};
+///
+/// let good = asm::parse("\t.type f, @function\nf:\n\tpush\t%rax\n\tpop\t%rcx\n\tret\n\t.size f, .-f\n");
+/// assert!(verify::verify(&good.unwrap()).is_ok());
+///
+/// let bad = asm::parse("\t.type f, @function\nf:\n\tpush\t%rax\n\tret\n\t.size f, .-f\n");
+/// assert!(verify::verify(&bad.unwrap()).is_err());
+/// ```
+pub fn verify(lines: &[Line]) -> Result<(), Diagnostic> {
+    check_labels(lines)?;
+
+    for line in lines {
+        if let Line::Instruction(instruction) = line {
+            check_operands(instruction).map_err(|message| invalid(message, line))?;
+        }
+    }
+
+    for cfg in cfg::build(lines) {
+        check_terminators(&cfg)?;
+        check_stack(&cfg)?;
+    }
+
+    Ok(())
+}
+
+/// Make the error for code that breaks a promise.
+fn invalid(message: impl Into<String>, line: &Line) -> Diagnostic {
+    Diagnostic::error(message).with_note(format!("in `{}`", line.to_string().trim()))
+}
+
+/// Check that labels are only defined once, and that every jump goes to one of them.
+///
+/// Calls are left alone, since they can go to functions defined somewhere else.
+fn check_labels(lines: &[Line]) -> Result<(), Diagnostic> {
+    let mut labels = HashSet::new();
+    for line in lines {
+        if let Line::Label(label) = line
+            && !labels.insert(label.as_str())
+        {
+            return Err(invalid(format!("`{label}` is defined twice"), line));
+        }
+    }
+
+    for line in lines {
+        if let Line::Instruction(Instruction::Jmp(label) | Instruction::Jcc(_, label)) = line
+            && !labels.contains(label.as_str())
+        {
+            return Err(invalid(format!("`{label}` is never defined"), line));
+        }
+    }
+
+    Ok(())
+}
+
+/// Check that an instruction's operands are of the kind and size it works on.
+fn check_operands(instruction: &Instruction) -> Result<(), String> {
+    use Instruction as I;
+
+    match instruction {
+        // `movq` copies a whole register, like the stack pointer into the frame pointer.
+        I::Mov(src, Operand::Reg(_, Size::Quad)) => check_value(src, Size::Quad)?,
+        I::Mov(src, dst)
+        | I::Add(src, dst)
+        | I::Sub(src, dst)
+        | I::Imul(src, dst)
+        | I::Cmp(src, dst)
+        | I::Test(src, dst) => {
+            check_value(src, Size::Long)?;
+            check_value(dst, Size::Long)?;
+            check_writable(dst)?;
+            if is_memory(src) && is_memory(dst) {
+                return Err("only one operand can be in memory".to_owned());
+            }
+        }
+        I::Push(operand) => check_value(operand, Size::Quad)?,
+        I::Pop(operand) => {
+            check_value(operand, Size::Quad)?;
+            check_writable(operand)?;
+        }
+        I::Neg(operand) | I::Not(operand) | I::Idiv(operand) => {
+            check_value(operand, Size::Long)?;
+            check_writable(operand)?;
+        }
+        I::Sete(operand) | I::Setne(operand) => {
+            check_value(operand, Size::Byte)?;
+            check_writable(operand)?;
+        }
+        I::Lea(src, dst) => {
+            if !is_memory(src) {
+                return Err("`lea` needs an address".to_owned());
+            }
+            if !matches!(dst, Operand::Reg(_, Size::Long | Size::Quad)) {
+                return Err("`lea` puts the address in a 32-bit or 64-bit register".to_owned());
+            }
+        }
+        I::And(src, dst) => {
+            let Operand::Reg(_, size) = dst else {
+                return Err("`and` needs a register to work on".to_owned());
+            };
+            check_value(src, *size)?;
+        }
+        I::Jmp(_) | I::Jcc(..) | I::Call(_) | I::Cdq | I::Syscall | I::Ret | I::Ud2 => {}
+    }
+
+    Ok(())
+}
+
+/// Check that a register operand is the size the instruction works on.
+fn check_value(operand: &Operand, size: Size) -> Result<(), String> {
+    match operand {
+        Operand::Reg(reg, actual) if *actual != size => Err(format!(
+            "`%{}` is the wrong size, expected `%{}`",
+            reg.name(*actual),
+            reg.name(size)
+        )),
+        _ => Ok(()),
+    }
+}
+
+/// Check that an operand can be written to.
+fn check_writable(operand: &Operand) -> Result<(), String> {
+    match operand {
+        Operand::Imm(_) => Err("a constant cannot be written to".to_owned()),
+        _ => Ok(()),
+    }
+}
+
+fn is_memory(operand: &Operand) -> bool {
+    matches!(operand, Operand::Mem(_) | Operand::Label(_))
+}
+
+/// Check that control cannot run off the end of a function's code.
+fn check_terminators(cfg: &Cfg) -> Result<(), Diagnostic> {
+    let Some(last) = cfg.blocks().last() else {
+        return Ok(());
+    };
+
+    let terminator = last.lines.iter().rev().find_map(|line| match line {
+        Line::Instruction(instruction) => Some(instruction),
+        _ => None,
+    });
+
+    match terminator {
+        Some(Instruction::Ret | Instruction::Jmp(_) | Instruction::Ud2) => Ok(()),
+        _ => Err(Diagnostic::error(format!(
+            "`{}` can run off the end of its code",
+            cfg.name
+        ))),
+    }
+}
+
+/// Check that every `pop` has a `push` to take the value from, and that the stack is back where
+/// it started at every `ret`.
+///
+/// The depth of the stack has to be the same on every path into a block, or else code after the
+/// block could not know where anything is.
+fn check_stack(cfg: &Cfg) -> Result<(), Diagnostic> {
+    let mut depths: Vec<Option<usize>> = vec![None; cfg.len()];
+    let mut work = Vec::new();
+    if !cfg.is_empty() {
+        depths[0] = Some(0);
+        work.push(0);
+    }
+
+    while let Some(block) = work.pop() {
+        let mut depth = depths[block].unwrap();
+
+        for line in &cfg.blocks()[block].lines {
+            match line {
+                Line::Instruction(Instruction::Push(_)) => depth += 1,
+                Line::Instruction(Instruction::Pop(_)) => {
+                    depth = depth
+                        .checked_sub(1)
+                        .ok_or_else(|| invalid("nothing has been pushed to pop", line))?;
+                }
+                Line::Instruction(Instruction::Ret) if depth != 0 => {
+                    return Err(invalid(
+                        format!("the stack still has {depth} values on it"),
+                        line,
+                    ));
+                }
+                _ => {}
+            }
+        }
+
+        for next in cfg.successors(block) {
+            match depths[next] {
+                None => {
+                    depths[next] = Some(depth);
+                    work.push(next);
+                }
+                Some(other) if other != depth => {
+                    return Err(Diagnostic::error(format!(
+                        "`{}` reaches a block with both {other} and {depth} values on the stack",
+                        cfg.name
+                    )));
+                }
+                Some(_) => {}
+            }
+        }
+    }
+
+    Ok(())
+}
@@ -0,0 +1,103 @@
+//! Make rules saying which files a compilation reads, like the ones `gcc -MD` writes.
+//!
+//! A build that includes the rules in its `Makefile` rebuilds a program whenever any file that
+//! went into it changes, not just its source file. There is no preprocessor yet, so the source
+//! file is the only one read for now. Headers will follow it once `#include` works.
+
+use std::path::Path;
+
+use crate::options::{CompileOptions, Emit};
+
+/// How long a line of a rule can get before the rest of it is continued on the next one.
+const LINE_WIDTH: usize = 75;
+
+/// Write the make rule for compiling the named source file with the given options.
+///
+/// The target is the one the options ask for, like `-MT`, or else the file the compilation writes,
+/// which is the executable if it only prints something.
+///
+/// # Examples
+///
+/// ```
+/// use ecc::depfile;
+/// use ecc::options::{CompileOptions, Emit};
+///
+/// let options = CompileOptions::new();
+/// assert_eq!(depfile::for_source("src/main.c", &options), "src/main: src/main.c\n");
+///
+/// let options = options.with_emit(Emit::Assembly);
+/// assert_eq!(depfile::for_source("src/main.c", &options), "src/main.s: src/main.c\n");
+/// ```
+pub fn for_source(file_name: &str, options: &CompileOptions) -> String {
+    let dependencies = options.dependencies.clone().unwrap_or_default();
+    let target = dependencies.target.unwrap_or_else(|| {
+        let path = Path::new(file_name);
+        let output = options.output_path(path);
+        let output = output.or_else(|| {
+            let executable = Emit::Executable.output_path(path)?;
+            Some(options.place_output(&executable))
+        });
+        output.unwrap_or_default().display().to_string()
+    });
+
+    rule(&target, &[file_name], dependencies.phony_headers)
+}
+
+/// Write a make rule saying that the target depends on the given files.
+///
+/// Characters that mean something to make, like spaces, are escaped, and long rules are split
+/// over several lines. With `phony_headers`, every file but the first gets an empty rule of its
+/// own, like `-MP`, so that make does not stop when a header is deleted.
+///
+/// # Examples
+///
+/// ```
+/// use ecc::depfile;
+///
+/// assert_eq!(
+///     depfile::rule("main", &["main.c", "my header.h"], true),
+///     "main: main.c my\\ header.h\n\nmy\\ header.h:\n"
+/// );
+/// ```
+pub fn rule(target: &str, prerequisites: &[&str], phony_headers: bool) -> String {
+    let mut rule = format!("{}:", escape(target));
+    let mut line_length = rule.len();
+
+    for prerequisite in prerequisites {
+        let prerequisite = escape(prerequisite);
+        if line_length + 1 + prerequisite.len() > LINE_WIDTH {
+            rule.push_str(" \\\n");
+            line_length = 0;
+        }
+
+        rule.push(' ');
+        rule.push_str(&prerequisite);
+        line_length += 1 + prerequisite.len();
+    }
+    rule.push('\n');
+
+    if phony_headers {
+        for header in prerequisites.iter().skip(1) {
+            rule.push_str(&format!("\n{}:\n", escape(header)));
+        }
+    }
+
+    rule
+}
+
+/// Escape the characters in a file name that make would otherwise treat specially.
+fn escape(file_name: &str) -> String {
+    let mut escaped = String::with_capacity(file_name.len());
+    for c in file_name.chars() {
+        match c {
+            ' ' | '#' => {
+                escaped.push('\\');
+                escaped.push(c);
+            }
+            '$' => escaped.push_str("$$"),
+            c => escaped.push(c),
+        }
+    }
+
+    escaped
+}
@@ -0,0 +1,778 @@
+use std::fmt::Write;
+
+use colored::Colorize;
+use unicode_width::UnicodeWidthChar;
+
+use crate::lexer::{Lexer, LineDirective};
+use crate::span::Span;
+use crate::token::TokenKind;
+
+pub mod codes;
+
+/// How serious a diagnostic is.
+#[derive(Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Debug)]
+pub enum Level {
+    /// Advice on how to fix a problem, always attached to another diagnostic.
+    Help,
+
+    /// Something worth pointing out, usually attached to another diagnostic.
+    Note,
+
+    /// Suspicious code that still compiles.
+    Warning,
+
+    /// A problem that stops compilation.
+    Error,
+}
+
+impl std::fmt::Display for Level {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Help => write!(f, "help"),
+            Self::Note => write!(f, "note"),
+            Self::Warning => write!(f, "warning"),
+            Self::Error => write!(f, "error"),
+        }
+    }
+}
+
+/// A span of source code with an optional message explaining its relevance.
+#[derive(Clone, Debug)]
+pub struct Label {
+    /// The code being pointed at.
+    pub span: Span,
+
+    /// What the code has to do with the diagnostic, if anything needs to be said.
+    pub message: Option<String>,
+}
+
+/// A note or help message attached to a diagnostic.
+///
+/// Sub-diagnostics explain the diagnostic they belong to, like pointing at the previous
+/// declaration of something that was declared twice. They may point at their own code, but they
+/// are always reported together with their parent.
+#[derive(Clone, Debug)]
+pub struct SubDiagnostic {
+    /// Either [`Level::Note`] or [`Level::Help`].
+    pub level: Level,
+
+    /// The message.
+    pub message: String,
+
+    /// The code the message is about, if any.
+    pub span: Option<Span>,
+}
+
+/// A machine-applicable fix for a diagnostic.
+///
+/// Applying a suggestion means replacing the text covered by its span with the replacement. An
+/// empty span inserts the replacement, and an empty replacement deletes the spanned text.
+#[derive(Clone, Debug)]
+pub struct Suggestion {
+    /// A short description of the fix, like "insert ';' here".
+    pub message: String,
+
+    /// The code to replace.
+    pub span: Span,
+
+    /// The code to replace it with.
+    pub replacement: String,
+}
+
+/// How diagnostics are printed.
+#[derive(Clone, Copy, PartialEq, Eq, Default, Debug)]
+pub enum ErrorFormat {
+    /// Pretty, colorful output meant for people. See [`render`].
+    #[default]
+    Human,
+
+    /// One JSON object per line, meant for editors and other tools. See [`to_json`].
+    Json,
+}
+
+/// A message about the source code.
+///
+/// Every stage of the compiler reports problems through this one type, so that they can all be
+/// rendered the same way. A diagnostic points at most one primary location, which is where the
+/// problem is, and any number of secondary locations that help explain it.
+#[derive(Clone, Debug)]
+pub struct Diagnostic {
+    /// How serious the diagnostic is.
+    pub level: Level,
+
+    /// A stable code identifying the kind of diagnostic, like `E0001`.
+    pub code: Option<&'static str>,
+
+    /// The main message.
+    pub message: String,
+
+    /// Where the problem is. Diagnostics that are not about any particular piece of code, like
+    /// missing input files, have no primary span.
+    pub primary: Option<Span>,
+
+    /// Other locations that are related to the problem.
+    pub secondary: Vec<Label>,
+
+    /// Notes and help messages that explain the problem.
+    pub children: Vec<SubDiagnostic>,
+
+    /// Fixes that could be applied to make the problem go away.
+    pub suggestions: Vec<Suggestion>,
+}
+
+impl Diagnostic {
+    /// Create a new diagnostic with the given level and message.
+    pub fn new(level: Level, message: impl Into<String>) -> Self {
+        Self {
+            level,
+            code: None,
+            message: message.into(),
+            primary: None,
+            secondary: Vec::new(),
+            children: Vec::new(),
+            suggestions: Vec::new(),
+        }
+    }
+
+    /// Create a new error.
+    pub fn error(message: impl Into<String>) -> Self {
+        Self::new(Level::Error, message)
+    }
+
+    /// Create a new warning.
+    pub fn warning(message: impl Into<String>) -> Self {
+        Self::new(Level::Warning, message)
+    }
+
+    /// Create a new note.
+    pub fn note(message: impl Into<String>) -> Self {
+        Self::new(Level::Note, message)
+    }
+
+    /// Set the diagnostic's code.
+    pub fn with_code(mut self, code: &'static str) -> Self {
+        self.code = Some(code);
+        self
+    }
+
+    /// Set the diagnostic's primary span.
+    pub fn with_span(mut self, span: Span) -> Self {
+        self.primary = Some(span);
+        self
+    }
+
+    /// Add a secondary span with an explanation.
+    pub fn with_secondary(mut self, span: Span, message: impl Into<String>) -> Self {
+        self.secondary.push(Label {
+            span,
+            message: Some(message.into()),
+        });
+        self
+    }
+
+    /// Attach a note that is not about any particular code.
+    pub fn with_note(self, message: impl Into<String>) -> Self {
+        self.with_child(Level::Note, message, None)
+    }
+
+    /// Attach a note pointing at the given code.
+    pub fn with_note_at(self, span: Span, message: impl Into<String>) -> Self {
+        self.with_child(Level::Note, message, Some(span))
+    }
+
+    /// Attach a help message.
+    pub fn with_help(self, message: impl Into<String>) -> Self {
+        self.with_child(Level::Help, message, None)
+    }
+
+    fn with_child(mut self, level: Level, message: impl Into<String>, span: Option<Span>) -> Self {
+        self.children.push(SubDiagnostic {
+            level,
+            message: message.into(),
+            span,
+        });
+        self
+    }
+
+    /// Suggest replacing the code covered by `span` with `replacement`.
+    pub fn with_suggestion(
+        mut self,
+        span: Span,
+        replacement: impl Into<String>,
+        message: impl Into<String>,
+    ) -> Self {
+        self.suggestions.push(Suggestion {
+            message: message.into(),
+            span,
+            replacement: replacement.into(),
+        });
+        self
+    }
+
+    /// Return true if this diagnostic should stop compilation.
+    pub fn is_error(&self) -> bool {
+        self.level == Level::Error
+    }
+}
+
+/// Somewhere to send diagnostics.
+///
+/// The compiler never prints diagnostics itself. Instead, every diagnostic is handed to an
+/// emitter, which can print it, collect it, forward it to an editor, or do whatever else makes
+/// sense. The file name and source code the diagnostic is about are passed along, since spans are
+/// meaningless without them.
+///
+/// A `Vec<Diagnostic>` is an emitter that simply collects everything it is given.
+///
+/// # Examples
+///
+/// ```
+/// use ecc::CompileOptions;
+/// use ecc::diagnostics::Diagnostic;
+///
+/// let options = CompileOptions::new();
+/// let mut diagnostics: Vec<Diagnostic> = Vec::new();
+/// let assembly = ecc::compile_source("int main(void) { return 1 }", &options, &mut diagnostics);
+///
+/// assert!(assembly.is_err());
+/// assert_eq!(diagnostics[0].message, "expected ';'");
+/// ```
+pub trait DiagnosticEmitter {
+    /// Handle a diagnostic about the given file.
+    fn emit(&mut self, diagnostic: &Diagnostic, file_name: &str, source: &str);
+}
+
+impl DiagnosticEmitter for Vec<Diagnostic> {
+    fn emit(&mut self, diagnostic: &Diagnostic, _file_name: &str, _source: &str) {
+        self.push(diagnostic.clone());
+    }
+}
+
+/// An emitter that prints diagnostics to stderr in the given format.
+#[derive(Clone, Debug, Default)]
+pub struct StderrEmitter {
+    format: ErrorFormat,
+
+    /// The code of the first error printed, if any had one.
+    first_code: Option<&'static str>,
+}
+
+impl StderrEmitter {
+    /// Create an emitter that prints in the given format.
+    pub fn new(format: ErrorFormat) -> Self {
+        Self {
+            format,
+            first_code: None,
+        }
+    }
+
+    /// Print a closing hint about `ecc --explain`, if any of the errors had a code.
+    ///
+    /// Nothing is printed for machine readable formats.
+    pub fn finish(&self) {
+        if self.format == ErrorFormat::Human
+            && let Some(code) = self.first_code
+        {
+            eprintln!("For more information about this error, try `ecc --explain {code}`.");
+        }
+    }
+}
+
+impl DiagnosticEmitter for StderrEmitter {
+    fn emit(&mut self, diagnostic: &Diagnostic, file_name: &str, source: &str) {
+        if diagnostic.is_error() && self.first_code.is_none() {
+            self.first_code = diagnostic.code;
+        }
+
+        match self.format {
+            ErrorFormat::Human => eprint!("{}", render(diagnostic, file_name, source)),
+            ErrorFormat::Json => eprintln!("{}", to_json(diagnostic, file_name, source)),
+        }
+    }
+}
+
+/// Render a diagnostic to a string.
+///
+/// The output starts with a header containing the level, code, and message. If the diagnostic has
+/// a primary span, the file name, line and column are printed next, followed by every line of
+/// source code that the diagnostic points at, with the relevant code underlined.
+pub fn render(diagnostic: &Diagnostic, file_name: &str, source: &str) -> String {
+    let mut output = String::new();
+
+    let header = match diagnostic.code {
+        Some(code) => format!("{}[{code}]", diagnostic.level),
+        None => diagnostic.level.to_string(),
+    };
+
+    let header = colorize(&header, diagnostic.level);
+    writeln!(output, "{}: {}", header, diagnostic.message.bold()).unwrap();
+
+    let Some(primary) = diagnostic.primary else {
+        for child in &diagnostic.children {
+            render_child(&mut output, file_name, source, child);
+        }
+
+        return output;
+    };
+
+    render_location(&mut output, file_name, source, primary);
+
+    let mut labels = vec![(primary, None, true)];
+    for label in &diagnostic.secondary {
+        labels.push((label.span, label.message.as_deref(), false));
+    }
+
+    labels.sort_by_key(|(span, _, _)| span.start);
+
+    writeln!(output, "{}", "      |".blue().bold()).unwrap();
+    for (span, message, is_primary) in labels {
+        render_label(&mut output, source, span, message, is_primary);
+    }
+
+    for child in &diagnostic.children {
+        render_child(&mut output, file_name, source, child);
+    }
+
+    for suggestion in &diagnostic.suggestions {
+        render_suggestion(&mut output, source, suggestion);
+    }
+
+    writeln!(output).unwrap();
+    output
+}
+
+/// Color some text according to the given level.
+fn colorize(text: &str, level: Level) -> colored::ColoredString {
+    let text = match level {
+        Level::Error => text.red(),
+        Level::Warning => text.yellow(),
+        Level::Note | Level::Help => text.cyan(),
+    };
+
+    text.bold()
+}
+
+/// Render the `--> file:line:column` line for a span.
+fn render_location(output: &mut String, file_name: &str, source: &str, span: Span) {
+    let (file, line, column) = presumed_location(source, span.start);
+    let file_name = file.unwrap_or(file_name);
+    writeln!(
+        output,
+        "  {} {file_name}:{line}:{column}",
+        "-->".blue().bold()
+    )
+    .unwrap();
+}
+
+/// Render a note or help message.
+///
+/// Messages without a span are tucked under the parent diagnostic like `= note: ...`. Messages
+/// with one get their own header, location, and underlined code.
+fn render_child(output: &mut String, file_name: &str, source: &str, child: &SubDiagnostic) {
+    let level = colorize(&child.level.to_string(), child.level);
+
+    let Some(span) = child.span else {
+        writeln!(
+            output,
+            "      {} {level}: {}",
+            "=".blue().bold(),
+            child.message
+        )
+        .unwrap();
+        return;
+    };
+
+    writeln!(output, "{level}: {}", child.message).unwrap();
+    render_location(output, file_name, source, span);
+    writeln!(output, "{}", "      |".blue().bold()).unwrap();
+    render_label(output, source, span, None, false);
+}
+
+/// Render a suggestion by showing the line it applies to with the fix already made.
+///
+/// Inserted code is marked with `+`, replaced code with `~`. Only the first line of a suggestion
+/// that spans several lines is shown.
+fn render_suggestion(output: &mut String, source: &str, suggestion: &Suggestion) {
+    let (_, line, _) = presumed_location(source, suggestion.span.start);
+    let (line_start, text) = line_containing(source, suggestion.span.start);
+
+    let start = suggestion.span.start - line_start;
+    let end = (suggestion.span.end - line_start).min(text.len());
+    let before = &text[..start];
+    let fixed = format!("{before}{}{}", suggestion.replacement, &text[end..]);
+
+    let padding = display_width(before);
+    let length = display_width(&fixed[..start + suggestion.replacement.len()]) - padding;
+
+    let marker = if suggestion.span.is_empty() { "+" } else { "~" };
+    let underline = marker.repeat(length.max(1));
+    let fixed = expand_tabs(&fixed);
+
+    writeln!(output, "{}: {}", "help".cyan().bold(), suggestion.message).unwrap();
+    writeln!(output, "{}", "      |".blue().bold()).unwrap();
+
+    let gutter = format!(" {line:>4} |").blue().bold();
+    writeln!(output, "{gutter} {fixed}").unwrap();
+
+    let gutter = "      |".blue().bold();
+    let underline = underline.green().bold();
+    writeln!(output, "{gutter} {: <padding$}{underline}", "").unwrap();
+}
+
+/// Render a diagnostic as a single line of JSON.
+///
+/// The object has the diagnostic's `level`, `code` (or `null`), and `message`, a list of `spans`,
+/// a list of `children`, and a list of `suggestions`. Every span records the file name, its byte
+/// offsets, and the line and column where it starts, and says whether it is the primary span.
+/// Every child records its level, message, and span (or `null`). Every suggestion records its
+/// message, span, and replacement text.
+///
+/// Locations are where `#line` directives say they are, but a directive in a comment is only part
+/// of the comment.
+///
+/// # Examples
+///
+/// ```
+/// use ecc::diagnostics::{self, Diagnostic};
+/// use ecc::span::Span;
+///
+/// let source = "#line 20 \"lib.c\"\n/*\n#line 100\n*/\nint x;\n";
+/// let start = source.find("int").unwrap();
+/// let diagnostic = Diagnostic::error("oops").with_span(Span::new(start, start + 3));
+/// let json = diagnostics::to_json(&diagnostic, "main.c", source);
+///
+/// assert!(json.contains(r#""file_name":"lib.c","byte_start":33,"byte_end":36,"line":23"#));
+/// ```
+pub fn to_json(diagnostic: &Diagnostic, file_name: &str, source: &str) -> String {
+    let mut spans = Vec::new();
+    if let Some(primary) = diagnostic.primary {
+        spans.push(span_to_json(primary, true, None, file_name, source));
+    }
+
+    for label in &diagnostic.secondary {
+        let message = label.message.as_deref();
+        spans.push(span_to_json(label.span, false, message, file_name, source));
+    }
+
+    let suggestions: Vec<_> = diagnostic
+        .suggestions
+        .iter()
+        .map(|suggestion| {
+            format!(
+                r#"{{"message":{},"span":{},"replacement":{}}}"#,
+                json_string(&suggestion.message),
+                span_to_json(suggestion.span, false, None, file_name, source),
+                json_string(&suggestion.replacement),
+            )
+        })
+        .collect();
+
+    let children: Vec<_> = diagnostic
+        .children
+        .iter()
+        .map(|child| {
+            let span = child.span.map_or("null".to_string(), |span| {
+                span_to_json(span, false, None, file_name, source)
+            });
+
+            format!(
+                r#"{{"level":{},"message":{},"span":{}}}"#,
+                json_string(&child.level.to_string()),
+                json_string(&child.message),
+                span,
+            )
+        })
+        .collect();
+
+    let code = diagnostic.code.map_or("null".to_string(), json_string);
+
+    format!(
+        r#"{{"level":{},"code":{},"message":{},"spans":[{}],"children":[{}],"suggestions":[{}]}}"#,
+        json_string(&diagnostic.level.to_string()),
+        code,
+        json_string(&diagnostic.message),
+        spans.join(","),
+        children.join(","),
+        suggestions.join(","),
+    )
+}
+
+/// Render a span as a JSON object.
+fn span_to_json(
+    span: Span,
+    is_primary: bool,
+    label: Option<&str>,
+    file_name: &str,
+    source: &str,
+) -> String {
+    let (file, line, column) = presumed_location(source, span.start);
+    let file_name = file.unwrap_or(file_name);
+    let label = label.map_or("null".to_string(), json_string);
+
+    format!(
+        r#"{{"file_name":{},"byte_start":{},"byte_end":{},"line":{},"column":{},"is_primary":{},"label":{}}}"#,
+        json_string(file_name),
+        span.start,
+        span.end,
+        line,
+        column,
+        is_primary,
+        label,
+    )
+}
+
+/// Quote and escape a string for use in JSON.
+fn json_string(s: &str) -> String {
+    let mut quoted = String::with_capacity(s.len() + 2);
+    quoted.push('"');
+
+    for c in s.chars() {
+        match c {
+            '"' => quoted.push_str("\\\""),
+            '\\' => quoted.push_str("\\\\"),
+            '\n' => quoted.push_str("\\n"),
+            '\r' => quoted.push_str("\\r"),
+            '\t' => quoted.push_str("\\t"),
+            c if c.is_control() => write!(quoted, "\\u{:04x}", c as u32).unwrap(),
+            c => quoted.push(c),
+        }
+    }
+
+    quoted.push('"');
+    quoted
+}
+
+/// Compute the edit distance between two strings.
+///
+/// This is the number of single character insertions, deletions, and substitutions needed to turn
+/// one string into the other. It is used to find likely typos for "did you mean" suggestions.
+pub(crate) fn edit_distance(a: &str, b: &str) -> usize {
+    let b: Vec<char> = b.chars().collect();
+    let mut previous: Vec<usize> = (0..=b.len()).collect();
+
+    for (i, ca) in a.chars().enumerate() {
+        let mut current = vec![i + 1];
+        for (j, cb) in b.iter().enumerate() {
+            let substitution = previous[j] + usize::from(ca != *cb);
+            let insertion = current[j] + 1;
+            let deletion = previous[j + 1] + 1;
+            current.push(substitution.min(insertion).min(deletion));
+        }
+        previous = current;
+    }
+
+    previous[b.len()]
+}
+
+/// Render one line of source code with the given span underlined.
+///
+/// Primary spans are underlined like `^~~~`, secondary ones like `----`. Spans that cover several
+/// lines are only underlined up to the end of their first line.
+fn render_label(
+    output: &mut String,
+    source: &str,
+    span: Span,
+    message: Option<&str>,
+    is_primary: bool,
+) {
+    let (_, line, _) = presumed_location(source, span.start);
+    let (line_start, text) = line_containing(source, span.start);
+
+    let start = span.start - line_start;
+    let end = (span.end - line_start).min(text.len());
+    let padding = display_width(&text[..start]);
+    let length = (display_width(&text[..end]) - padding).max(1);
+    let text = expand_tabs(text);
+
+    let underline = if is_primary {
+        format!("^{}", "~".repeat(length - 1)).red().bold()
+    } else {
+        "-".repeat(length).blue().bold()
+    };
+
+    let gutter = format!(" {line:>4} |").blue().bold();
+    writeln!(output, "{gutter} {text}").unwrap();
+
+    let gutter = "      |".blue().bold();
+    match message {
+        Some(message) => {
+            writeln!(output, "{gutter} {: <padding$}{underline} {message}", "").unwrap()
+        }
+        None => writeln!(output, "{gutter} {: <padding$}{underline}", "").unwrap(),
+    }
+}
+
+/// The number of columns between tab stops when rendering source code.
+const TAB_WIDTH: usize = 4;
+
+/// Find the line and column number of a byte offset into the source.
+///
+/// Both numbers start at 1. The column counts characters, not bytes, so that it matches what
+/// editors show for lines containing multi-byte characters.
+pub(crate) fn line_and_column(source: &str, offset: usize) -> (usize, usize) {
+    let before = &source[..offset];
+    let line = before.matches('\n').count() + 1;
+    let line_start = before.rfind('\n').map_or(0, |i| i + 1);
+
+    (line, before[line_start..].chars().count() + 1)
+}
+
+/// Find the file name, line and column that a byte offset into the source is reported at.
+///
+/// This goes over the whole source, so anything that looks up many offsets should build a
+/// [`LineMap`] once instead.
+pub(crate) fn presumed_location(source: &str, offset: usize) -> (Option<&str>, usize, usize) {
+    LineMap::new(source).presumed_location(source, offset)
+}
+
+/// Where the lines of a source start, and which `#line` directives renumber them, so that
+/// locations in it can be found without going over it again.
+pub(crate) struct LineMap {
+    /// The offset of the start of every line, in order.
+    line_starts: Vec<usize>,
+
+    /// Every line directive, in order.
+    directives: Vec<Renumbering>,
+}
+
+/// What a line directive does to the lines after it.
+struct Renumbering {
+    /// The index of the line after the directive, counting from 0.
+    first_line: usize,
+
+    /// The line number the directive gives that line.
+    presumed_line: usize,
+
+    /// Where the name of the file the lines are from is in the source, if this directive or an
+    /// earlier one changed it.
+    file: Option<Span>,
+}
+
+impl LineMap {
+    /// Map the lines of a source.
+    ///
+    /// The directives are the ones the lexer finds, so a `#line` inside a comment does not count.
+    pub(crate) fn new(source: &str) -> Self {
+        let line_starts: Vec<_> = std::iter::once(0)
+            .chain(source.match_indices('\n').map(|(i, _)| i + 1))
+            .collect();
+
+        let mut directives: Vec<Renumbering> = Vec::new();
+        let tokens = Lexer::new(source).with_comments().filter_map(Result::ok);
+        for token in tokens.filter(|token| token.kind == TokenKind::SpecialLineDirective) {
+            let Some(directive) = LineDirective::parse(token.lexeme) else {
+                continue;
+            };
+
+            let file = match directive.file {
+                // The name is part of the lexeme, which is part of the source.
+                Some(file) => {
+                    let start = token.span.start
+                        + (file.as_ptr() as usize - token.lexeme.as_ptr() as usize);
+                    Some(Span::new(start, start + file.len()))
+                }
+                None => directives.last().and_then(|last| last.file),
+            };
+            directives.push(Renumbering {
+                first_line: line_starts.partition_point(|&start| start <= token.span.start),
+                presumed_line: directive.line,
+                file,
+            });
+        }
+
+        Self {
+            line_starts,
+            directives,
+        }
+    }
+
+    /// Find the line and column number of a byte offset into the source, like
+    /// [`line_and_column`].
+    pub(crate) fn line_and_column(&self, source: &str, offset: usize) -> (usize, usize) {
+        let line = self.line_starts.partition_point(|&start| start <= offset);
+        let line_start = self.line_starts[line - 1];
+
+        (line, source[line_start..offset].chars().count() + 1)
+    }
+
+    /// Get the text of a line of the source, by its number counting from 1, without its line
+    /// ending.
+    pub(crate) fn line<'a>(&self, source: &'a str, line: usize) -> &'a str {
+        let start = self.line_starts[line - 1];
+        let end = self
+            .line_starts
+            .get(line)
+            .map_or(source.len(), |&next| next - 1);
+
+        source[start..end]
+            .strip_suffix('\r')
+            .unwrap_or(&source[start..end])
+    }
+
+    /// Find the file name, line and column that a byte offset into the source is reported at.
+    ///
+    /// They are the ones [`line_and_column`] finds, unless a `#line` directive before the offset
+    /// changed the line numbers and maybe the file name of the lines after it. The file name is
+    /// [`None`] if no directive changed it.
+    pub(crate) fn presumed_location<'a>(
+        &self,
+        source: &'a str,
+        offset: usize,
+    ) -> (Option<&'a str>, usize, usize) {
+        let (line, column) = self.line_and_column(source, offset);
+
+        // Directives only apply to the lines after their own.
+        let index = line - 1;
+        let applied = self
+            .directives
+            .partition_point(|directive| directive.first_line <= index);
+        match applied.checked_sub(1).map(|i| &self.directives[i]) {
+            Some(directive) => (
+                directive.file.map(|file| &source[file.start..file.end]),
+                directive.presumed_line + index - directive.first_line,
+                column,
+            ),
+            None => (None, line, column),
+        }
+    }
+}
+
+/// Get the text of the line containing a byte offset, without its newline, along with the offset
+/// at which the line starts.
+fn line_containing(source: &str, offset: usize) -> (usize, &str) {
+    let line_start = source[..offset].rfind('\n').map_or(0, |i| i + 1);
+    let line_end = source[offset..]
+        .find('\n')
+        .map_or(source.len(), |i| offset + i);
+
+    (line_start, &source[line_start..line_end])
+}
+
+/// Compute how many terminal columns some text from the start of a line takes up.
+///
+/// Tabs advance to the next tab stop, wide characters (like most CJK characters) take up two
+/// columns, and combining characters take up none. This has to agree with [`expand_tabs`], or the
+/// underlines will not line up with the code they point at.
+fn display_width(text: &str) -> usize {
+    text.chars().fold(0, |width, c| match c {
+        '\t' => (width / TAB_WIDTH + 1) * TAB_WIDTH,
+        c => width + c.width().unwrap_or(0),
+    })
+}
+
+/// Replace the tabs in a line of source code with spaces up to the next tab stop.
+///
+/// Terminals disagree on how wide a tab is, so tabs are never printed as they are.
+fn expand_tabs(text: &str) -> String {
+    let mut expanded = String::with_capacity(text.len());
+    for c in text.chars() {
+        if c == '\t' {
+            let width = display_width(&expanded);
+            expanded.push_str(&" ".repeat(TAB_WIDTH - width % TAB_WIDTH));
+        } else {
+            expanded.push(c);
+        }
+    }
+
+    expanded
+}
@@ -0,0 +1,366 @@
+//! Stable diagnostic codes and their extended explanations.
+//!
+//! Every kind of diagnostic gets its own code, so that people (and tools) can look up what went
+//! wrong without having to match on the message, which may change. Codes are never reused, even if
+//! the diagnostic they belonged to is removed.
+
+/// A character that cannot start any token.
+pub const UNKNOWN_CHARACTER: &str = "E0001";
+
+/// A block comment that is never closed.
+pub const UNTERMINATED_COMMENT: &str = "E0002";
+
+/// A string literal that is never closed.
+pub const UNTERMINATED_STRING: &str = "E0003";
+
+/// A number that runs into identifier characters.
+pub const MALFORMED_NUMBER: &str = "E0004";
+
+/// A token that does not fit the grammar where it appears.
+pub const UNEXPECTED_TOKEN: &str = "E0005";
+
+/// The source ended in the middle of a construct.
+pub const UNEXPECTED_END_OF_FILE: &str = "E0006";
+
+/// Tokens after the end of the program.
+pub const TRAILING_TOKENS: &str = "E0007";
+
+/// An integer literal too large for any supported type.
+pub const INTEGER_TOO_LARGE: &str = "E0008";
+
+/// A `return` statement whose value does not match the function's return type.
+pub const RETURN_TYPE_MISMATCH: &str = "E0009";
+
+/// A `//` comment in a version of C that does not have them.
+pub const LINE_COMMENT: &str = "E0010";
+
+/// A `#line` directive that does not give a line number and an optional file name.
+pub const MALFORMED_LINE_DIRECTIVE: &str = "E0011";
+
+/// A `_Generic` selection that associates two expressions with the same type, or has two
+/// `default`s.
+pub const DUPLICATE_GENERIC_ASSOCIATION: &str = "E0012";
+
+/// A `_Generic` selection with no association for the type of its controlling expression.
+pub const NO_GENERIC_ASSOCIATION: &str = "E0013";
+
+/// An integer literal written with a prefix or suffix that is not supported yet.
+pub const UNSUPPORTED_INTEGER_LITERAL: &str = "E0014";
+
+/// An `aligned` attribute that asks for an alignment that is not a power of two, or is too large.
+pub const INVALID_ALIGNMENT: &str = "E0015";
+
+/// An escape sequence in a string literal that is not supported.
+pub const UNSUPPORTED_ESCAPE_SEQUENCE: &str = "E0016";
+
+/// Every code along with its extended explanation.
+const EXPLANATIONS: &[(&str, &str)] = &[
+    (
+        UNKNOWN_CHARACTER,
+        r#"A character that is not part of the C language was found.
+
+Erroneous code example:
+
+    int main(void) {
+        return 1 @ 2; // error: '@' is not a C operator
+    }
+
+Only the characters that make up C tokens may appear outside of comments and
+string literals. Remove the character, or replace it with the operator you
+meant to use.
+"#,
+    ),
+    (
+        UNTERMINATED_COMMENT,
+        r#"A block comment was opened with '/*' but never closed with '*/'.
+
+Erroneous code example:
+
+    int main(void) {
+        /* the answer
+        return 42;
+    }
+
+Everything after the '/*' is part of the comment, including the rest of the
+file. Block comments do not nest: the first '*/' ends the comment, no matter
+how many '/*' came before it. Close the comment where you meant it to end.
+"#,
+    ),
+    (
+        UNTERMINATED_STRING,
+        r#"A string literal was opened with '"' but the line ended before it was closed.
+
+Erroneous code example:
+
+    int main(void) {
+        "hello
+        return 0;
+    }
+
+String literals cannot span multiple lines. Close the string on the same line,
+and write '\n' inside of it if the string should contain a newline.
+"#,
+    ),
+    (
+        MALFORMED_NUMBER,
+        r#"A number was immediately followed by letters or underscores.
+
+Erroneous code example:
+
+    int main(void) {
+        return 123abc;
+    }
+
+Identifiers cannot start with a digit, so this is neither a number nor a name.
+Put a space or an operator between the number and the name, or rename the
+identifier so that it starts with a letter or an underscore.
+"#,
+    ),
+    (
+        UNEXPECTED_TOKEN,
+        r#"A token appeared somewhere the grammar does not allow it.
+
+Erroneous code example:
+
+    int main(void) {
+        return 2 + * 3;
+    }
+
+The error message says what the compiler expected to find instead. Often the
+fix is a missing or misspelled keyword, or a forgotten delimiter like ';' or
+')'.
+"#,
+    ),
+    (
+        UNEXPECTED_END_OF_FILE,
+        r#"The file ended while something was still being parsed.
+
+Erroneous code example:
+
+    int main(void) {
+        return 2;
+
+The error message says what the compiler expected to find next. Usually a
+closing '}' or ')' has gone missing, or the file was cut short.
+"#,
+    ),
+    (
+        TRAILING_TOKENS,
+        r#"There is code after the end of the program.
+
+Erroneous code example:
+
+    int main(void) {
+        return 0;
+    }
+
+    int x;
+
+For now, a program consists of exactly one function definition, and nothing may
+come after it except whitespace and comments.
+"#,
+    ),
+    (
+        INTEGER_TOO_LARGE,
+        r#"An integer literal is too large to be represented.
+
+Erroneous code example:
+
+    int main(void) {
+        return 99999999999;
+    }
+
+In C, an integer literal that does not fit in an `int` gets a wider type, like
+`long`. The only integer type supported so far is `int`, so every literal must
+be at most 2147483647.
+
+To write the smallest `int`, negate the largest one and subtract one, since
+`-2147483648` is the negation of a literal that is too large:
+
+    int main(void) {
+        return -2147483647 - 1;
+    }
+"#,
+    ),
+    (
+        RETURN_TYPE_MISMATCH,
+        r#"A `return` statement gives a value when the function returns none, or the other way around.
+
+Erroneous code example:
+
+    void f(void) {
+        return 1; // error: `f` returns `void`
+    }
+
+A function declared to return `void` has no value to give back, so its `return`
+statements must not have one. Write `return;`, or change the return type to
+`int` if the value was meant to be used.
+
+The opposite is an error too:
+
+    int f(void) {
+        return; // error: `f` returns `int`
+    }
+
+Whoever called `f` expects an `int`, so every `return` must say which one.
+"#,
+    ),
+    (
+        LINE_COMMENT,
+        r#"A `//` comment was used in C89, which only has `/* */` comments.
+
+Erroneous code example, compiled with `--std=c89`:
+
+    int main(void) {
+        return 0; // success
+    }
+
+Comments that run to the end of the line were added in C99. Write the comment
+as a block comment instead:
+
+    int main(void) {
+        return 0; /* success */
+    }
+
+or pick a later standard, like `--std=c99`.
+"#,
+    ),
+    (
+        MALFORMED_LINE_DIRECTIVE,
+        r#"A `#line` directive was not followed by a line number and an optional file name.
+
+Erroneous code example:
+
+    #line twelve "parser.y"
+    int main(void) {
+        return 0;
+    }
+
+A `#line` directive changes the line number, and optionally the file name,
+that diagnostics and debug information give for the lines after it. The line
+number has to be written in decimal, and the file name has to be a string
+literal:
+
+    #line 12 "parser.y"
+
+The markers that `gcc -E` writes, like `# 12 "parser.y" 2`, are accepted too.
+"#,
+    ),
+    (
+        DUPLICATE_GENERIC_ASSOCIATION,
+        r#"A `_Generic` selection has more than one association for the same type.
+
+Erroneous code example:
+
+    int main(void) {
+        return _Generic(0, int: 1, int: 2);
+    }
+
+A selection stands for the expression associated with the type of its
+controlling expression, so there can only be one for each type. The same goes
+for `default`, which may appear at most once. Remove the association that is
+not meant to be chosen.
+"#,
+    ),
+    (
+        NO_GENERIC_ASSOCIATION,
+        r#"No association of a `_Generic` selection matches the type of its controlling expression.
+
+Erroneous code example:
+
+    int main(void) {
+        return _Generic(0, bool: 1);
+    }
+
+The controlling expression `0` is an `int`, and there is no association for
+`int`, so the selection has no value. Add an association for the type, or a
+`default` one, which is chosen when no other association matches:
+
+    int main(void) {
+        return _Generic(0, bool: 1, default: 2);
+    }
+"#,
+    ),
+    (
+        UNSUPPORTED_INTEGER_LITERAL,
+        r#"An integer literal is written in a way that is valid C, but not supported yet.
+
+Erroneous code example:
+
+    int main(void) {
+        return 0x1F + 10u;
+    }
+
+Only decimal literals without a suffix are supported so far. Hexadecimal
+literals like `0x1F` and binary ones like `0b11111` have to be written in
+decimal, as `31`. Suffixes like `u` and `L` pick a wider or unsigned type, but
+the only integer type supported so far is `int`, so leave them out:
+
+    int main(void) {
+        return 31 + 10;
+    }
+"#,
+    ),
+    (
+        INVALID_ALIGNMENT,
+        r#"An `aligned` attribute asks for an alignment that cannot be given.
+
+Erroneous code example:
+
+    __attribute__((aligned(12))) int main(void) {
+        return 0;
+    }
+
+An alignment says what the address of a function has to be a multiple of, and
+addresses can only be aligned to powers of two, like 8 or 16. The largest
+alignment that can be asked for is 2147483648. To get the strictest alignment
+that anything on the target needs, leave the argument out:
+
+    __attribute__((aligned)) int main(void) {
+        return 0;
+    }
+"#,
+    ),
+    (
+        UNSUPPORTED_ESCAPE_SEQUENCE,
+        r#"A string literal contains an escape sequence that is not supported.
+
+Erroneous code example:
+
+    int main(void) {
+        asm("movl $1, %eax\x0a");
+        return 0;
+    }
+
+Only the simple escape sequences are supported so far: `\'`, `\"`, `\?`, `\\`,
+`\a`, `\b`, `\f`, `\n`, `\r`, `\t` and `\v`. Octal and hexadecimal escapes,
+like `\12` and `\x0a`, and universal character names, like `\u00e9`, are not.
+Any other character after a backslash is not an escape sequence at all. Write
+the character itself, or one of the simple escapes, instead:
+
+    int main(void) {
+        asm("movl $1, %eax\n");
+        return 0;
+    }
+"#,
+    ),
+];
+
+/// Get the extended explanation for a diagnostic code.
+///
+/// If the code does not exist, [`None`] is returned.
+///
+/// # Examples
+///
+/// ```
+/// use ecc::diagnostics::codes;
+///
+/// assert!(codes::explain(codes::UNKNOWN_CHARACTER).is_some());
+/// assert!(codes::explain("E9999").is_none());
+/// ```
+pub fn explain(code: &str) -> Option<&'static str> {
+    EXPLANATIONS
+        .iter()
+        .find(|(c, _)| *c == code)
+        .map(|(_, explanation)| *explanation)
+}
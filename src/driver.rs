@@ -0,0 +1,443 @@
+//! Compiling files on disk, which is what the `ecc` binary does.
+//!
+//! Everything here needs a file system and a way to run the linker, so it is only built with the
+//! `driver` feature. Without it, the library still compiles source held in memory, which is all
+//! targets like `wasm32-unknown-unknown` can do.
+
+use std::ffi::{OsStr, OsString};
+use std::fs::File;
+use std::io::{BufWriter, Write};
+use std::path::{Path, PathBuf};
+use std::process::{Command, Output};
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+use crate::ast;
+use crate::compiler::asm::Line;
+use crate::compiler::cfg;
+use crate::diagnostics::{Diagnostic, DiagnosticEmitter, LineMap};
+use crate::hooks::Hooks;
+use crate::source_file::SourceFile;
+use crate::span::Span;
+use crate::{
+    CompileError, CompileOptions, CompileResult, Emit, compile_named_source_into, depfile, elf,
+    interpret_named_source, lint_named_source,
+};
+
+/// Compile the given file, producing whatever `options` asks to emit.
+///
+/// Executables, static libraries and assembly are written to [`CompileOptions::out_dir`], or next
+/// to the source file without one, while syntax tree dumps and drawings are printed to stdout. Any
+/// other files that are needed along the way are kept in a temporary directory, and removed once
+/// the output is written. With [`CompileOptions::dependencies`], a make
+/// rule listing the files that were read is written too, next to the source file unless it says
+/// where. Every diagnostic is sent to the given emitter, including one for files that cannot be
+/// read or written and for linker failures.
+pub fn compile_file<P>(
+    path: P,
+    options: &CompileOptions,
+    emitter: &mut dyn DiagnosticEmitter,
+) -> CompileResult<()>
+where
+    P: AsRef<Path>,
+{
+    let path = path.as_ref();
+    let file_name = path.display().to_string();
+    let source = open_source(path, &file_name, emitter)?;
+
+    let result = compile_opened_file(path, &file_name, &source, options, emitter);
+    if let Err(e) = &result
+        && !e.is_reported()
+    {
+        emitter.emit(&failure(e), &file_name, &source);
+    }
+
+    result
+}
+
+/// Compile a file that has already been read.
+fn compile_opened_file(
+    path: &Path,
+    file_name: &str,
+    source: &str,
+    options: &CompileOptions,
+    emitter: &mut dyn DiagnosticEmitter,
+) -> CompileResult<()> {
+    let mut reports = Reports::default();
+    let mut compile_into = |out: &mut dyn Write| {
+        compile_named_source_into(file_name, source, options, emitter, &mut reports, out)
+    };
+    let dependency_file = options.dependencies.as_ref().and_then(|d| d.file.clone());
+    if let Some(out_dir) = &options.out_dir {
+        std::fs::create_dir_all(out_dir).map_err(in_file(out_dir))?;
+    }
+
+    let Some(output) = options.output_path(path) else {
+        match &dependency_file {
+            Some(file) if options.emit == Emit::Dependencies => {
+                let mut out = File::create(file).map_err(in_file(file))?;
+                compile_into(&mut out).map_err(|e| name_file(e, file))?;
+            }
+            _ => compile_into(&mut std::io::stdout().lock())?,
+        }
+        return write_stack_usage(path, file_name, source, &reports, options);
+    };
+
+    // The assembly goes straight to a file as it is generated. That file is in a directory of its
+    // own, along with everything else that is only needed until the output is written, so that
+    // nothing already next to the source is overwritten or removed, even if compiling fails.
+    let work_dir = WorkDir::create()?;
+    let assembly_file = work_dir.file_for(path, "s");
+    let out = File::create(&assembly_file).map_err(in_file(&assembly_file))?;
+    let mut out = BufWriter::new(out);
+    compile_into(&mut out)
+        .and_then(|()| Ok(out.flush()?))
+        .map_err(|e| name_file(e, &assembly_file))?;
+    write_stack_usage(path, file_name, source, &reports, options)?;
+
+    if options.dependencies.is_some() {
+        let dependency_file =
+            dependency_file.unwrap_or_else(|| options.place_output(&path.with_extension("d")));
+        let rule = depfile::for_source(file_name, options);
+        std::fs::write(&dependency_file, rule).map_err(in_file(&dependency_file))?;
+    }
+
+    let mut result = Ok(());
+    if options.print_size {
+        result = print_size(&assembly_file, options);
+    }
+    if result.is_ok() {
+        result = match options.emit {
+            Emit::Assembly => move_file(&assembly_file, &output),
+            Emit::Executable => link_program(&assembly_file, &output, options),
+            Emit::StaticLib => archive(&assembly_file, &output, options),
+            Emit::Ast | Emit::AstDot | Emit::CfgDot | Emit::Ir | Emit::Dependencies => {
+                unreachable!("{:?} has no output file", options.emit)
+            }
+        };
+    }
+
+    result
+}
+
+/// Write how much stack each function uses to a `.su` file, if the options ask for it and any code
+/// was generated.
+fn write_stack_usage(
+    path: &Path,
+    file_name: &str,
+    source: &str,
+    reports: &Reports,
+    options: &CompileOptions,
+) -> CompileResult<()> {
+    if !options.stack_usage || reports.stack_usage.is_empty() {
+        return Ok(());
+    }
+
+    let report_file = options.place_output(&path.with_extension("su"));
+    let report = reports.stack_usage_report(file_name, source);
+    std::fs::write(&report_file, report).map_err(in_file(&report_file))?;
+    Ok(())
+}
+
+/// Move a finished file from the work directory to where it belongs.
+///
+/// Renaming only works within a file system, and the work directory is often on another one, so
+/// the file is copied if it cannot be renamed.
+fn move_file(from: &Path, to: &Path) -> CompileResult<()> {
+    if std::fs::rename(from, to).is_err() {
+        std::fs::copy(from, to).map_err(in_file(to))?;
+    }
+    Ok(())
+}
+
+/// A directory for the files that are only needed while one file compiles, which is removed along
+/// with everything in it when it is dropped.
+struct WorkDir(PathBuf);
+
+impl WorkDir {
+    /// Create a new, empty directory in the system's temporary directory.
+    ///
+    /// Its name is unique to this process and to the compilation, so that neither other
+    /// invocations nor other files compiled by this one can get in the way.
+    fn create() -> CompileResult<Self> {
+        static CREATED: AtomicUsize = AtomicUsize::new(0);
+
+        let temp_dir = std::env::temp_dir();
+        loop {
+            let number = CREATED.fetch_add(1, Ordering::Relaxed);
+            let path = temp_dir.join(format!("ecc-{}-{number}", std::process::id()));
+            match std::fs::create_dir(&path) {
+                Ok(()) => return Ok(Self(path)),
+                // Left behind by an earlier process that had the same ID and was killed.
+                Err(e) if e.kind() == std::io::ErrorKind::AlreadyExists => continue,
+                Err(e) => return Err(in_file(&temp_dir)(e)),
+            }
+        }
+    }
+
+    /// Where a file named after the given source file, but with another extension, goes in the
+    /// directory.
+    fn file_for(&self, source: &Path, extension: &str) -> PathBuf {
+        let name = source.file_stem().unwrap_or_default();
+        self.0.join(name).with_extension(extension)
+    }
+}
+
+impl Drop for WorkDir {
+    fn drop(&mut self) {
+        let _ = std::fs::remove_dir_all(&self.0);
+    }
+}
+
+/// Read the given source file, reporting it to the emitter if that fails.
+///
+/// Changing a big source file while it is being compiled is undefined behavior, and can crash the
+/// compiler with `SIGBUS`. See [`SourceFile::open`].
+fn open_source(
+    path: &Path,
+    file_name: &str,
+    emitter: &mut dyn DiagnosticEmitter,
+) -> CompileResult<SourceFile> {
+    // SAFETY: This is not checked. The compiler never writes to its input, and the driver assumes
+    // that nothing else does while it runs, the same way `clang` does when it maps its input.
+    unsafe { SourceFile::open(path) }.map_err(|error| {
+        let e = in_file(path)(error);
+        emitter.emit(&failure(&e), file_name, "");
+        e
+    })
+}
+
+/// Make a diagnostic for an error that happened outside of the pipeline, and so was not reported
+/// yet.
+fn failure(e: &CompileError) -> Diagnostic {
+    let diagnostic = Diagnostic::error(e.to_string());
+    match e {
+        CompileError::Spawn { error, .. } if error.kind() == std::io::ErrorKind::NotFound => {
+            diagnostic.with_help(
+                "make sure that it is installed, or choose another program with `--link-with` or \
+                 `--archive-with`",
+            )
+        }
+        _ => diagnostic,
+    }
+}
+
+/// Say which file an I/O error happened in.
+fn in_file(path: &Path) -> impl FnOnce(std::io::Error) -> CompileError + '_ {
+    move |error| CompileError::File {
+        path: path.to_owned(),
+        error,
+    }
+}
+
+/// Say which file an I/O error that came out of the pipeline happened in, since the pipeline only
+/// knows that it was writing somewhere.
+fn name_file(e: CompileError, path: &Path) -> CompileError {
+    match e {
+        CompileError::Io(error) => in_file(path)(error),
+        e => e,
+    }
+}
+
+/// Run a command until it finishes, collecting what it printed.
+fn run(command: &mut Command) -> CompileResult<Output> {
+    command.output().map_err(|error| CompileError::Spawn {
+        program: command.get_program().into(),
+        error,
+    })
+}
+
+/// What the reports that the options can ask for are made from, collected while a file compiles.
+#[derive(Default)]
+struct Reports {
+    /// Every function, and where its name is in the source.
+    functions: Vec<(String, Span)>,
+
+    /// How many bytes of stack each function uses, in the order they were generated.
+    stack_usage: Vec<(String, usize)>,
+}
+
+impl Reports {
+    /// Say how much stack each function uses, in the format of `gcc -fstack-usage`.
+    ///
+    /// Nothing is ever allocated on the stack at run time, so every size is `static`.
+    fn stack_usage_report(&self, file_name: &str, source: &str) -> String {
+        let lines = LineMap::new(source);
+        let mut report = String::new();
+        for (name, bytes) in &self.stack_usage {
+            let (file, line, column) = self
+                .functions
+                .iter()
+                .find(|(function, _)| function == name)
+                .map(|(_, span)| lines.presumed_location(source, span.start))
+                .unwrap_or((None, 0, 0));
+            let file = file.unwrap_or(file_name);
+            report.push_str(&format!("{file}:{line}:{column}:{name}\t{bytes}\tstatic\n"));
+        }
+        report
+    }
+}
+
+impl Hooks for Reports {
+    fn after_parse(&mut self, program: &ast::Program) {
+        let function = &program.function;
+        self.functions
+            .push((function.name.clone(), function.name_span));
+    }
+
+    fn after_passes(&mut self, lines: &[Line]) {
+        self.stack_usage = cfg::build(lines)
+            .iter()
+            .map(|cfg| (cfg.name.clone(), cfg.stack_usage()))
+            .collect();
+    }
+}
+
+/// Check the given file for errors and run the enabled lints on it, without writing anything.
+pub fn lint_file<P>(
+    path: P,
+    options: &CompileOptions,
+    emitter: &mut dyn DiagnosticEmitter,
+) -> CompileResult<()>
+where
+    P: AsRef<Path>,
+{
+    let path = path.as_ref();
+    let file_name = path.display().to_string();
+    let source = open_source(path, &file_name, emitter)?;
+    lint_named_source(&file_name, &source, options, emitter)
+}
+
+/// Run the given file with the interpreter instead of compiling it, returning what its function
+/// returned.
+pub fn interpret_file<P>(
+    path: P,
+    options: &CompileOptions,
+    emitter: &mut dyn DiagnosticEmitter,
+) -> CompileResult<i32>
+where
+    P: AsRef<Path>,
+{
+    let path = path.as_ref();
+    let file_name = path.display().to_string();
+    let source = open_source(path, &file_name, emitter)?;
+    interpret_named_source(&file_name, &source, options, emitter)
+}
+
+/// Assemble the given assembly file, and print how many bytes each section and function came out
+/// to.
+fn print_size(assembly_file: &Path, options: &CompileOptions) -> CompileResult<()> {
+    let object_file = assembly_file.with_extension("size.o");
+    let output = run(toolchain(options).args([
+        OsStr::new("-c"),
+        OsStr::new("-o"),
+        object_file.as_os_str(),
+        assembly_file.as_os_str(),
+    ]))?;
+    std::io::stderr().write_all(&output.stderr)?;
+    if !output.status.success() {
+        return Err(CompileError::Link(output.status));
+    }
+
+    let object = std::fs::read(&object_file).map_err(in_file(&object_file))?;
+    let sizes = elf::sizes(&object).ok_or_else(|| {
+        CompileError::Codegen(format!("could not read `{}`", object_file.display()))
+    })?;
+
+    println!("{:<24}{:>8}", "section", "bytes");
+    for (name, size) in &sizes.sections {
+        println!("{name:<24}{size:>8}");
+    }
+    println!("{:<24}{:>8}", "function", "bytes");
+    for (name, size) in &sizes.functions {
+        println!("{name:<24}{size:>8}");
+    }
+
+    Ok(())
+}
+
+/// Assemble the given assembly file, and put the object file in a static library named after it
+/// with the archiver the options ask for.
+///
+/// The library is made afresh, so that nothing from an earlier build lingers in it.
+fn archive(assembly_file: &Path, library: &Path, options: &CompileOptions) -> CompileResult<()> {
+    let object_file = assembly_file.with_extension("o");
+    let output = run(toolchain(options).args([
+        OsStr::new("-c"),
+        OsStr::new("-o"),
+        object_file.as_os_str(),
+        assembly_file.as_os_str(),
+    ]))?;
+    std::io::stderr().write_all(&output.stderr)?;
+    if !output.status.success() {
+        return Err(CompileError::Link(output.status));
+    }
+
+    if library.exists() {
+        std::fs::remove_file(library).map_err(in_file(library))?;
+    }
+
+    let output = run(Command::new(options.archiver()).args([
+        OsStr::new("rcs"),
+        library.as_os_str(),
+        object_file.as_os_str(),
+    ]))?;
+    std::io::stderr().write_all(&output.stderr)?;
+
+    if output.status.success() {
+        Ok(())
+    } else {
+        Err(CompileError::Link(output.status))
+    }
+}
+
+/// Start a command that runs the program the options assemble and link with.
+fn toolchain(options: &CompileOptions) -> Command {
+    let mut command = match &options.linker {
+        Some(linker) => Command::new(linker),
+        None => Command::new(options.target.default_linker()),
+    };
+    if let Some(sysroot) = &options.sysroot {
+        let mut arg = OsString::from("--sysroot=");
+        arg.push(sysroot);
+        command.arg(arg);
+    }
+
+    command
+}
+
+/// Run `gcc`, or whichever linker the options ask for, on the given assembly file, writing the
+/// executable to the given path.
+///
+/// Since I do not really feel like writing my own linker and standard library, it seems like a
+/// natural choice to link the program in this way. The linker's own output is passed through.
+///
+/// A profiled program is linked with `-pg`, which brings in `mcount` and the code that writes
+/// `gmon.out`. A freestanding program is linked with `-nostdlib`, and statically, since there is no
+/// dynamic loader to start it either.
+fn link_program(
+    assembly_file: &Path,
+    executable: &Path,
+    options: &CompileOptions,
+) -> CompileResult<()> {
+    let mut command = toolchain(options);
+    if options.profiling {
+        command.arg("-pg");
+    }
+    if options.freestanding {
+        command.args(["-nostdlib", "-static"]);
+    }
+    let output = run(command.args([
+        OsStr::new("-o"),
+        executable.as_os_str(),
+        assembly_file.as_os_str(),
+    ]))?;
+
+    std::io::stdout().write_all(&output.stdout)?;
+    std::io::stderr().write_all(&output.stderr)?;
+
+    if output.status.success() {
+        Ok(())
+    } else {
+        Err(CompileError::Link(output.status))
+    }
+}
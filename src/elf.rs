@@ -0,0 +1,112 @@
+//! Reading sizes out of ELF object files.
+//!
+//! This is just enough of the format to say how big each section and function came out, which is
+//! what `--print-size` reports. Only 64-bit little-endian objects are understood, since those are
+//! the only ones the compiler makes.
+
+/// How many bytes each part of an object file takes up.
+#[derive(Clone, PartialEq, Eq, Default, Debug)]
+pub(crate) struct Sizes {
+    /// Every section that is loaded into memory when the program runs, like `.text` and `.data`,
+    /// in the order they appear in the file.
+    pub sections: Vec<(String, u64)>,
+
+    /// Every function, in the order they appear in the symbol table.
+    pub functions: Vec<(String, u64)>,
+}
+
+/// Section type of a symbol table.
+const SHT_SYMTAB: u32 = 2;
+
+/// Section flag of sections that are loaded into memory.
+const SHF_ALLOC: u64 = 2;
+
+/// Symbol type of functions.
+const STT_FUNC: u8 = 2;
+
+/// Read the sizes of the sections and functions in an object file, or `None` if it is not a
+/// 64-bit little-endian ELF file.
+pub(crate) fn sizes(object: &[u8]) -> Option<Sizes> {
+    if object.get(..6)? != b"\x7fELF\x02\x01" {
+        return None;
+    }
+
+    let section_offset = u64_at(object, 0x28)? as usize;
+    let section_size = u16_at(object, 0x3a)? as usize;
+    let section_count = u16_at(object, 0x3c)? as usize;
+    let names_index = u16_at(object, 0x3e)? as usize;
+
+    let sections = (0..section_count)
+        .map(|i| Section::read(object, section_offset + i * section_size))
+        .collect::<Option<Vec<_>>>()?;
+    let section_names = sections.get(names_index)?.data(object)?;
+
+    let mut sizes = Sizes::default();
+    for section in &sections {
+        if section.flags & SHF_ALLOC != 0 {
+            sizes
+                .sections
+                .push((string_at(section_names, section.name)?, section.size));
+        }
+
+        if section.kind == SHT_SYMTAB {
+            let names = sections.get(section.link as usize)?.data(object)?;
+            for symbol in section.data(object)?.chunks_exact(24) {
+                if symbol[4] & 0xf == STT_FUNC {
+                    let name = string_at(names, u32_at(symbol, 0)?)?;
+                    sizes.functions.push((name, u64_at(symbol, 16)?));
+                }
+            }
+        }
+    }
+
+    Some(sizes)
+}
+
+/// The parts of a section header that are needed.
+struct Section {
+    name: u32,
+    kind: u32,
+    flags: u64,
+    offset: u64,
+    size: u64,
+    link: u32,
+}
+
+impl Section {
+    fn read(object: &[u8], at: usize) -> Option<Self> {
+        Some(Self {
+            name: u32_at(object, at)?,
+            kind: u32_at(object, at + 4)?,
+            flags: u64_at(object, at + 8)?,
+            offset: u64_at(object, at + 24)?,
+            size: u64_at(object, at + 32)?,
+            link: u32_at(object, at + 40)?,
+        })
+    }
+
+    /// The bytes of the section in the file.
+    fn data<'a>(&self, object: &'a [u8]) -> Option<&'a [u8]> {
+        let start = self.offset as usize;
+        object.get(start..start.checked_add(self.size as usize)?)
+    }
+}
+
+fn u16_at(bytes: &[u8], at: usize) -> Option<u16> {
+    Some(u16::from_le_bytes(bytes.get(at..at + 2)?.try_into().ok()?))
+}
+
+fn u32_at(bytes: &[u8], at: usize) -> Option<u32> {
+    Some(u32::from_le_bytes(bytes.get(at..at + 4)?.try_into().ok()?))
+}
+
+fn u64_at(bytes: &[u8], at: usize) -> Option<u64> {
+    Some(u64::from_le_bytes(bytes.get(at..at + 8)?.try_into().ok()?))
+}
+
+/// Read the nul-terminated string at an offset into a string table.
+fn string_at(table: &[u8], at: u32) -> Option<String> {
+    let bytes = table.get(at as usize..)?;
+    let end = bytes.iter().position(|&byte| byte == 0)?;
+    Some(String::from_utf8_lossy(&bytes[..end]).into_owned())
+}
@@ -0,0 +1,144 @@
+use std::io;
+use std::path::PathBuf;
+use std::process::ExitStatus;
+
+use crate::diagnostics::Diagnostic;
+use crate::lexer::LexError;
+
+/// An error that stops compilation.
+///
+/// Each variant corresponds to a stage of the pipeline. The detailed diagnostics for a failure
+/// have already been sent to the emitter by the time one of these is returned, so this type is
+/// mostly useful for deciding what to do next, and for using `?` in code built on top of ecc.
+#[derive(Debug)]
+pub enum CompileError {
+    /// The source code contained lexical errors.
+    Lex(Vec<LexError>),
+
+    /// The tokens did not form a valid program.
+    Parse(Diagnostic),
+
+    /// The program was syntactically valid, but meaningless.
+    Semantic(Vec<Diagnostic>),
+
+    /// Lints or pedantic checks found problems, and `-Werror` or `-pedantic-errors` made them
+    /// errors.
+    Lint(Vec<Diagnostic>),
+
+    /// The interpreter could not finish running the program.
+    Runtime(Diagnostic),
+
+    /// Assembly could not be generated for the program.
+    Codegen(String),
+
+    /// Reading or writing something other than a file, like stdout, failed.
+    Io(io::Error),
+
+    /// A file could not be read, written or removed.
+    File { path: PathBuf, error: io::Error },
+
+    /// Another program, like the linker, could not be started.
+    Spawn { program: PathBuf, error: io::Error },
+
+    /// The linker ran, but failed.
+    Link(ExitStatus),
+}
+
+impl std::fmt::Display for CompileError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Lex(errors) => match errors.as_slice() {
+                [e] => write!(f, "lexing failed: {e}"),
+                _ => write!(f, "lexing failed with {} errors", errors.len()),
+            },
+            Self::Parse(diagnostic) => write!(f, "parsing failed: {}", diagnostic.message),
+            Self::Semantic(diagnostics) => match diagnostics.as_slice() {
+                [d] => write!(f, "semantic analysis failed: {}", d.message),
+                _ => write!(
+                    f,
+                    "semantic analysis failed with {} errors",
+                    diagnostics.len()
+                ),
+            },
+            Self::Lint(diagnostics) => match diagnostics.as_slice() {
+                [d] => write!(f, "linting failed: {}", d.message),
+                _ => write!(f, "linting failed with {} errors", diagnostics.len()),
+            },
+            Self::Runtime(diagnostic) => write!(f, "interpreting failed: {}", diagnostic.message),
+            Self::Codegen(message) => write!(f, "code generation failed: {message}"),
+            Self::Io(e) => write!(f, "{e}"),
+            Self::File { path, error } => write!(f, "`{}`: {}", path.display(), reason(error)),
+            Self::Spawn { program, error } => {
+                write!(
+                    f,
+                    "could not run `{}`: {}",
+                    program.display(),
+                    reason(error)
+                )
+            }
+            Self::Link(status) => write!(f, "linking failed: linker {status}"),
+        }
+    }
+}
+
+impl std::error::Error for CompileError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            Self::Io(error) | Self::File { error, .. } | Self::Spawn { error, .. } => Some(error),
+            _ => None,
+        }
+    }
+}
+
+impl CompileError {
+    /// Whether the diagnostics for the error were already sent to the emitter by the stage that
+    /// failed.
+    ///
+    /// The other errors happen outside of the pipeline, and whoever gets one still has to say
+    /// what went wrong.
+    pub fn is_reported(&self) -> bool {
+        match self {
+            Self::Lex(_)
+            | Self::Parse(_)
+            | Self::Semantic(_)
+            | Self::Lint(_)
+            | Self::Runtime(_) => true,
+            Self::Codegen(_)
+            | Self::Io(_)
+            | Self::File { .. }
+            | Self::Spawn { .. }
+            | Self::Link(_) => false,
+        }
+    }
+}
+
+/// Say why an I/O error happened, in words that do not need the error code to make sense.
+fn reason(error: &io::Error) -> String {
+    match error.kind() {
+        io::ErrorKind::NotFound => "no such file or directory".to_owned(),
+        io::ErrorKind::PermissionDenied => "permission denied".to_owned(),
+        io::ErrorKind::InvalidData => "the file is not valid UTF-8".to_owned(),
+        _ => error.to_string(),
+    }
+}
+
+impl From<LexError> for CompileError {
+    fn from(e: LexError) -> Self {
+        Self::Lex(vec![e])
+    }
+}
+
+impl From<Vec<LexError>> for CompileError {
+    fn from(errors: Vec<LexError>) -> Self {
+        Self::Lex(errors)
+    }
+}
+
+impl From<io::Error> for CompileError {
+    fn from(e: io::Error) -> Self {
+        Self::Io(e)
+    }
+}
+
+/// A simple type alias for a [`Result`] whose [`Err`] variant contains a [`CompileError`].
+pub type CompileResult<T> = Result<T, CompileError>;
@@ -0,0 +1,125 @@
+//! C bindings, for embedding the compiler in other languages.
+//!
+//! The crate is also built as a `cdylib`, and `include/ecc.h` declares what is exported here.
+//! Strings handed out by these functions are owned by the library and must be released with
+//! [`ecc_string_free`].
+
+use std::ffi::{CStr, CString, c_char, c_int};
+use std::panic::{self, AssertUnwindSafe};
+use std::ptr;
+
+use crate::diagnostics::{self, Diagnostic};
+use crate::options::CompileOptions;
+
+/// The source compiled successfully.
+pub const ECC_OK: c_int = 0;
+
+/// The source has errors, which are described by the diagnostics.
+pub const ECC_COMPILE_ERROR: c_int = 1;
+
+/// An argument was null or the source was not valid UTF-8.
+pub const ECC_INVALID_ARGUMENT: c_int = 2;
+
+/// The compiler itself crashed. This is always a bug in `ecc`.
+pub const ECC_INTERNAL_ERROR: c_int = 3;
+
+/// Compile a NUL-terminated string of C source code to assembly.
+///
+/// On success, `*assembly_out` is set to the generated assembly. Whether or not compilation
+/// succeeded, `*diagnostics_out` is set to every diagnostic produced, one JSON object per line in
+/// the format of `--error-format=json`. Either out-parameter may be null if the caller is not
+/// interested in it. Anything not set is set to null instead.
+///
+/// Returns one of the `ECC_*` status codes.
+///
+/// # Safety
+///
+/// `source` must point to a NUL-terminated string, and each out-parameter must either be null or
+/// point to memory that can hold a pointer.
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn ecc_compile(
+    source: *const c_char,
+    assembly_out: *mut *mut c_char,
+    diagnostics_out: *mut *mut c_char,
+) -> c_int {
+    // SAFETY: The caller promises that the out-parameters are either null or writable.
+    unsafe {
+        set_out(assembly_out, None);
+        set_out(diagnostics_out, None);
+    }
+
+    if source.is_null() {
+        return ECC_INVALID_ARGUMENT;
+    }
+
+    // SAFETY: The caller promises that `source` is NUL-terminated.
+    let Ok(source) = unsafe { CStr::from_ptr(source) }.to_str() else {
+        return ECC_INVALID_ARGUMENT;
+    };
+
+    let mut emitted: Vec<Diagnostic> = Vec::new();
+    let result = panic::catch_unwind(AssertUnwindSafe(|| {
+        crate::compile_source(source, &CompileOptions::new(), &mut emitted)
+    }));
+
+    let Ok(result) = result else {
+        return ECC_INTERNAL_ERROR;
+    };
+
+    let rendered: String = emitted
+        .iter()
+        .map(|d| diagnostics::to_json(d, "<source>", source) + "\n")
+        .collect();
+
+    // SAFETY: As above.
+    unsafe {
+        set_out(diagnostics_out, Some(rendered));
+    }
+
+    match result {
+        Ok(assembly) => {
+            // SAFETY: As above.
+            unsafe {
+                set_out(assembly_out, Some(assembly));
+            }
+            ECC_OK
+        }
+        Err(_) => ECC_COMPILE_ERROR,
+    }
+}
+
+/// Release a string returned by this library. Passing null does nothing.
+///
+/// # Safety
+///
+/// `string` must be null or a string returned by this library that has not been freed yet.
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn ecc_string_free(string: *mut c_char) {
+    if !string.is_null() {
+        // SAFETY: The caller promises that the string came from `CString::into_raw` below.
+        drop(unsafe { CString::from_raw(string) });
+    }
+}
+
+/// Hand a string to the caller through an out-parameter, if they asked for it.
+///
+/// Neither assembly nor diagnostics can contain NUL bytes, since they are built from the
+/// compiler's own messages and pieces of the source, which came from a C string.
+///
+/// # Safety
+///
+/// `out` must be null or writable.
+unsafe fn set_out(out: *mut *mut c_char, value: Option<String>) {
+    if out.is_null() {
+        return;
+    }
+
+    let value = value
+        .and_then(|value| CString::new(value).ok())
+        .map_or(ptr::null_mut(), CString::into_raw);
+
+    // SAFETY: The caller promises that `out` is writable.
+    unsafe {
+        *out = value;
+    }
+}
@@ -0,0 +1,125 @@
+//! Classifying source code for syntax highlighting.
+//!
+//! Editors color code by what each piece of it means. A plain lexer can tell keywords from
+//! identifiers, but knowing that an identifier names a function takes semantic analysis, so
+//! [`classify`] runs the front end when it can and falls back to purely lexical categories when
+//! the code does not parse.
+
+use crate::lexer::Lexer;
+use crate::parser;
+use crate::sema::{self, SymbolKind};
+use crate::span::Span;
+use crate::token::{Token, TokenKind};
+
+/// What a piece of source code is, as far as highlighting is concerned.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum SemanticKind {
+    /// A keyword, like `int` or `return`.
+    Keyword,
+
+    /// An identifier that is not known to be anything more specific.
+    Identifier,
+
+    /// The name of a function.
+    Function,
+
+    /// An integer literal.
+    Number,
+
+    /// A string literal.
+    String,
+
+    /// A comment.
+    Comment,
+
+    /// An operator, like `+` or `!`.
+    Operator,
+}
+
+/// A classified piece of source code.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub struct SemanticToken {
+    /// Where the piece of code is.
+    pub span: Span,
+
+    /// What it is.
+    pub kind: SemanticKind,
+}
+
+/// Classify every interesting piece of the given source code, in order.
+///
+/// Delimiters and whitespace are not interesting, so they are left out. Code that does not lex is
+/// skipped over, and the rest of the source is still classified.
+///
+/// # Examples
+///
+/// ```
+/// use ecc::highlight::{self, SemanticKind};
+///
+/// let source = "int main(void) { return 0; } // done";
+/// let kinds: Vec<_> = highlight::classify(source)
+///     .into_iter()
+///     .map(|token| token.kind)
+///     .collect();
+///
+/// assert_eq!(
+///     kinds,
+///     [
+///         SemanticKind::Keyword,
+///         SemanticKind::Function,
+///         SemanticKind::Keyword,
+///         SemanticKind::Keyword,
+///         SemanticKind::Number,
+///         SemanticKind::Comment,
+///     ]
+/// );
+/// ```
+pub fn classify(source: &str) -> Vec<SemanticToken> {
+    let tokens: Vec<Token> = Lexer::new(source)
+        .with_comments()
+        .filter_map(Result::ok)
+        .collect();
+
+    let functions = function_names(&tokens);
+
+    tokens
+        .iter()
+        .filter_map(|token| {
+            let kind = match token.kind {
+                TokenKind::LiteralIdentifier if functions.contains(&token.span) => {
+                    SemanticKind::Function
+                }
+                TokenKind::LiteralIdentifier => SemanticKind::Identifier,
+                TokenKind::LiteralInteger => SemanticKind::Number,
+                TokenKind::LiteralString => SemanticKind::String,
+                TokenKind::SpecialComment => SemanticKind::Comment,
+                kind if kind.is_keyword() => SemanticKind::Keyword,
+                kind if kind.is_operator() => SemanticKind::Operator,
+                _ => return None,
+            };
+
+            Some(SemanticToken {
+                span: token.span,
+                kind,
+            })
+        })
+        .collect()
+}
+
+/// Find where functions are named, if the code makes enough sense to tell.
+fn function_names(tokens: &[Token]) -> Vec<Span> {
+    let code = tokens
+        .iter()
+        .filter(|token| !token.kind.is_trivia())
+        .copied();
+
+    let Ok(program) = parser::parse_token_stream(code) else {
+        return Vec::new();
+    };
+
+    sema::analyze(&program)
+        .iter()
+        .filter(|symbol| symbol.kind == SymbolKind::Function)
+        .map(|symbol| symbol.span)
+        .collect()
+}
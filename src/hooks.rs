@@ -0,0 +1,126 @@
+//! Callbacks into the compilation pipeline.
+//!
+//! Implementing [`Hooks`] and passing it to [`crate::compile_source_with_hooks`] lets library users
+//! look at what each stage of the compiler produced, without having to drive the stages
+//! themselves. Every method does nothing by default, so only the interesting stages need to be
+//! overridden.
+
+use crate::ast;
+use crate::compiler::asm::Line;
+use crate::compiler::passes::PassManager;
+use crate::sema::SymbolTable;
+use crate::token::Token;
+
+/// Callbacks invoked after each stage of the pipeline, with what that stage produced.
+///
+/// A stage's hook is only called if the stage succeeded, and stages that the requested output does
+/// not need are not run at all, so their hooks are not called either.
+///
+/// # Examples
+///
+/// ```
+/// use ecc::CompileOptions;
+/// use ecc::diagnostics::Diagnostic;
+/// use ecc::hooks::Hooks;
+/// use ecc::token::Token;
+///
+/// #[derive(Default)]
+/// struct CountTokens(usize);
+///
+/// impl Hooks for CountTokens {
+///     fn after_lex(&mut self, tokens: &[Token<'_>]) {
+///         self.0 = tokens.len();
+///     }
+/// }
+///
+/// let mut hooks = CountTokens::default();
+/// let mut diagnostics: Vec<Diagnostic> = Vec::new();
+/// ecc::compile_source_with_hooks(
+///     "int main(void) { return 0; }",
+///     &CompileOptions::new(),
+///     &mut diagnostics,
+///     &mut hooks,
+/// )
+/// .unwrap();
+///
+/// // Ten tokens, plus the end of file.
+/// assert_eq!(hooks.0, 11);
+/// ```
+pub trait Hooks {
+    /// Called with every token in the source, ending with the end of file token.
+    fn after_lex(&mut self, tokens: &[Token<'_>]) {
+        let _ = tokens;
+    }
+
+    /// Called with the syntax tree.
+    fn after_parse(&mut self, program: &ast::Program) {
+        let _ = program;
+    }
+
+    /// Called with the symbol table produced by semantic analysis.
+    fn after_sema(&mut self, symbols: &SymbolTable) {
+        let _ = symbols;
+    }
+
+    /// Called with the optimization passes that are about to run over the generated code, so that
+    /// passes can be added, e.g. with [`PassManager::add`].
+    fn before_passes(&mut self, passes: &mut PassManager) {
+        let _ = passes;
+    }
+
+    /// Called with the name of a pass and the generated code, right after that pass has run over
+    /// it.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use ecc::CompileOptions;
+    /// use ecc::compiler::asm::Line;
+    /// use ecc::diagnostics::Diagnostic;
+    /// use ecc::hooks::Hooks;
+    ///
+    /// #[derive(Default)]
+    /// struct PassNames(Vec<String>);
+    ///
+    /// impl Hooks for PassNames {
+    ///     fn after_pass(&mut self, name: &str, _: &[Line]) {
+    ///         self.0.push(name.to_string());
+    ///     }
+    /// }
+    ///
+    /// let mut hooks = PassNames::default();
+    /// let mut diagnostics: Vec<Diagnostic> = Vec::new();
+    /// ecc::compile_source_with_hooks(
+    ///     "int main(void) { return 2 + 3; }",
+    ///     &CompileOptions::new().with_passes(["fold", "dce"]),
+    ///     &mut diagnostics,
+    ///     &mut hooks,
+    /// )
+    /// .unwrap();
+    ///
+    /// assert_eq!(hooks.0, ["fold", "dce"]);
+    /// ```
+    fn after_pass(&mut self, name: &str, lines: &[Line]) {
+        let _ = (name, lines);
+    }
+
+    /// Called with the generated code, after every pass has run over it.
+    fn after_passes(&mut self, lines: &[Line]) {
+        let _ = lines;
+    }
+
+    /// Called with the generated assembly.
+    ///
+    /// This is only called when the assembly is collected into a string, as with
+    /// [`crate::compile_source_with_hooks`], and not when the driver streams it to a file.
+    /// [`Hooks::after_passes`] is called either way, with the code the assembly is printed from.
+    #[deprecated(
+        note = "not called when the assembly is streamed to a file; use `after_passes` instead"
+    )]
+    fn after_codegen(&mut self, assembly: &str) {
+        let _ = assembly;
+    }
+}
+
+/// No hooks at all.
+impl Hooks for () {}
@@ -0,0 +1,128 @@
+//! Running programs directly from the syntax tree.
+//!
+//! The interpreter gives the same answers as the generated code, without an assembler, a linker,
+//! or even an x86-64 machine. That makes it handy on platforms without `gcc`, and as a second
+//! opinion when testing the code generator: a program that gives different results when
+//! interpreted and when compiled has found a bug in one of them.
+//!
+//! Arithmetic wraps around on overflow, like the generated code does. Operations that would make
+//! the generated code trap, like dividing by zero, stop the interpreter with an error instead.
+
+use crate::ast::{BinaryOp, Expr, Function, Program, Statement, UnaryOp};
+use crate::diagnostics::Diagnostic;
+use crate::sema;
+use crate::types::Type;
+
+/// A simple type alias for a [`Result`] whose [`Err`] variant contains a [`Diagnostic`].
+pub type InterpResult<T> = Result<T, Diagnostic>;
+
+/// Run a program, returning what its function returned.
+///
+/// # Examples
+///
+/// ```
+/// use ecc::{interp, lexer, parser};
+///
+/// let tokens = lexer::tokenize("int main(void) { return (1 + 2) * -3; }").unwrap();
+/// let program = parser::parse_token_stream(tokens).unwrap();
+///
+/// assert_eq!(interp::run_program(&program).unwrap(), -9);
+/// ```
+pub fn run_program(program: &Program) -> InterpResult<i32> {
+    run_function(&program.function)
+}
+
+/// Run a function, returning what it returned.
+///
+/// # Examples
+///
+/// ```
+/// use ecc::{interp, lexer, parser};
+///
+/// let tokens = lexer::tokenize("bool f(void) { return true + true; }").unwrap();
+/// let function = parser::parse_function(tokens).unwrap();
+///
+/// assert_eq!(interp::run_function(&function).unwrap(), 1);
+/// ```
+pub fn run_function(function: &Function) -> InterpResult<i32> {
+    // Nothing branches yet, and every statement either returns or cannot be interpreted, so only
+    // the first one ever runs. A function that falls off the end or returns `void` gives 0, which
+    // is what `main` does and as good as anything for the others, whose callers cannot rely on the
+    // value.
+    match function.body.first() {
+        Some(Statement::Return(Some(expr))) if function.return_type == Type::Bool => {
+            Ok((eval_expr(expr)? != 0) as i32)
+        }
+        Some(Statement::Return(Some(expr))) => eval_expr(expr),
+        Some(Statement::Return(None)) | None => Ok(0),
+        Some(Statement::Asm { span, .. }) => {
+            Err(Diagnostic::error("inline assembly cannot be interpreted")
+                .with_span(*span)
+                .with_note("compile the program instead of running it with `--interpret`"))
+        }
+    }
+}
+
+/// Evaluate an expression.
+pub fn eval_expr(expr: &Expr) -> InterpResult<i32> {
+    match expr {
+        Expr::Integer { value, .. } => Ok(*value),
+        Expr::Boolean { value, .. } => Ok(*value as i32),
+
+        Expr::Unary {
+            operator, operand, ..
+        } => {
+            let operand = eval_expr(operand)?;
+            Ok(match operator {
+                UnaryOp::Compliment => !operand,
+                UnaryOp::NegateArith => operand.wrapping_neg(),
+                UnaryOp::NegateLogical => (operand == 0) as i32,
+            })
+        }
+
+        Expr::Binary {
+            operator,
+            left,
+            right,
+            span,
+        } => {
+            let left = eval_expr(left)?;
+            let right = eval_expr(right)?;
+
+            match operator {
+                BinaryOp::Plus => Ok(left.wrapping_add(right)),
+                BinaryOp::Minus => Ok(left.wrapping_sub(right)),
+                BinaryOp::Times => Ok(left.wrapping_mul(right)),
+                BinaryOp::Divide => left
+                    .checked_div(right)
+                    .ok_or_else(|| division_error(left, right).with_span(*span)),
+                BinaryOp::Mod => left
+                    .checked_rem(right)
+                    .ok_or_else(|| division_error(left, right).with_span(*span)),
+            }
+        }
+
+        Expr::Generic {
+            controlling,
+            associations,
+            span,
+        } => match sema::select(controlling, associations) {
+            Some(selected) => eval_expr(selected),
+            None => Err(Diagnostic::error(
+                "no association matches the controlling expression's type",
+            )
+            .with_span(*span)),
+        },
+    }
+}
+
+/// Explain why a division could not be done.
+fn division_error(left: i32, right: i32) -> Diagnostic {
+    if right == 0 {
+        Diagnostic::error("division by zero")
+    } else {
+        Diagnostic::error(format!(
+            "the result of dividing {left} by {right} does not fit in an `int`"
+        ))
+    }
+}
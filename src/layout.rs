@@ -0,0 +1,274 @@
+//! Where values are put in memory.
+//!
+//! Sizes and alignments follow the System V ABI for x86-64, which is what `gcc` uses, so that
+//! structs and unions laid out here can be shared with code it compiled. Nothing in the language
+//! needs a layout yet, but `sizeof`, member access, and `offsetof` will all be answered from here.
+
+use crate::types::Type;
+
+/// How much memory a value takes, and which addresses it can start at.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub struct Layout {
+    /// The size in bytes, which is always a multiple of the alignment.
+    pub size: u64,
+
+    /// The alignment in bytes, which is a power of two. Values must start at addresses that are a
+    /// multiple of it.
+    pub align: u64,
+}
+
+impl Layout {
+    /// The layout of values of a type, or `None` for types that values cannot have, like `void`
+    /// and functions.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use ecc::layout::Layout;
+    /// use ecc::types::Type;
+    ///
+    /// assert_eq!(Layout::of(&Type::Int), Some(Layout { size: 4, align: 4 }));
+    /// ```
+    pub fn of(ty: &Type) -> Option<Self> {
+        match ty {
+            Type::Int => Some(Self { size: 4, align: 4 }),
+            Type::Bool => Some(Self { size: 1, align: 1 }),
+            Type::Void | Type::Function { .. } => None,
+        }
+    }
+}
+
+/// The layout of a struct or union, and where each member is in it.
+#[derive(Clone, PartialEq, Eq, Debug)]
+pub struct RecordLayout {
+    layout: Layout,
+    members: Vec<Member>,
+
+    /// Whether the members share memory, rather than coming one after another.
+    is_union: bool,
+}
+
+/// A member of a struct or union.
+#[derive(Clone, PartialEq, Eq, Debug)]
+pub struct Member {
+    /// The member's name.
+    pub name: String,
+
+    /// How far from the start of the record the member is, in bytes.
+    pub offset: u64,
+
+    /// The layout of the member itself.
+    pub layout: Layout,
+}
+
+impl RecordLayout {
+    /// Lay out a struct, whose members come one after another in the order they are declared.
+    ///
+    /// Each member starts at the first offset after the one before it that suits its alignment.
+    /// The struct is as aligned as its most aligned member, and padded at the end to a multiple of
+    /// that, so that every element of an array of them is aligned too.
+    ///
+    /// # Panics
+    ///
+    /// If a member's alignment is not a power of two.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use ecc::layout::{Layout, RecordLayout};
+    ///
+    /// let char_ = Layout { size: 1, align: 1 };
+    /// let int = Layout { size: 4, align: 4 };
+    ///
+    /// // struct { char c; int i; char d; }
+    /// let record = RecordLayout::structure([("c", char_), ("i", int), ("d", char_)]);
+    ///
+    /// assert_eq!(record.offset_of("i"), Some(4));
+    /// assert_eq!(record.offset_of("d"), Some(8));
+    /// assert_eq!(record.layout(), Layout { size: 12, align: 4 });
+    /// ```
+    pub fn structure<S: Into<String>>(members: impl IntoIterator<Item = (S, Layout)>) -> Self {
+        let mut size = 0;
+        let mut align = 1;
+        let members = members
+            .into_iter()
+            .map(|(name, layout)| {
+                let offset = align_to(size, layout.align);
+                size = offset + layout.size;
+                align = align.max(layout.align);
+                Member {
+                    name: name.into(),
+                    offset,
+                    layout,
+                }
+            })
+            .collect();
+
+        Self {
+            layout: Layout {
+                size: align_to(size, align),
+                align,
+            },
+            members,
+            is_union: false,
+        }
+    }
+
+    /// Lay out a union, whose members all start at the beginning and share the same memory.
+    ///
+    /// The union is as big as its biggest member and as aligned as its most aligned one, rounded
+    /// up to a multiple of the alignment.
+    ///
+    /// # Panics
+    ///
+    /// If a member's alignment is not a power of two.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use ecc::layout::{Layout, RecordLayout};
+    ///
+    /// let bytes = Layout { size: 5, align: 1 };
+    /// let int = Layout { size: 4, align: 4 };
+    ///
+    /// // union { char bytes[5]; int i; }
+    /// let record = RecordLayout::union([("bytes", bytes), ("i", int)]);
+    ///
+    /// assert_eq!(record.offset_of("i"), Some(0));
+    /// assert_eq!(record.layout(), Layout { size: 8, align: 4 });
+    /// ```
+    pub fn union<S: Into<String>>(members: impl IntoIterator<Item = (S, Layout)>) -> Self {
+        let members: Vec<_> = members
+            .into_iter()
+            .map(|(name, layout)| {
+                check_align(layout.align);
+                Member {
+                    name: name.into(),
+                    offset: 0,
+                    layout,
+                }
+            })
+            .collect();
+
+        let size = members.iter().map(|member| member.layout.size).max();
+        let align = members.iter().map(|member| member.layout.align).max();
+        let align = align.unwrap_or(1);
+
+        Self {
+            layout: Layout {
+                size: align_to(size.unwrap_or(0), align),
+                align,
+            },
+            members,
+            is_union: true,
+        }
+    }
+
+    /// The layout of the whole record, which is what a struct or union member of this type gets.
+    pub fn layout(&self) -> Layout {
+        self.layout
+    }
+
+    /// Every member, in the order they were declared.
+    pub fn members(&self) -> &[Member] {
+        &self.members
+    }
+
+    /// Find a member by name.
+    pub fn member(&self, name: &str) -> Option<&Member> {
+        self.members.iter().find(|member| member.name == name)
+    }
+
+    /// How far from the start of the record the named member is, like `offsetof`.
+    pub fn offset_of(&self, name: &str) -> Option<u64> {
+        self.member(name).map(|member| member.offset)
+    }
+
+    /// How the record is passed to a function or returned from one, by value.
+    ///
+    /// The System V ABI splits a record into eightbytes and classifies each one. There are no
+    /// floating point types yet, so every eightbyte is in the INTEGER class, and goes in a general
+    /// purpose register. Records bigger than two eightbytes are in the MEMORY class instead.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use ecc::layout::{Layout, Passing, RecordLayout};
+    ///
+    /// let int = Layout { size: 4, align: 4 };
+    ///
+    /// let pair = RecordLayout::structure([("a", int), ("b", int)]);
+    /// assert_eq!(pair.passing(), Passing::Registers(1));
+    ///
+    /// let triple = RecordLayout::structure([("a", int), ("b", int), ("c", int)]);
+    /// assert_eq!(triple.passing(), Passing::Registers(2));
+    ///
+    /// let big = [("a", int), ("b", int), ("c", int), ("d", int), ("e", int)];
+    /// let big = RecordLayout::structure(big);
+    /// assert_eq!(big.passing(), Passing::Memory);
+    /// ```
+    pub fn passing(&self) -> Passing {
+        if self.layout.size > 16 {
+            Passing::Memory
+        } else {
+            Passing::Registers(self.layout.size.div_ceil(8) as usize)
+        }
+    }
+
+    /// How many bytes of the record are padding, which belongs to no member.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use ecc::layout::{Layout, RecordLayout};
+    ///
+    /// let char_ = Layout { size: 1, align: 1 };
+    /// let int = Layout { size: 4, align: 4 };
+    ///
+    /// assert_eq!(RecordLayout::structure([("c", char_), ("i", int)]).padding(), 3);
+    /// assert_eq!(RecordLayout::structure([("i", int), ("c", char_)]).padding(), 3);
+    /// assert_eq!(RecordLayout::union([("c", char_), ("i", int)]).padding(), 0);
+    /// ```
+    pub fn padding(&self) -> u64 {
+        let sizes = self.members.iter().map(|member| member.layout.size);
+        let used = if self.is_union {
+            sizes.max().unwrap_or(0)
+        } else {
+            sizes.sum()
+        };
+
+        self.layout.size - used
+    }
+}
+
+/// How a value is passed to a function or returned from one.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum Passing {
+    /// In this many general purpose registers, one for each eightbyte of the value.
+    ///
+    /// Arguments take the next free ones of `%rdi`, `%rsi`, `%rdx`, `%rcx`, `%r8` and `%r9`, and
+    /// go in memory like [`Passing::Memory`] if there are not enough left for the whole value.
+    /// Return values come back in `%rax` and then `%rdx`.
+    Registers(usize),
+
+    /// In memory.
+    ///
+    /// Arguments are copied onto the stack. To return one, the caller passes a pointer to space
+    /// for it in `%rdi`, as if it were the first argument, and the function gives the pointer back
+    /// in `%rax`.
+    Memory,
+}
+
+/// Round an offset up to the next multiple of an alignment.
+fn align_to(offset: u64, align: u64) -> u64 {
+    check_align(align);
+    offset.div_ceil(align) * align
+}
+
+/// Make sure that an alignment is a power of two, which also makes sure that it is not zero.
+fn check_align(align: u64) {
+    assert!(
+        align.is_power_of_two(),
+        "alignment {align} is not a power of two"
+    );
+}
@@ -1,28 +1,334 @@
+use crate::diagnostics::{Diagnostic, codes};
+use crate::options::{CompileOptions, Standard, Version};
+use crate::span::Span;
 use crate::token::Token;
 use crate::token::TokenKind;
-use crate::token::check_keyword;
+use crate::token::{check_keyword, keyword_standard};
+
+/// The kind of problem the lexer ran into.
+///
+/// # Examples
+///
+/// ```
+/// use ecc::lexer::{self, LexErrorKind};
+///
+/// let kind = |source| lexer::tokenize(source).unwrap_err()[0].kind.clone();
+///
+/// assert_eq!(kind("0x1F"), LexErrorKind::HexadecimalLiteral);
+/// assert_eq!(kind("10u"), LexErrorKind::IntegerSuffix);
+/// assert_eq!(kind("10abc"), LexErrorKind::MalformedNumber);
+/// ```
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum LexErrorKind {
+    /// A character that cannot start any token.
+    UnknownCharacter(char),
+
+    /// A `/*` comment that was never closed.
+    UnterminatedComment,
+
+    /// A string literal that hit the end of the line or file before its closing quote.
+    UnterminatedString,
+
+    /// A number immediately followed by identifier characters, like `123abc`.
+    MalformedNumber,
+
+    /// A hexadecimal integer literal, like `0x1F`, which is not supported yet.
+    HexadecimalLiteral,
+
+    /// A binary integer literal, like `0b101`, which is not supported yet.
+    BinaryLiteral,
+
+    /// An integer literal with a suffix, like `10u`, which is not supported yet.
+    IntegerSuffix,
+
+    /// A `//` comment in C89, which only has `/* */` comments.
+    LineComment,
+
+    /// A `#line` directive that is not followed by a line number and an optional file name.
+    MalformedLineDirective,
+}
+
+impl LexErrorKind {
+    /// The diagnostic code for this kind of error.
+    pub fn code(&self) -> &'static str {
+        match self {
+            Self::UnknownCharacter(_) => codes::UNKNOWN_CHARACTER,
+            Self::UnterminatedComment => codes::UNTERMINATED_COMMENT,
+            Self::UnterminatedString => codes::UNTERMINATED_STRING,
+            Self::MalformedNumber => codes::MALFORMED_NUMBER,
+            Self::HexadecimalLiteral | Self::BinaryLiteral | Self::IntegerSuffix => {
+                codes::UNSUPPORTED_INTEGER_LITERAL
+            }
+            Self::LineComment => codes::LINE_COMMENT,
+            Self::MalformedLineDirective => codes::MALFORMED_LINE_DIRECTIVE,
+        }
+    }
+}
+
+impl std::fmt::Display for LexErrorKind {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::UnknownCharacter(c) => write!(f, "unknown character {c:?}"),
+            Self::UnterminatedComment => write!(f, "unterminated comment"),
+            Self::UnterminatedString => write!(f, "unterminated string literal"),
+            Self::MalformedNumber => write!(f, "malformed number"),
+            Self::HexadecimalLiteral => write!(f, "hexadecimal literals are not supported"),
+            Self::BinaryLiteral => write!(f, "binary literals are not supported"),
+            Self::IntegerSuffix => write!(f, "integer suffixes are not supported"),
+            Self::LineComment => write!(f, "`//` comments are not allowed in C89"),
+            Self::MalformedLineDirective => write!(f, "malformed `#line` directive"),
+        }
+    }
+}
+
+/// An error that can be generated while lexing.
+///
+/// Like a token, a lex error remembers where in the source it came from so that it can be reported
+/// with the offending text underlined.
+#[derive(Clone, Debug)]
+pub struct LexError {
+    /// What went wrong.
+    pub kind: LexErrorKind,
+
+    /// The offending text.
+    pub span: Span,
+}
+
+impl std::fmt::Display for LexError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        self.kind.fmt(f)
+    }
+}
+
+impl From<LexError> for Diagnostic {
+    fn from(e: LexError) -> Self {
+        let diagnostic = Diagnostic::error(e.to_string())
+            .with_code(e.kind.code())
+            .with_span(e.span);
+
+        match e.kind {
+            LexErrorKind::UnterminatedComment => {
+                diagnostic.with_note("block comments do not nest, and end at the first '*/'")
+            }
+            LexErrorKind::UnterminatedString => {
+                diagnostic.with_help("string literals cannot span lines; use '\\n' for a newline")
+            }
+            LexErrorKind::MalformedNumber => {
+                diagnostic.with_note("identifiers cannot start with a digit")
+            }
+            LexErrorKind::HexadecimalLiteral | LexErrorKind::BinaryLiteral => {
+                diagnostic.with_help("write the value in decimal")
+            }
+            LexErrorKind::IntegerSuffix => {
+                diagnostic.with_note("every integer literal is an `int` for now")
+            }
+            LexErrorKind::LineComment => {
+                diagnostic.with_help("use a `/* */` comment, or `--std=c99` or later")
+            }
+            LexErrorKind::MalformedLineDirective => {
+                diagnostic.with_help("write it like `#line 12 \"file.c\"`")
+            }
+            LexErrorKind::UnknownCharacter(_) => diagnostic,
+        }
+    }
+}
+
+/// A `#line` directive, which changes the line number and file name that the lines after it are
+/// reported with.
+///
+/// Generated code uses them to point diagnostics and debuggers at the file it was generated from.
+/// `gcc -E` writes them without the `line`, and with flags after the file name, like
+/// `# 12 "main.c" 2`. Those are accepted too, and the flags are ignored.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub struct LineDirective<'a> {
+    /// The line number of the line after the directive.
+    pub line: usize,
+
+    /// The name of the file that the lines after the directive are from, as written between the
+    /// quotes, or [`None`] if it stays the same.
+    pub file: Option<&'a str>,
+}
+
+impl<'a> LineDirective<'a> {
+    /// Parse a line of source code as a line directive.
+    ///
+    /// [`None`] is returned if the line does not hold a line directive, or holds a malformed one.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use ecc::lexer::LineDirective;
+    ///
+    /// let directive = LineDirective::parse("#line 12 \"parser.y\"").unwrap();
+    /// assert_eq!(directive.line, 12);
+    /// assert_eq!(directive.file, Some("parser.y"));
+    ///
+    /// assert!(LineDirective::parse("# 1 \"main.c\" 2").is_some());
+    /// assert!(LineDirective::parse("#line twelve").is_none());
+    /// ```
+    pub fn parse(text: &'a str) -> Option<Self> {
+        let (rest, is_marker) = Self::strip_introducer(text)?;
+
+        let digits = rest
+            .find(|c: char| !c.is_ascii_digit())
+            .unwrap_or(rest.len());
+        let line = rest[..digits].parse().ok()?;
+
+        let rest = rest[digits..].trim();
+        if rest.is_empty() {
+            return Some(Self { line, file: None });
+        }
+
+        let quoted = rest.strip_prefix('"')?;
+        let mut chars = quoted.char_indices();
+        let end = loop {
+            match chars.next()? {
+                (i, '"') => break i,
+                (_, '\\') => {
+                    chars.next()?;
+                }
+                _ => {}
+            }
+        };
+
+        let mut flags = quoted[end + 1..].split_whitespace();
+        let flags_allowed = is_marker && flags.all(|flag| flag.chars().all(|c| c.is_ascii_digit()));
+        if !flags_allowed && !quoted[end + 1..].trim().is_empty() {
+            return None;
+        }
+
+        Some(Self {
+            line,
+            file: Some(&quoted[..end]),
+        })
+    }
+
+    /// Return true if a line of source code starts like a line directive, whether or not the rest
+    /// of it is well formed.
+    fn starts(text: &str) -> bool {
+        Self::strip_introducer(text).is_some()
+    }
+
+    /// Strip the `#line` or `#` from the start of a line directive, along with the whitespace
+    /// after it, and say whether it was a marker written by `gcc -E`.
+    fn strip_introducer(text: &str) -> Option<(&str, bool)> {
+        let rest = text.trim_start().strip_prefix('#')?.trim_start();
+
+        if let Some(rest) = rest.strip_prefix("line")
+            && rest.starts_with(|c: char| c.is_ascii_whitespace())
+        {
+            Some((rest.trim_start(), false))
+        } else if rest.starts_with(|c: char| c.is_ascii_digit()) {
+            Some((rest, true))
+        } else {
+            None
+        }
+    }
+}
 
 /// Tokenize a string of source code.
 ///
-/// This function lexes a string of C source code into individual tokens. If the source code is not
-/// ascii, you will get some very strange results.
-pub fn tokenize(source: &str) -> Vec<Token> {
-    let bytes = source.as_bytes();
-    let mut lexer = Lexer::new(bytes);
+/// This function lexes a string of C source code into individual tokens. Lexing does not stop at
+/// the first error, so that every lexical problem in the file can be reported at once. If there
+/// were any errors, they are returned instead of the tokens.
+///
+/// The last token is always a [`TokenKind::SpecialEndOfFile`] token marking where the source
+/// ends, so that errors about running out of code can point at the right place.
+///
+/// This is a convenience for collecting a [`Lexer`], which produces the same tokens one at a time.
+pub fn tokenize(source: &str) -> Result<Vec<Token<'_>>, Vec<LexError>> {
+    tokenize_as(source, &CompileOptions::default())
+}
+
+/// Tokenize a string of source code the way the given options ask for, which decides the version
+/// of the C standard and whether trigraphs are replaced.
+///
+/// This is otherwise the same as [`tokenize`].
+pub fn tokenize_as<'a>(
+    source: &'a str,
+    options: &CompileOptions,
+) -> Result<Vec<Token<'a>>, Vec<LexError>> {
     let mut tokens = Vec::new();
+    let mut errors = Vec::new();
 
-    while let Some(token) = lexer.next_token() {
-        tokens.push(token);
+    for result in Lexer::new(source)
+        .with_standard(options.standard)
+        .with_trigraphs(options.trigraphs)
+    {
+        match result {
+            Ok(token) => tokens.push(token),
+            Err(e) => errors.push(e),
+        }
     }
 
-    tokens
+    if errors.is_empty() {
+        Ok(tokens)
+    } else {
+        Err(errors)
+    }
 }
 
-struct Lexer<'a> {
+/// A lexer.
+///
+/// The lexer is an iterator that lazily splits source code into tokens, so tokens can be processed
+/// as they are produced instead of all being collected first. Errors are yielded in between the
+/// tokens, and lexing carries on after them. The last item is always a
+/// [`TokenKind::SpecialEndOfFile`] token.
+///
+/// # Examples
+///
+/// ```
+/// use ecc::lexer::Lexer;
+/// use ecc::token::TokenKind;
+///
+/// let kinds: Vec<_> = Lexer::new("return 0;")
+///     .map(|result| result.unwrap().kind)
+///     .collect();
+///
+/// assert_eq!(
+///     kinds,
+///     [
+///         TokenKind::KeywordReturn,
+///         TokenKind::LiteralInteger,
+///         TokenKind::DelimSemicolon,
+///         TokenKind::SpecialEndOfFile,
+///     ]
+/// );
+/// ```
+pub struct Lexer<'a> {
+    /// The source code, which every lexeme borrows from.
+    text: &'a str,
     source: &'a [u8],
     current: usize,
     line: usize,
     column: usize,
+
+    /// Whether the end of file token has been produced yet.
+    finished: bool,
+
+    /// Whether comments are produced as tokens instead of being skipped.
+    keep_comments: bool,
+
+    /// The version of the C standard the source is written in.
+    standard: Standard,
+
+    /// Whether trigraphs are replaced by the character they stand for.
+    trigraphs: bool,
+}
+
+impl<'a> Iterator for Lexer<'a> {
+    type Item = Result<Token<'a>, LexError>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.finished {
+            return None;
+        }
+
+        self.next_token().or_else(|| {
+            self.finished = true;
+            Some(Ok(self.make_end_of_file()))
+        })
+    }
 }
 
 impl<'a> Lexer<'a> {
@@ -30,19 +336,120 @@ impl<'a> Lexer<'a> {
     ///
     /// This constructor initializes the source view to the given string, setting the current
     /// character index to the beginning of the string and the line and column to 1.
-    fn new(source: &'a [u8]) -> Self {
+    pub fn new(source: &'a str) -> Self {
         Self {
-            source,
+            text: source,
+            source: source.as_bytes(),
             current: 0,
             line: 1,
             column: 1,
+            finished: false,
+            keep_comments: false,
+            standard: Standard::default(),
+            trigraphs: false,
         }
     }
 
+    /// Make the lexer produce comments as [`TokenKind::SpecialComment`] tokens and line directives
+    /// as [`TokenKind::SpecialLineDirective`] tokens instead of skipping them.
+    ///
+    /// This is for tools that care about the exact source, like highlighters and formatters. The
+    /// parser does not expect either kind of token, so they have to be filtered out before parsing.
+    /// [`TokenKind::is_trivia`] tells which tokens those are.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use ecc::lexer::Lexer;
+    /// use ecc::token::TokenKind;
+    ///
+    /// let trivia: Vec<_> = Lexer::new("#line 3 \"main.c\"\nreturn 0; // done")
+    ///     .with_comments()
+    ///     .map(Result::unwrap)
+    ///     .filter(|token| token.kind.is_trivia())
+    ///     .map(|token| token.lexeme)
+    ///     .collect();
+    ///
+    /// assert_eq!(trivia, ["#line 3 \"main.c\"", "// done"]);
+    /// ```
+    pub fn with_comments(mut self) -> Self {
+        self.keep_comments = true;
+        self
+    }
+
+    /// Lex source code written in the given version of the C standard, instead of the newest one.
+    ///
+    /// Keywords that the version does not have yet are identifiers, `//` comments are an error in
+    /// C89, and digraphs like `<%` are only lexed from C99 on.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use ecc::lexer::Lexer;
+    /// use ecc::options::Standard;
+    /// use ecc::token::TokenKind;
+    ///
+    /// let mut lexer = Lexer::new("inline").with_standard(Standard::C89);
+    ///
+    /// assert_eq!(lexer.next().unwrap().unwrap().kind, TokenKind::LiteralIdentifier);
+    /// ```
+    pub fn with_standard(mut self, standard: Standard) -> Self {
+        self.standard = standard;
+        self
+    }
+
+    /// Replace trigraphs like `??<` with the character they stand for, as C did before C23.
+    ///
+    /// They are replaced everywhere, even in comments and string literals. Lexemes are still slices
+    /// of the source, so they keep the trigraphs as written.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use ecc::lexer::Lexer;
+    /// use ecc::token::TokenKind;
+    ///
+    /// let tokens: Vec<_> = Lexer::new("??< ??>")
+    ///     .with_trigraphs(true)
+    ///     .map(Result::unwrap)
+    ///     .collect();
+    ///
+    /// assert_eq!(tokens[0].kind, TokenKind::DelimBraceLeft);
+    /// assert_eq!(tokens[1].kind, TokenKind::DelimBraceRight);
+    /// assert_eq!(tokens[1].lexeme, "??>");
+    /// ```
+    pub fn with_trigraphs(mut self, trigraphs: bool) -> Self {
+        self.trigraphs = trigraphs;
+        self
+    }
+
+    /// Return true if digraphs like `<%` are tokens, rather than two separate characters.
+    fn digraphs(&self) -> bool {
+        self.standard.version > Version::C89
+    }
+
+    /// The character that the trigraph ending in the given character stands for.
+    fn trigraph(c: u8) -> Option<u8> {
+        let replacement = match c {
+            b'=' => b'#',
+            b'(' => b'[',
+            b'/' => b'\\',
+            b')' => b']',
+            b'\'' => b'^',
+            b'<' => b'{',
+            b'!' => b'|',
+            b'>' => b'}',
+            b'-' => b'~',
+            _ => return None,
+        };
+
+        Some(replacement)
+    }
+
     /// Return true if the given character could be the start of an identifier. This includes
     /// uppercase and lowercase alphabetic characters and underscores.
     fn is_ident_start(c: u8) -> bool {
-        (b'A' <= c && c <= b'Z') || (b'a' <= c && c <= b'z') || c == b'_'
+        c.is_ascii_alphabetic() || c == b'_'
     }
 
     /// Return true if the given character could be in the middle of an identifier. This includes
@@ -53,18 +460,35 @@ impl<'a> Lexer<'a> {
 
     /// Return true if the given character is a digit, e.g. '0' to '9'.
     fn is_digit(c: u8) -> bool {
-        b'0' <= c && c <= b'9'
+        c.is_ascii_digit()
+    }
+
+    /// Get the character at the given offset into the source, and how many bytes spell it.
+    ///
+    /// That is always one byte, unless trigraphs are being replaced and one starts at the offset.
+    fn char_at(&self, offset: usize) -> Option<(u8, usize)> {
+        let c = *self.source.get(offset)?;
+        if self.trigraphs
+            && c == b'?'
+            && self.source.get(offset + 1) == Some(&b'?')
+            && let Some(replacement) = self.source.get(offset + 2).and_then(|&c| Self::trigraph(c))
+        {
+            return Some((replacement, 3));
+        }
+
+        Some((c, 1))
     }
 
     /// Get the current character.
     ///
     /// TODO: Write better description.
     fn peek(&self) -> Option<u8> {
-        self.source.get(self.current).copied()
+        self.char_at(self.current).map(|(c, _)| c)
     }
 
     fn peek_next(&self) -> Option<u8> {
-        self.source.get(self.current + 1).copied()
+        let (_, width) = self.char_at(self.current)?;
+        self.char_at(self.current + width).map(|(c, _)| c)
     }
 
     /// Advance the lexer by one character.
@@ -73,43 +497,148 @@ impl<'a> Lexer<'a> {
     /// and returns that character. If the lexer was already at the end of the string, no operation
     /// is performed and [`None`] is returned.
     fn advance(&mut self) -> Option<u8> {
-        let current = self.peek();
-        if let Some(current) = current {
-            self.current += 1;
-            self.column += 1;
+        let (current, width) = self.char_at(self.current)?;
+        self.current += width;
+        self.column += width;
 
-            if current == b'\n' {
-                self.line += 1;
-                self.column = 1;
-            }
+        if current == b'\n' {
+            self.line += 1;
+            self.column = 1;
         }
 
-        current
+        Some(current)
+    }
+
+    /// Make an error of the given kind starting at the given offset.
+    ///
+    /// The error spans from the start offset up to (but not including) the lexer's current
+    /// character.
+    fn make_error(&self, kind: LexErrorKind, start: usize) -> LexError {
+        LexError {
+            kind,
+            span: Span::new(start, self.current),
+        }
     }
 
     /// Skip past any whitespace and comments.
     ///
     /// This method advances the position of the lexer until the current character is not a
-    /// whitespace character. If the next non-whitespace character is a slash followed by another
-    /// slash, the comment will be skipped. Naturally, if that was already the case when the method
-    /// was called, the lexer's state is not altered.
-    fn skip_whitespace(&mut self) {
+    /// whitespace character. If the next non-whitespace character starts a `//` or `/*` comment,
+    /// the comment will be skipped, unless comments are being kept as tokens. Naturally, if that
+    /// was already the case when the method was called, the lexer's state is not altered.
+    ///
+    /// If a block comment is still open when the end of the source is reached, an error pointing
+    /// at the opening `/*` is returned.
+    fn skip_whitespace(&mut self) -> Result<(), LexError> {
         while let Some(c) = self.peek() {
             if c.is_ascii_whitespace() {
                 self.advance();
                 continue;
             }
 
-            if c == b'/'
-                && let Some(b'/') = self.peek_next()
-            {
-                while let Some(c) = self.peek()
-                    && c != b'\n'
-                {
+            if let Some(line_end) = self.line_directive_end() {
+                if self.keep_comments {
+                    break;
+                }
+                self.skip_line_directive(line_end)?;
+                continue;
+            }
+
+            match (c, self.peek_next()) {
+                (b'/', Some(b'/' | b'*')) if self.keep_comments => break,
+                (b'/', Some(b'/')) => self.skip_line_comment()?,
+                (b'/', Some(b'*')) => self.skip_block_comment()?,
+                _ => break,
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Return true if nothing but whitespace comes before the current character on its line.
+    fn at_line_start(&self) -> bool {
+        self.source[..self.current]
+            .iter()
+            .rev()
+            .take_while(|&&c| c != b'\n')
+            .all(u8::is_ascii_whitespace)
+    }
+
+    /// If the lexer is pointing at a line directive, get the offset of the end of its line.
+    fn line_directive_end(&self) -> Option<usize> {
+        if self.peek() != Some(b'#') || !self.at_line_start() {
+            return None;
+        }
+
+        let line_end = self.text[self.current..]
+            .find('\n')
+            .map_or(self.text.len(), |i| self.current + i);
+        LineDirective::starts(&self.text[self.current..line_end]).then_some(line_end)
+    }
+
+    /// Skip a line directive that ends at the given offset, stopping right before the newline
+    /// that ends it.
+    ///
+    /// The directive does not change the lines and columns of tokens, which always say where they
+    /// are in the source itself. Diagnostics and debug information read it again to work out
+    /// where to say that they are.
+    fn skip_line_directive(&mut self, end: usize) -> Result<(), LexError> {
+        let start = self.current;
+        while self.current < end {
+            self.advance();
+        }
+
+        match LineDirective::parse(&self.text[start..end]) {
+            Some(_) => Ok(()),
+            None => Err(self.make_error(LexErrorKind::MalformedLineDirective, start)),
+        }
+    }
+
+    /// Skip a `//` comment, stopping right before the newline that ends it.
+    ///
+    /// C89 does not have them, so the comment is an error there, but it is still skipped so that
+    /// lexing can carry on after it.
+    fn skip_line_comment(&mut self) -> Result<(), LexError> {
+        let start = self.current;
+        while let Some(c) = self.peek()
+            && c != b'\n'
+        {
+            self.advance();
+        }
+
+        if self.standard.version < Version::C99 {
+            return Err(LexError {
+                kind: LexErrorKind::LineComment,
+                span: Span::new(start, start + 2),
+            });
+        }
+
+        Ok(())
+    }
+
+    /// Skip a `/* ... */` comment, assuming the lexer is pointing at the opening slash.
+    fn skip_block_comment(&mut self) -> Result<(), LexError> {
+        let start = self.current;
+
+        self.advance();
+        self.advance();
+
+        loop {
+            match (self.peek(), self.peek_next()) {
+                (Some(b'*'), Some(b'/')) => {
+                    self.advance();
                     self.advance();
+                    return Ok(());
+                }
+                (Some(_), _) => {
+                    self.advance();
+                }
+                (None, _) => {
+                    return Err(LexError {
+                        kind: LexErrorKind::UnterminatedComment,
+                        span: Span::new(start, start + 2),
+                    });
                 }
-            } else {
-                break;
             }
         }
     }
@@ -118,31 +647,43 @@ impl<'a> Lexer<'a> {
     ///
     /// This method constructs a token with the given type, taking the line and column information
     /// from the lexer. The token is assumed to be one character long, so a single character
-    /// substring is taken from the source.
+    /// substring is taken from the source, or three if the character was spelled with a trigraph.
     ///
     /// NOTE: This method is marked `#[must_use]`. If you just want to advance the lexer, use
     /// `advance`.
     #[must_use]
-    fn make_token_and_advance(&mut self, kind: TokenKind) -> Token {
-        let bytes = &self.source[self.current..self.current + 1];
-        let lexeme = str::from_utf8(bytes).unwrap().to_string();
-        let token = Token {
-            kind,
-            lexeme,
-            line: self.line,
-            column: self.column,
-        };
+    fn make_token_and_advance(&mut self, kind: TokenKind) -> Token<'a> {
+        self.make_long_token_and_advance(kind, 1)
+    }
 
-        self.advance();
+    /// Make a token of the given type that is the given number of characters long, and advance
+    /// past it.
+    ///
+    /// This is otherwise the same as [`Self::make_token_and_advance`].
+    #[must_use]
+    fn make_long_token_and_advance(&mut self, kind: TokenKind, length: usize) -> Token<'a> {
+        let start = self.current;
+        let line = self.line;
+        let column = self.column;
 
-        token
+        for _ in 0..length {
+            self.advance();
+        }
+
+        Token {
+            kind,
+            lexeme: &self.text[start..self.current],
+            span: Span::new(start, self.current),
+            line,
+            column,
+        }
     }
 
     /// Consume the next identifier from the source.
     ///
     /// This method assumes that the lexer's current character is the start of an identifier. If
     /// not, an exception is thrown.
-    fn make_identifier(&mut self) -> Token {
+    fn make_identifier(&mut self) -> Token<'a> {
         let Some(current) = self.peek() else {
             panic!("expected the start of an identifier");
         };
@@ -164,19 +705,27 @@ impl<'a> Lexer<'a> {
             self.advance();
         }
 
-        let lexeme = str::from_utf8(&self.source[start..start + length]).unwrap();
-        let kind = check_keyword(lexeme);
+        let lexeme = &self.text[start..start + length];
+        let kind = if !self.standard.includes(keyword_standard(lexeme)) {
+            TokenKind::LiteralIdentifier
+        } else {
+            check_keyword(lexeme)
+        };
 
         Token {
             kind,
-            lexeme: lexeme.to_owned(),
+            lexeme,
+            span: Span::new(start, self.current),
             line: self.line,
             column,
         }
     }
 
     /// Consume the next number from the source.
-    fn make_number(&mut self) -> Token {
+    ///
+    /// A number that runs straight into identifier characters (`123abc`) is not a valid token, so
+    /// the whole run is consumed and reported as a single malformed number.
+    fn make_number(&mut self) -> Result<Token<'a>, LexError> {
         let Some(true) = self.peek().map(Self::is_digit) else {
             panic!("expected a digit");
         };
@@ -194,15 +743,207 @@ impl<'a> Lexer<'a> {
             self.advance();
         }
 
-        let lexeme = str::from_utf8(&self.source[start..start + length])
-            .unwrap()
-            .to_owned();
+        if let Some(current) = self.peek()
+            && Self::is_ident(current)
+        {
+            while let Some(current) = self.peek()
+                && Self::is_ident(current)
+            {
+                self.advance();
+            }
+
+            let digits = &self.text[start..start + length];
+            let rest = &self.text[start + length..self.current];
+            return Err(self.make_error(Self::classify_malformed_number(digits, rest), start));
+        }
+
+        let lexeme = &self.text[start..start + length];
 
-        Token {
+        Ok(Token {
             kind: TokenKind::LiteralInteger,
             lexeme,
+            span: Span::new(start, self.current),
             line: self.line,
-            column: column,
+            column,
+        })
+    }
+
+    /// Consume the next string literal from the source.
+    ///
+    /// This method assumes that the lexer is pointing at the opening double quote. Escape
+    /// sequences are kept verbatim in the lexeme; the only one the lexer cares about is `\"`,
+    /// which does not end the string. C strings cannot span lines, so a newline before the closing
+    /// quote makes the literal unterminated.
+    fn make_string(&mut self) -> Result<Token<'a>, LexError> {
+        let start = self.current;
+        let line = self.line;
+        let column = self.column;
+
+        self.advance();
+
+        loop {
+            match self.peek() {
+                Some(b'"') => {
+                    self.advance();
+                    break;
+                }
+                Some(b'\\') if self.peek_next().is_some_and(|c| c != b'\n') => {
+                    self.advance();
+                    self.advance();
+                }
+                Some(b'\n') | None => {
+                    return Err(self.make_error(LexErrorKind::UnterminatedString, start));
+                }
+                Some(_) => {
+                    self.advance();
+                }
+            }
+        }
+
+        let lexeme = &self.text[start..self.current];
+
+        Ok(Token {
+            kind: TokenKind::LiteralString,
+            lexeme,
+            span: Span::new(start, self.current),
+            line,
+            column,
+        })
+    }
+
+    /// Work out what is wrong with a number whose digits are followed by identifier characters.
+    ///
+    /// The characters could be a prefix or suffix that C has but this compiler does not support
+    /// yet, which deserves a better error than a number that runs into an identifier.
+    fn classify_malformed_number(digits: &str, rest: &str) -> LexErrorKind {
+        let prefixed = |marker: char, is_digit: fn(char) -> bool| {
+            digits == "0"
+                && rest
+                    .strip_prefix([marker, marker.to_ascii_uppercase()])
+                    .is_some_and(|body| {
+                        let suffix = body.trim_start_matches(is_digit);
+                        suffix.len() < body.len() && Self::is_integer_suffix(suffix)
+                    })
+        };
+
+        if prefixed('x', |c| c.is_ascii_hexdigit()) {
+            LexErrorKind::HexadecimalLiteral
+        } else if prefixed('b', |c| matches!(c, '0' | '1')) {
+            LexErrorKind::BinaryLiteral
+        } else if !rest.is_empty() && Self::is_integer_suffix(rest) {
+            LexErrorKind::IntegerSuffix
+        } else {
+            LexErrorKind::MalformedNumber
+        }
+    }
+
+    /// Return true if the given text can end an integer literal, like `u` or `ULL`. Nothing at
+    /// all counts too.
+    fn is_integer_suffix(suffix: &str) -> bool {
+        // The two letters of `ll` have to be the same case.
+        if suffix.contains("lL") || suffix.contains("Ll") {
+            return false;
+        }
+
+        matches!(
+            suffix.to_ascii_lowercase().as_str(),
+            "" | "u" | "l" | "ll" | "ul" | "lu" | "ull" | "llu" | "wb" | "uwb" | "wbu"
+        )
+    }
+
+    /// Consume the next comment from the source.
+    ///
+    /// This method assumes that the lexer is pointing at the `//` or `/*` that opens the comment.
+    /// The lexeme is the whole comment, including the slashes and stars that delimit it.
+    fn make_comment(&mut self) -> Result<Token<'a>, LexError> {
+        let start = self.current;
+        let line = self.line;
+        let column = self.column;
+
+        if self.peek_next() == Some(b'/') {
+            self.skip_line_comment()?;
+        } else {
+            self.skip_block_comment()?;
+        }
+
+        Ok(Token {
+            kind: TokenKind::SpecialComment,
+            lexeme: &self.text[start..self.current],
+            span: Span::new(start, self.current),
+            line,
+            column,
+        })
+    }
+
+    /// Consume a line directive that ends at the given offset, keeping it as a token.
+    ///
+    /// The lexeme is the whole directive, without the newline that ends it.
+    fn make_line_directive(&mut self, end: usize) -> Result<Token<'a>, LexError> {
+        let start = self.current;
+        let line = self.line;
+        let column = self.column;
+        self.skip_line_directive(end)?;
+
+        Ok(Token {
+            kind: TokenKind::SpecialLineDirective,
+            lexeme: &self.text[start..self.current],
+            span: Span::new(start, self.current),
+            line,
+            column,
+        })
+    }
+
+    /// Consume a character that cannot start a token and turn it into an error.
+    ///
+    /// The source is valid UTF-8, so the whole (possibly multi-byte) character is consumed and
+    /// reported instead of just its first byte.
+    fn make_unknown_character(&mut self) -> LexError {
+        let start = self.current;
+
+        // Trigraphs stand for ASCII characters, like `??!` for `|`.
+        if let Some((c, 3)) = self.char_at(start) {
+            self.advance();
+            return self.make_error(LexErrorKind::UnknownCharacter(c.into()), start);
+        }
+
+        // The lexer only ever stops on character boundaries, so this cannot fail.
+        let c = self.text[self.current..].chars().next().unwrap();
+
+        self.current += c.len_utf8();
+        self.column += 1;
+
+        LexError {
+            kind: LexErrorKind::UnknownCharacter(c),
+            span: Span::new(start, self.current),
+        }
+    }
+
+    /// Make the token that marks the end of the source.
+    ///
+    /// The token is empty. If the source ends with a newline, the token is placed right before it,
+    /// so that it is rendered at the end of the last line instead of on a line of its own.
+    fn make_end_of_file(&self) -> Token<'a> {
+        let mut end = self.source.len();
+        let mut line = self.line;
+        let mut column = self.column;
+
+        if end > 0 && self.source[end - 1] == b'\n' {
+            end -= 1;
+            line -= 1;
+            column = end
+                - self.source[..end]
+                    .iter()
+                    .rposition(|&c| c == b'\n')
+                    .map_or(0, |i| i + 1)
+                + 1;
+        }
+
+        Token {
+            kind: TokenKind::SpecialEndOfFile,
+            lexeme: "",
+            span: Span::new(end, end),
+            line,
+            column,
         }
     }
 
@@ -211,13 +952,61 @@ impl<'a> Lexer<'a> {
     /// This method reads the next token from the source string. If the lexer has already read all
     /// of the tokens from the string (e.g. the source pointer is past the end of the string), then
     /// a null optional is returned.
-    fn next_token(&mut self) -> Option<Token> {
-        self.skip_whitespace();
+    fn next_token(&mut self) -> Option<Result<Token<'a>, LexError>> {
+        if let Err(e) = self.skip_whitespace() {
+            return Some(Err(e));
+        }
 
         let current = self.peek()?;
+
+        // Comments and line directives are only left for us when they are being kept as tokens.
+        if let (b'/', Some(b'/' | b'*')) = (current, self.peek_next()) {
+            return Some(self.make_comment());
+        }
+        if let Some(line_end) = self.line_directive_end() {
+            return Some(self.make_line_directive(line_end));
+        }
+
+        // The character after the current one only matters if it could finish a digraph.
+        let next = if self.digraphs() {
+            self.peek_next()
+        } else {
+            None
+        };
+        let token = match (current, next) {
+            (b'<', Some(b'%')) => self.make_long_token_and_advance(TokenKind::DelimBraceLeft, 2),
+            (b'%', Some(b'>')) => self.make_long_token_and_advance(TokenKind::DelimBraceRight, 2),
+            (b'<', Some(b':')) => self.make_long_token_and_advance(TokenKind::DelimBracketLeft, 2),
+            (b':', Some(b'>')) => self.make_long_token_and_advance(TokenKind::DelimBracketRight, 2),
+            (b'%', Some(b':')) => return Some(Err(self.make_hash())),
+            _ => return Some(self.make_single_character_token(current)),
+        };
+
+        Some(Ok(token))
+    }
+
+    /// Consume a `#`, spelled as `%:` or as itself, and turn it into an error.
+    ///
+    /// There is no preprocessor yet, so nothing can use it.
+    fn make_hash(&mut self) -> LexError {
+        let start = self.current;
+        if self.advance() == Some(b'%') {
+            self.advance();
+        }
+
+        self.make_error(LexErrorKind::UnknownCharacter('#'), start)
+    }
+
+    /// Extract the next token from the lexer, when it starts with the given character and is not
+    /// a digraph.
+    fn make_single_character_token(&mut self, current: u8) -> Result<Token<'a>, LexError> {
         let token = match current {
             b'{' => self.make_token_and_advance(TokenKind::DelimBraceLeft),
             b'}' => self.make_token_and_advance(TokenKind::DelimBraceRight),
+            b'[' => self.make_token_and_advance(TokenKind::DelimBracketLeft),
+            b']' => self.make_token_and_advance(TokenKind::DelimBracketRight),
+            b':' => self.make_token_and_advance(TokenKind::DelimColon),
+            b',' => self.make_token_and_advance(TokenKind::DelimComma),
             b'(' => self.make_token_and_advance(TokenKind::DelimParenLeft),
             b')' => self.make_token_and_advance(TokenKind::DelimParenRight),
             b';' => self.make_token_and_advance(TokenKind::DelimSemicolon),
@@ -228,17 +1017,19 @@ impl<'a> Lexer<'a> {
             b'/' => self.make_token_and_advance(TokenKind::OperatorSlash),
             b'*' => self.make_token_and_advance(TokenKind::OperatorStar),
             b'~' => self.make_token_and_advance(TokenKind::OperatorTilde),
+            b'"' => return self.make_string(),
+            b'#' => return Err(self.make_hash()),
             _ => {
                 if Self::is_ident_start(current) {
                     self.make_identifier()
                 } else if Self::is_digit(current) {
-                    self.make_number()
+                    return self.make_number();
                 } else {
-                    self.make_token_and_advance(TokenKind::SpecialError)
+                    return Err(self.make_unknown_character());
                 }
             }
         };
 
-        Some(token)
+        Ok(token)
     }
 }
@@ -1,111 +1,316 @@
 // #![warn(missing_docs)]
 #![allow(dead_code)]
+// Diagnostics are big, but they are only created when something has gone wrong, so it is not worth
+// boxing them to make the happy path's results smaller.
+#![allow(clippy::result_large_err)]
 
-use std::ffi::OsStr;
-use std::io::Write;
-use std::path::Path;
-use std::process::Command;
+use std::io;
 
-use crate::parser::ParseError;
-use crate::token::Token;
+use crate::diagnostics::{Diagnostic, DiagnosticEmitter};
+use crate::hooks::Hooks;
+
+pub use crate::error::{CompileError, CompileResult};
+pub use crate::options::{CompileOptions, Emit};
+
+#[cfg(feature = "driver")]
+pub use crate::driver::{compile_file, interpret_file, lint_file};
 
 pub mod ast;
+#[cfg(feature = "driver")]
+pub mod compdb;
 pub mod compiler;
+pub mod depfile;
+pub mod diagnostics;
+#[cfg(feature = "driver")]
+mod driver;
+#[cfg(feature = "driver")]
+mod elf;
+pub mod error;
+pub mod ffi;
+pub mod highlight;
+pub mod hooks;
+pub mod interp;
+pub mod layout;
 pub mod lexer;
+pub mod lint;
+pub mod options;
 pub mod parser;
+pub mod pedantic;
+pub mod sema;
+#[cfg(feature = "driver")]
+pub mod source_file;
+pub mod span;
 pub mod token;
+pub mod types;
+#[cfg(feature = "wasm")]
+pub mod wasm;
 
-/// Run the entire compilation pipeline, taking source code to assembly.
-pub fn compile_source(source: &str) -> String {
-    let tokens = lexer::tokenize(source);
-    let tree = match parser::parse_token_stream(tokens) {
-        Ok(tree) => tree,
-        Err(e) => {
-            print_parse_error(e, source);
-            std::process::exit(1);
-        }
-    };
+/// Run the entire compilation pipeline on source code held in memory.
+///
+/// The result is the textual form of whatever `options` asks to emit: the syntax tree dump for
+/// [`Emit::Ast`], its drawing for [`Emit::AstDot`], the make rule for [`Emit::Dependencies`], and
+/// assembly otherwise, since an executable can only be linked from a file (see [`compile_file`]).
+/// Every diagnostic is sent to the given emitter. If there were any errors, the stage that failed
+/// is returned.
+pub fn compile_source(
+    source: &str,
+    options: &CompileOptions,
+    emitter: &mut dyn DiagnosticEmitter,
+) -> CompileResult<String> {
+    compile_named_source("<source>", source, options, emitter, &mut ())
+}
 
-    compiler::compile_ast(tree)
+/// Run the entire compilation pipeline on source code held in memory, calling the given hooks
+/// after each stage.
+///
+/// This is otherwise the same as [`compile_source`].
+pub fn compile_source_with_hooks(
+    source: &str,
+    options: &CompileOptions,
+    emitter: &mut dyn DiagnosticEmitter,
+    hooks: &mut dyn Hooks,
+) -> CompileResult<String> {
+    compile_named_source("<source>", source, options, emitter, hooks)
 }
 
-pub fn compile_and_link<P>(path: P)
-where
-    P: AsRef<Path>,
-{
-    let path = path.as_ref();
-    let source = std::fs::read_to_string(path).unwrap();
-    let assembly = compile_source(&source);
-    let assembly_file = path.with_extension("s");
-
-    std::fs::write(assembly_file.clone(), assembly).unwrap();
-    let result = link_program(&assembly_file);
-    std::fs::remove_file(assembly_file).unwrap();
-    if !result {
-        std::process::exit(1);
-    }
+/// Run the front end and semantic analysis on source code held in memory.
+///
+/// The returned symbol table can be asked what the names in the program refer to, which is what
+/// editors, linters, and tests usually want to know. Every diagnostic is sent to the given
+/// emitter.
+pub fn analyze_source(
+    source: &str,
+    emitter: &mut dyn DiagnosticEmitter,
+) -> CompileResult<sema::SymbolTable> {
+    let tree = parse_named_source(
+        "<source>",
+        source,
+        &CompileOptions::default(),
+        emitter,
+        &mut (),
+    )?;
+    Ok(sema::analyze(&tree))
 }
 
-/// Run `gcc` on the given assembly file.
+/// Run source code held in memory with the interpreter, returning what its function returned.
+///
+/// The program is checked the same way it would be for compiling, including lints, and every
+/// diagnostic is sent to the given emitter. An error that stops the interpreter is reported as
+/// [`CompileError::Runtime`].
+///
+/// # Examples
+///
+/// ```
+/// use ecc::CompileOptions;
+/// use ecc::diagnostics::Diagnostic;
+///
+/// let mut diagnostics: Vec<Diagnostic> = Vec::new();
+/// let source = "int main(void) { return 6 * 7; }";
+/// let result = ecc::interpret_source(source, &CompileOptions::new(), &mut diagnostics);
 ///
-/// Since I do not really feel like writing my own linker and standard library, it seems like a
-/// natural choice to link the program in this way. The return value indicates whether or not
-/// linking was successful.
-fn link_program<P>(assembly_file: P) -> bool
-where
-    P: AsRef<Path>,
-{
-    let assembly_file = assembly_file.as_ref();
-    let without_extension = assembly_file.with_extension("");
-    let output = Command::new("gcc")
-        .args([
-            OsStr::new("-o"),
-            without_extension.as_os_str(),
-            assembly_file.as_os_str(),
-        ])
-        .output()
-        .unwrap();
-
-    std::io::stdout().write_all(&output.stdout).unwrap();
-    std::io::stderr().write_all(&output.stderr).unwrap();
-
-    output.status.success()
+/// assert_eq!(result.unwrap(), 42);
+/// ```
+pub fn interpret_source(
+    source: &str,
+    options: &CompileOptions,
+    emitter: &mut dyn DiagnosticEmitter,
+) -> CompileResult<i32> {
+    interpret_named_source("<source>", source, options, emitter)
 }
 
-/// Print a pretty parse error.
-fn print_parse_error(e: ParseError, source: &str) {
-    match e.token {
-        Some(token) => print_parse_error_at_token(token, &e.message, source),
-        None => print_parse_error_at_eof(&e.message, source),
+/// Run the entire compilation pipeline on source code read from the named file.
+///
+/// The name is only used for reporting diagnostics.
+pub(crate) fn compile_named_source(
+    file_name: &str,
+    source: &str,
+    options: &CompileOptions,
+    emitter: &mut dyn DiagnosticEmitter,
+    hooks: &mut dyn Hooks,
+) -> CompileResult<String> {
+    let mut output = Vec::new();
+    compile_named_source_into(file_name, source, options, emitter, hooks, &mut output)?;
+    let output = String::from_utf8(output).expect("the compiler only writes UTF-8");
+
+    if matches!(
+        options.emit,
+        Emit::Assembly | Emit::Executable | Emit::StaticLib
+    ) {
+        #[allow(deprecated)]
+        hooks.after_codegen(&output);
+    }
+
+    Ok(output)
+}
+
+/// Run the entire compilation pipeline on source code read from the named file, writing the
+/// result to the given sink as it is produced.
+///
+/// Assembly is written line by line, without ever being collected into a string, so
+/// [`Hooks::after_codegen`] is not called. Nothing is written if there were any errors.
+pub(crate) fn compile_named_source_into(
+    file_name: &str,
+    source: &str,
+    options: &CompileOptions,
+    emitter: &mut dyn DiagnosticEmitter,
+    hooks: &mut dyn Hooks,
+    out: &mut dyn io::Write,
+) -> CompileResult<()> {
+    // Finding the files a source reads only takes the preprocessor, like with `gcc -M`.
+    if options.emit == Emit::Dependencies {
+        out.write_all(depfile::for_source(file_name, options).as_bytes())?;
+        return Ok(());
+    }
+
+    let tree = parse_named_source(file_name, source, options, emitter, hooks)?;
+    let tree_output = match options.emit {
+        Emit::Ast => Some(ast::dump::dump_program(&tree)),
+        Emit::AstDot => Some(ast::dot::dot_program(&tree)),
+        Emit::Assembly
+        | Emit::Executable
+        | Emit::StaticLib
+        | Emit::CfgDot
+        | Emit::Ir
+        | Emit::Dependencies => None,
+    };
+    if let Some(tree_output) = tree_output {
+        out.write_all(tree_output.as_bytes())?;
+        return Ok(());
+    }
+
+    let symbols = sema::analyze(&tree);
+    hooks.after_sema(&symbols);
+
+    run_lints(file_name, source, &tree, options, emitter)?;
+
+    let mut compiler = compiler::Compiler::new()
+        .with_opt_level(options.opt_level)
+        .with_wrapv(options.wrapv)
+        .with_profiling(options.profiling)
+        .with_freestanding(options.freestanding && options.emit == Emit::Executable);
+    if options.debug_info {
+        compiler = compiler.with_debug_info(file_name, source);
+    }
+    if options.source_comments {
+        compiler = compiler.with_source_comments(file_name, source);
+    }
+    if options.ub_checks {
+        compiler = compiler.with_ub_checks(file_name, source);
     }
+    compiler.compile_program(tree);
+
+    let mut passes = match &options.passes {
+        Some(names) => compiler::passes::PassManager::with_names(names),
+        None => compiler::passes::PassManager::for_opt_level(options.opt_level),
+    }
+    .with_print_after_all(options.print_after_passes);
+    hooks.before_passes(&mut passes);
+    compiler.run_passes(&mut passes, hooks);
+    hooks.after_passes(compiler.lines());
+
+    match options.emit {
+        Emit::CfgDot => {
+            let dot = compiler::cfg::dot(&compiler::cfg::build(compiler.lines()));
+            out.write_all(dot.as_bytes())?;
+        }
+        Emit::Ir => compiler::asm::write(compiler.lines(), out)?,
+        _ => compiler.finish_into(out)?,
+    }
+
+    Ok(())
 }
 
-fn print_parse_error_at_token(token: Token, message: &str, source: &str) {
-    eprintln!("message: {message}");
+/// Run the front end and lints on source code read from the named file, without generating any
+/// code.
+pub(crate) fn lint_named_source(
+    file_name: &str,
+    source: &str,
+    options: &CompileOptions,
+    emitter: &mut dyn DiagnosticEmitter,
+) -> CompileResult<()> {
+    let tree = parse_named_source(file_name, source, options, emitter, &mut ())?;
+    run_lints(file_name, source, &tree, options, emitter)
+}
 
-    // The unwrap call here should never fail. This is because the token came from some line in the
-    // source code, so if the lexer did its job correctly, there should exist a line whose number
-    // mathes that of the token.
-    let (_, line) = source
-        .lines()
-        .enumerate()
-        .find(|(number, _)| *number == token.line - 1)
-        .unwrap();
+/// Run source code read from the named file with the interpreter.
+pub(crate) fn interpret_named_source(
+    file_name: &str,
+    source: &str,
+    options: &CompileOptions,
+    emitter: &mut dyn DiagnosticEmitter,
+) -> CompileResult<i32> {
+    let tree = parse_named_source(file_name, source, options, emitter, &mut ())?;
+    run_lints(file_name, source, &tree, options, emitter)?;
 
-    let space_padding = token.column - 1;
-    let tilde_padding = token.lexeme.len() - 1;
+    interp::run_program(&tree).map_err(|e| {
+        emitter.emit(&e, file_name, source);
+        CompileError::Runtime(e)
+    })
+}
 
-    eprintln!(" {:>4} | {line}", token.line);
-    eprintln!("      | {: <space_padding$}^{:~<tilde_padding$}", "", "",);
+/// Run the enabled lints, failing if `-Werror` turned what they found into errors.
+fn run_lints(
+    file_name: &str,
+    source: &str,
+    tree: &ast::Program,
+    options: &CompileOptions,
+    emitter: &mut dyn DiagnosticEmitter,
+) -> CompileResult<()> {
+    let found = lint::run(tree, &options.warnings);
+    for diagnostic in &found {
+        emitter.emit(diagnostic, file_name, source);
+    }
+
+    if found.iter().any(Diagnostic::is_error) {
+        return Err(CompileError::Lint(found));
+    }
+
+    Ok(())
 }
 
-fn print_parse_error_at_eof(message: &str, source: &str) {
-    eprintln!("message: {}", message);
+/// Run the front end on source code read from the named file, taking it to a syntax tree that
+/// [`sema::check`] found nothing wrong with.
+///
+/// What [`pedantic::check`] finds is reported too, and stops compilation if it is an error.
+fn parse_named_source(
+    file_name: &str,
+    source: &str,
+    options: &CompileOptions,
+    emitter: &mut dyn DiagnosticEmitter,
+    hooks: &mut dyn Hooks,
+) -> CompileResult<ast::Program> {
+    let tokens = match lexer::tokenize_as(source, options) {
+        Ok(tokens) => tokens,
+        Err(errors) => {
+            for e in &errors {
+                emitter.emit(&Diagnostic::from(e.clone()), file_name, source);
+            }
+            return Err(CompileError::Lex(errors));
+        }
+    };
+    hooks.after_lex(&tokens);
+
+    let found = pedantic::check(&tokens, options);
+    for diagnostic in &found {
+        emitter.emit(diagnostic, file_name, source);
+    }
+    if found.iter().any(Diagnostic::is_error) {
+        return Err(CompileError::Lint(found));
+    }
+
+    let tree = parser::parse_token_stream(tokens).map_err(|e| {
+        emitter.emit(&e, file_name, source);
+        CompileError::Parse(e)
+    })?;
+    hooks.after_parse(&tree);
 
-    let lines = source.lines();
-    let (number, line) = lines.enumerate().last().unwrap();
-    let padding = line.len();
+    let errors = sema::check(&tree);
+    if !errors.is_empty() {
+        for e in &errors {
+            emitter.emit(e, file_name, source);
+        }
+        return Err(CompileError::Semantic(errors));
+    }
 
-    eprintln!(" {number:>4} | {line}");
-    eprintln!("      | {: <padding$}^", "");
+    Ok(tree)
 }
@@ -0,0 +1,376 @@
+//! Lints: warnings about code that is valid C, but probably not what was meant.
+//!
+//! Every lint has a name, and is turned on with `-Wname` and off with `-Wno-name`. Some lints are
+//! on by default, and the rest are turned on by `-Wall`. Lints only look at programs that parsed,
+//! so a lint never has to make sense of broken code.
+//!
+//! Adding a lint means writing a function that checks a program and adding it to [`LINTS`].
+
+use crate::ast::{self, BinaryOp, Expr, Program, Statement, UnaryOp, Visitor};
+use crate::diagnostics::{Diagnostic, Level};
+use crate::interp;
+use crate::options::Warnings;
+use crate::sema;
+use crate::types::Type;
+
+/// A check for a kind of suspicious code.
+#[derive(Clone, Copy, Debug)]
+pub struct Lint {
+    /// The name used to turn the lint on and off, as in `-Wname` and `-Wno-name`.
+    pub name: &'static str,
+
+    /// A short description of what the lint catches.
+    pub description: &'static str,
+
+    /// Whether the lint runs without `-Wall`.
+    pub enabled_by_default: bool,
+
+    /// Look through a program, returning a warning for each problem found.
+    check: fn(&Program) -> Vec<Diagnostic>,
+}
+
+impl Lint {
+    /// Whether the lint runs with the given warning settings.
+    ///
+    /// The last `-Wname` or `-Wno-name` for the lint wins. Without either, the lint runs if it is
+    /// on by default or `-Wall` was given.
+    pub fn is_enabled(&self, warnings: &Warnings) -> bool {
+        warnings
+            .overrides
+            .iter()
+            .rev()
+            .find(|(name, _)| name == self.name)
+            .map_or(self.enabled_by_default || warnings.all, |&(_, on)| on)
+    }
+}
+
+/// Every lint the compiler knows about.
+pub const LINTS: &[Lint] = &[
+    Lint {
+        name: "div-by-zero",
+        description: "dividing by an expression that is always zero",
+        enabled_by_default: true,
+        check: check_div_by_zero,
+    },
+    Lint {
+        name: "return-type",
+        description: "a function other than `main` reaching its closing brace without returning",
+        enabled_by_default: true,
+        check: check_return_type,
+    },
+    Lint {
+        name: "invalid-noreturn",
+        description: "a `return` statement in a function declared `_Noreturn`",
+        enabled_by_default: true,
+        check: check_invalid_noreturn,
+    },
+    Lint {
+        name: "attributes",
+        description: "an `__attribute__` that is not supported, and has no effect",
+        enabled_by_default: true,
+        check: check_attributes,
+    },
+    Lint {
+        name: "unused-function",
+        description: "a `static` function that is never used",
+        enabled_by_default: false,
+        check: check_unused_function,
+    },
+    Lint {
+        name: "redundant-negation",
+        description: "negating a value twice in a way that gives the value back",
+        enabled_by_default: false,
+        check: check_redundant_negation,
+    },
+    Lint {
+        name: "exit-status",
+        description: "returning a constant from `main` that does not fit in an exit status",
+        enabled_by_default: false,
+        check: check_exit_status,
+    },
+];
+
+/// Find a lint by name.
+///
+/// # Examples
+///
+/// ```
+/// use ecc::lint;
+///
+/// assert!(lint::find("redundant-negation").is_some());
+/// assert!(lint::find("no-such-lint").is_none());
+/// ```
+pub fn find(name: &str) -> Option<&'static Lint> {
+    LINTS.iter().find(|lint| lint.name == name)
+}
+
+/// Run every enabled lint on a program, returning what they found.
+///
+/// With `-Werror`, the warnings are turned into errors.
+///
+/// # Examples
+///
+/// ```
+/// use ecc::lint;
+/// use ecc::options::Warnings;
+/// use ecc::{lexer, parser};
+///
+/// let tokens = lexer::tokenize("int main(void) { return ~~1; }").unwrap();
+/// let program = parser::parse_token_stream(tokens).unwrap();
+///
+/// assert!(lint::run(&program, &Warnings::default()).is_empty());
+///
+/// let all = Warnings {
+///     all: true,
+///     ..Warnings::default()
+/// };
+/// assert_eq!(lint::run(&program, &all).len(), 1);
+/// ```
+pub fn run(program: &Program, warnings: &Warnings) -> Vec<Diagnostic> {
+    let mut found = Vec::new();
+
+    for lint in LINTS.iter().filter(|lint| lint.is_enabled(warnings)) {
+        // Say where the lint came from, unless it was asked for by name.
+        let asked_for = warnings.overrides.iter().any(|(name, _)| name == lint.name);
+        let because = if asked_for {
+            None
+        } else if lint.enabled_by_default {
+            Some(format!("`-W{}` is on by default", lint.name))
+        } else {
+            Some(format!("`-W{}` is enabled by `-Wall`", lint.name))
+        };
+
+        for mut diagnostic in (lint.check)(program) {
+            if warnings.as_errors {
+                diagnostic.level = Level::Error;
+            }
+            if let Some(because) = &because {
+                diagnostic = diagnostic.with_note(because.clone());
+            }
+            found.push(diagnostic);
+        }
+    }
+
+    found
+}
+
+/// Look for `/` and `%` whose right operand is always zero.
+///
+/// The generated `idivl` would crash the program with `SIGFPE` as soon as it ran.
+fn check_div_by_zero(program: &Program) -> Vec<Diagnostic> {
+    struct Finder(Vec<Diagnostic>);
+
+    impl Visitor for Finder {
+        fn visit_expr(&mut self, expr: &Expr) {
+            if let Expr::Binary {
+                operator: operator @ (BinaryOp::Divide | BinaryOp::Mod),
+                right,
+                span,
+                ..
+            } = expr
+                // Every expression is a constant for now, so the interpreter can fold it.
+                && matches!(interp::eval_expr(right), Ok(0))
+            {
+                let message = match operator {
+                    BinaryOp::Divide => "division by zero",
+                    _ => "remainder of division by zero",
+                };
+
+                let mut diagnostic = Diagnostic::warning(message)
+                    .with_span(*span)
+                    .with_note("the program will crash when this is evaluated");
+                if !matches!(**right, Expr::Integer { .. } | Expr::Boolean { .. }) {
+                    diagnostic = diagnostic.with_secondary(right.span(), "this is always zero");
+                }
+
+                self.0.push(diagnostic);
+            }
+
+            // Only the chosen association of a `_Generic` is ever evaluated.
+            if let Expr::Generic {
+                controlling,
+                associations,
+                ..
+            } = expr
+            {
+                if let Some(selected) = sema::select(controlling, associations) {
+                    self.visit_expr(selected);
+                }
+                return;
+            }
+
+            ast::visit::walk_expr(self, expr);
+        }
+    }
+
+    let mut finder = Finder(Vec::new());
+    finder.visit_program(program);
+    finder.0
+}
+
+/// Look for functions that can reach their closing brace without returning a value.
+///
+/// `main` is allowed to, since it returns 0 when it does, but any other function returns whatever
+/// happens to be in `%eax`. A `void` function has nothing to return, and a `_Noreturn` function is
+/// not meant to get there at all, so they are left alone.
+fn check_return_type(program: &Program) -> Vec<Diagnostic> {
+    let function = &program.function;
+    if function.name == "main"
+        || function.noreturn
+        || function.return_type == Type::Void
+        || !function.can_fall_off_end()
+    {
+        return Vec::new();
+    }
+
+    vec![
+        Diagnostic::warning(format!(
+            "`{}` can reach the end of its body without returning a value",
+            function.name
+        ))
+        .with_span(function.name_span)
+        .with_note("only `main` returns 0 when it reaches its closing brace"),
+    ]
+}
+
+/// Look for `return` statements in functions that are declared never to return.
+fn check_invalid_noreturn(program: &Program) -> Vec<Diagnostic> {
+    let function = &program.function;
+    if !function.noreturn {
+        return Vec::new();
+    }
+
+    function
+        .body
+        .iter()
+        .filter_map(|statement| match statement {
+            Statement::Return(expr) => Some(expr),
+            Statement::Asm { .. } => None,
+        })
+        .map(|expr| {
+            let span = expr.as_ref().map_or(function.name_span, Expr::span);
+            Diagnostic::warning(format!(
+                "`{}` is declared `_Noreturn`, but it returns",
+                function.name
+            ))
+            .with_span(span)
+        })
+        .collect()
+}
+
+/// Look for attributes that the compiler does not know what to do with.
+///
+/// They are skipped so that real headers can be compiled, but code that relies on one of them
+/// might not do what it should.
+fn check_attributes(program: &Program) -> Vec<Diagnostic> {
+    program
+        .function
+        .ignored_attributes
+        .iter()
+        .map(|attribute| {
+            Diagnostic::warning(format!(
+                "the `{}` attribute is not supported, and is ignored",
+                attribute.name
+            ))
+            .with_span(attribute.span)
+        })
+        .collect()
+}
+
+/// Look for `static` functions that nothing calls.
+///
+/// Nothing outside the file can call a `static` function, and there are no calls yet, so every one
+/// of them is unused. `static inline` functions are meant to be left unused by the files that do
+/// not need them, and `__attribute__((unused))` says that it is on purpose.
+fn check_unused_function(program: &Program) -> Vec<Diagnostic> {
+    let function = &program.function;
+    if !function.is_static || function.inline || function.unused {
+        return Vec::new();
+    }
+
+    vec![
+        Diagnostic::warning(format!("`{}` is never used", function.name))
+            .with_span(function.name_span)
+            .with_note("it is `static`, so only this file can call it")
+            .with_help("mark it `__attribute__((unused))` if that is on purpose"),
+    ]
+}
+
+/// Look for `~~x` and `-(-x)`, which are just `x`.
+///
+/// `!!x` is left alone, since it turns any value into 0 or 1 and is commonly written on purpose.
+fn check_redundant_negation(program: &Program) -> Vec<Diagnostic> {
+    struct Finder(Vec<Diagnostic>);
+
+    impl Visitor for Finder {
+        fn visit_expr(&mut self, expr: &Expr) {
+            if let Expr::Unary {
+                operator: outer @ (UnaryOp::Compliment | UnaryOp::NegateArith),
+                operand,
+                span,
+            } = expr
+                && let Expr::Unary {
+                    operator: inner, ..
+                } = **operand
+                && inner == *outer
+            {
+                self.0.push(
+                    Diagnostic::warning("negating a value twice gives it back unchanged")
+                        .with_span(*span),
+                );
+            }
+
+            ast::visit::walk_expr(self, expr);
+        }
+    }
+
+    let mut finder = Finder(Vec::new());
+    finder.visit_program(program);
+    finder.0
+}
+
+/// Look for `main` returning a literal that the exit status cannot hold.
+///
+/// Only the low eight bits of the value returned from `main` make it to the parent process, so
+/// `return 256;` looks like success.
+fn check_exit_status(program: &Program) -> Vec<Diagnostic> {
+    let function = &program.function;
+    if function.name != "main" {
+        return Vec::new();
+    }
+
+    function
+        .body
+        .iter()
+        .filter_map(|statement| {
+            let Statement::Return(Some(expr)) = statement else {
+                return None;
+            };
+            let value = match expr {
+                Expr::Integer { value, .. } => *value,
+                Expr::Unary {
+                    operator: UnaryOp::NegateArith,
+                    operand,
+                    ..
+                } => match **operand {
+                    Expr::Integer { value, .. } => value.wrapping_neg(),
+                    _ => return None,
+                },
+                _ => return None,
+            };
+
+            if (0..=255).contains(&value) {
+                return None;
+            }
+
+            Some(
+                Diagnostic::warning(format!(
+                    "`main` returns {value}, but the exit status will be {}",
+                    value & 0xff
+                ))
+                .with_span(expr.span())
+                .with_note("only the low eight bits of the return value reach the parent process"),
+            )
+        })
+        .collect()
+}
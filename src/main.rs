@@ -1,17 +1,441 @@
-use colored::Colorize;
+// Diagnostics are big, but they are only created when something has gone wrong, so it is not worth
+// boxing them to make the happy path's results smaller.
+#![allow(clippy::result_large_err)]
+
+use std::io::{BufRead, Write};
+
+use ecc::ast::{BoolKeyword, Function};
+use ecc::compiler::passes;
+use ecc::diagnostics::{self, Diagnostic, DiagnosticEmitter, ErrorFormat, StderrEmitter, codes};
+use ecc::options::{CompileOptions, Emit, Extensions, OptLevel, Standard, Target};
+use ecc::span::Span;
+use ecc::token::TokenKind;
+use ecc::types::Type;
+use ecc::{compdb, interp, lexer, lint, parser};
 
 fn main() {
     let mut args = std::env::args();
     let program_name = args.next().unwrap(); // This should never panic
-    let Some(file_name) = args.next() else {
-        eprintln!(
-            "{program_name}: {} {}",
-            "error:".bold().red(),
-            "no input files".bold().white()
-        );
 
+    let mut file_name = None;
+    let mut error_format = ErrorFormat::Human;
+    let mut options = CompileOptions::new();
+    let mut lint_only = false;
+    let mut interpret = false;
+    let mut database = None;
+    let mut record_database = None;
+
+    while let Some(arg) = args.next() {
+        if arg == "repl" && file_name.is_none() && !lint_only {
+            repl();
+        } else if arg == "lint" && file_name.is_none() && !lint_only {
+            // `ecc lint FILE` checks the file with every lint, and compiles nothing.
+            lint_only = true;
+            options.warnings.all = true;
+        } else if let Some(path) = arg.strip_prefix("--compile-commands=") {
+            database = Some(path.to_owned());
+        } else if let Some(path) = arg.strip_prefix("--record-compile-commands=") {
+            record_database = Some(path.to_owned());
+        } else if arg == "--interpret" {
+            interpret = true;
+        } else if arg == "--explain" {
+            let Some(code) = args.next() else {
+                fail(
+                    &program_name,
+                    "'--explain' needs an error code",
+                    error_format,
+                );
+            };
+
+            explain(&program_name, &code, error_format);
+        } else if let Some(format) = arg.strip_prefix("--error-format=") {
+            error_format = match format {
+                "human" => ErrorFormat::Human,
+                "json" => ErrorFormat::Json,
+                _ => fail(
+                    &program_name,
+                    &format!("unknown error format '{format}'"),
+                    error_format,
+                ),
+            };
+        } else if let Some(kind) = arg.strip_prefix("--emit=") {
+            options.emit = match kind {
+                "exe" => Emit::Executable,
+                "asm" => Emit::Assembly,
+                "staticlib" => Emit::StaticLib,
+                "ast" => Emit::Ast,
+                "ast-dot" => Emit::AstDot,
+                "cfg-dot" => Emit::CfgDot,
+                "ir" => Emit::Ir,
+                _ => fail(
+                    &program_name,
+                    &format!("unknown emit kind '{kind}'"),
+                    error_format,
+                ),
+            };
+        } else if let Some(name) = arg
+            .strip_prefix("--std=")
+            .or_else(|| arg.strip_prefix("-std="))
+        {
+            options.standard = Standard::from_name(name).unwrap_or_else(|| {
+                fail(
+                    &program_name,
+                    &format!("unknown standard '{name}'"),
+                    error_format,
+                )
+            });
+        } else if let Some(triple) = arg.strip_prefix("--target=") {
+            options.target = match triple {
+                "x86_64-linux-gnu" | "x86_64-unknown-linux-gnu" => Target::X86_64Linux,
+                _ => fail(
+                    &program_name,
+                    &format!("unsupported target '{triple}'"),
+                    error_format,
+                ),
+            };
+        } else if arg == "--link-with"
+            || arg == "--archive-with"
+            || arg == "--sysroot"
+            || arg == "--out-dir"
+        {
+            let Some(path) = args.next() else {
+                fail(
+                    &program_name,
+                    &format!("'{arg}' needs a path"),
+                    error_format,
+                );
+            };
+
+            match arg.as_str() {
+                "--link-with" => options.linker = Some(path.into()),
+                "--archive-with" => options.archiver = Some(path.into()),
+                "--sysroot" => options.sysroot = Some(path.into()),
+                _ => options.out_dir = Some(path.into()),
+            }
+        } else if let Some(path) = arg.strip_prefix("--link-with=") {
+            options.linker = Some(path.into());
+        } else if let Some(path) = arg.strip_prefix("--archive-with=") {
+            options.archiver = Some(path.into());
+        } else if let Some(path) = arg.strip_prefix("--sysroot=") {
+            options.sysroot = Some(path.into());
+        } else if let Some(path) = arg.strip_prefix("--out-dir=") {
+            options.out_dir = Some(path.into());
+        } else if let Some(level) = arg.strip_prefix("-O") {
+            options.opt_level = match level {
+                "0" => OptLevel::O0,
+                "" | "1" => OptLevel::O1,
+                "2" => OptLevel::O2,
+                _ => fail(
+                    &program_name,
+                    &format!("unknown optimization level '{arg}'"),
+                    error_format,
+                ),
+            };
+        } else if arg == "-g" {
+            options.debug_info = true;
+        } else if arg == "-fverbose-asm" {
+            options.source_comments = true;
+        } else if arg == "-nostdlib" {
+            options.freestanding = true;
+        } else if arg == "-ffreestanding" {
+            // This only tells the compiler not to assume anything about the C library, which it
+            // never does. Linking is up to `-nostdlib`.
+        } else if arg == "-pg" {
+            options.profiling = true;
+        } else if arg == "-fwrapv" {
+            options.wrapv = true;
+        } else if arg == "-fno-wrapv" {
+            options.wrapv = false;
+        } else if arg == "-trigraphs" {
+            options.trigraphs = true;
+        } else if arg == "-fgnu-extensions" || arg == "-Wno-pedantic" {
+            options.extensions = Extensions::Gnu;
+        } else if arg == "-pedantic" || arg == "-Wpedantic" {
+            options.extensions = Extensions::Warn;
+        } else if arg == "-pedantic-errors" || arg == "-fno-gnu-extensions" {
+            options.extensions = Extensions::Deny;
+        } else if let Some(sanitizers) = arg.strip_prefix("-fsanitize=") {
+            for sanitizer in sanitizers.split(',') {
+                match sanitizer {
+                    "undefined" => options.ub_checks = true,
+                    _ => fail(
+                        &program_name,
+                        &format!("unsupported sanitizer '{sanitizer}'"),
+                        error_format,
+                    ),
+                }
+            }
+        } else if let Some(names) = arg.strip_prefix("--passes=") {
+            // An empty list runs no passes at all.
+            let names: Vec<_> = names.split(',').filter(|name| !name.is_empty()).collect();
+            for name in &names {
+                if passes::find(name).is_none() {
+                    fail(
+                        &program_name,
+                        &format!("unknown pass '{name}'"),
+                        error_format,
+                    );
+                }
+            }
+
+            options.passes = Some(names.into_iter().map(str::to_owned).collect());
+        } else if arg == "--stack-usage" || arg == "-fstack-usage" {
+            options.stack_usage = true;
+        } else if arg == "--print-size" {
+            options.print_size = true;
+        } else if arg == "--print-after-all" {
+            options.print_after_passes = true;
+        } else if arg == "-Wall" {
+            options.warnings.all = true;
+        } else if arg == "-Werror" {
+            options.warnings.as_errors = true;
+        } else if let Some(name) = arg.strip_prefix("-W") {
+            let (name, on) = match name.strip_prefix("no-") {
+                Some(name) => (name, false),
+                None => (name, true),
+            };
+
+            if lint::find(name).is_none() {
+                fail(
+                    &program_name,
+                    &format!("unknown warning option '{arg}'"),
+                    error_format,
+                );
+            }
+
+            options.warnings.overrides.push((name.to_owned(), on));
+        } else if arg == "-M" || arg == "-MM" {
+            // There are no system headers to leave out yet, so `-MM` is the same as `-M`.
+            options.emit = Emit::Dependencies;
+            options.dependencies.get_or_insert_default();
+        } else if arg == "-MD" || arg == "-MMD" {
+            options.dependencies.get_or_insert_default();
+        } else if arg == "-MP" {
+            options.dependencies.get_or_insert_default().phony_headers = true;
+        } else if arg == "-MF" || arg == "-MT" {
+            let Some(value) = args.next() else {
+                fail(
+                    &program_name,
+                    &format!("'{arg}' needs an argument"),
+                    error_format,
+                );
+            };
+
+            let dependencies = options.dependencies.get_or_insert_default();
+            if arg == "-MF" {
+                dependencies.file = Some(value.into());
+            } else {
+                dependencies.target = Some(value);
+            }
+        } else if let Some(path) = arg.strip_prefix("-I") {
+            let path = if path.is_empty() {
+                let Some(path) = args.next() else {
+                    fail(&program_name, "'-I' needs a directory", error_format);
+                };
+                path
+            } else {
+                path.to_owned()
+            };
+
+            options.include_paths.push(path.into());
+        } else if arg.starts_with('-') {
+            fail(
+                &program_name,
+                &format!("unknown option '{arg}'"),
+                error_format,
+            );
+        } else {
+            file_name = Some(arg);
+        }
+    }
+
+    if let Err(message) = options.validate() {
+        fail(&program_name, &message, error_format);
+    }
+
+    if let Some(database) = database {
+        build_database(&program_name, &database, error_format);
+    }
+
+    let Some(file_name) = file_name else {
+        fail(&program_name, "no input files", error_format);
+    };
+
+    if let Some(database) = record_database {
+        record_command(&program_name, &database, &file_name, error_format);
+    }
+
+    let mut emitter = StderrEmitter::new(error_format);
+    let result = if lint_only {
+        ecc::lint_file(file_name, &options, &mut emitter)
+    } else if interpret {
+        // The program's return value becomes the exit status, just like when it is compiled.
+        ecc::interpret_file(file_name, &options, &mut emitter)
+            .map(|status| std::process::exit(status))
+    } else {
+        ecc::compile_file(file_name, &options, &mut emitter)
+    };
+
+    if result.is_err() {
+        emitter.finish();
         std::process::exit(1);
+    }
+}
+
+/// Compile every file in a compilation database with the flags recorded for it, and exit.
+fn build_database(program_name: &str, path: &str, error_format: ErrorFormat) -> ! {
+    let commands = match compdb::read(path) {
+        Ok(commands) => commands,
+        Err(e) => fail(program_name, &e.to_string(), error_format),
+    };
+
+    let mut emitter = StderrEmitter::new(error_format);
+    let mut failed = false;
+    for command in &commands {
+        let options = command.options();
+        if let Err(message) = options.validate() {
+            let file_name = command.source_path().display().to_string();
+            emitter.emit(&Diagnostic::error(message), &file_name, "");
+            failed = true;
+            continue;
+        }
+
+        let result = ecc::compile_file(command.source_path(), &options, &mut emitter);
+        failed |= result.is_err();
+    }
+
+    emitter.finish();
+    std::process::exit(if failed { 1 } else { 0 });
+}
+
+/// Add the command line that is compiling `file_name` to a compilation database.
+///
+/// The option asking for this is left out of what is recorded, so that tools that rerun the
+/// command do not rewrite the database.
+fn record_command(program_name: &str, path: &str, file_name: &str, error_format: ErrorFormat) {
+    let directory = match std::env::current_dir() {
+        Ok(directory) => directory,
+        Err(e) => fail(program_name, &e.to_string(), error_format),
+    };
+
+    let command = compdb::CompileCommand {
+        directory,
+        file: file_name.into(),
+        arguments: std::env::args()
+            .filter(|arg| !arg.starts_with("--record-compile-commands="))
+            .collect(),
+    };
+
+    if let Err(e) = compdb::record(path, &command) {
+        fail(program_name, &e.to_string(), error_format);
+    }
+}
+
+/// Read lines from stdin and run each one with the interpreter, printing the results, until the
+/// input runs out.
+fn repl() -> ! {
+    let stdin = std::io::stdin();
+    let mut lines = stdin.lock().lines();
+
+    loop {
+        print!("ecc> ");
+        std::io::stdout().flush().unwrap();
+
+        let Some(Ok(line)) = lines.next() else {
+            println!();
+            std::process::exit(0);
+        };
+
+        if line.trim().is_empty() {
+            continue;
+        }
+
+        match eval_line(&line) {
+            Ok(value) => println!("{value}"),
+            Err(errors) => {
+                for e in &errors {
+                    eprint!("{}", diagnostics::render(e, "<repl>", &line));
+                }
+            }
+        }
+    }
+}
+
+/// Run a line typed into the REPL, returning its value.
+///
+/// A line can be a whole function definition, a statement to run as the body of a function, or
+/// just an expression, optionally followed by a semicolon. Which one it is meant to be is decided
+/// by how it starts.
+fn eval_line(line: &str) -> Result<i32, Vec<Diagnostic>> {
+    let mut tokens = lexer::tokenize(line)
+        .map_err(|errors| errors.into_iter().map(Diagnostic::from).collect::<Vec<_>>())?;
+
+    let result = match tokens.first().map(|token| token.kind) {
+        Some(
+            TokenKind::KeywordInt
+            | TokenKind::KeywordVoid
+            | TokenKind::KeywordBool
+            | TokenKind::KeywordStatic
+            | TokenKind::KeywordInline
+            | TokenKind::KeywordNoreturn
+            | TokenKind::KeywordAttribute,
+        ) => parser::parse_function(tokens).and_then(|function| interp::run_function(&function)),
+        Some(TokenKind::KeywordReturn | TokenKind::KeywordAsm) => parser::parse_statement(tokens)
+            .and_then(|statement| {
+                interp::run_function(&Function {
+                    name: "main".to_owned(),
+                    name_span: Span::default(),
+                    is_static: false,
+                    inline: false,
+                    noreturn: false,
+                    aligned: None,
+                    unused: false,
+                    ignored_attributes: Vec::new(),
+                    return_type: Type::Int,
+                    bool_keyword: BoolKeyword::default(),
+                    body: vec![statement],
+                })
+            }),
+        _ => {
+            // The last token marks the end of the line, so the semicolon would come just before.
+            let semicolon = tokens.len().checked_sub(2);
+            if semicolon.is_some_and(|i| tokens[i].kind == TokenKind::DelimSemicolon) {
+                tokens.remove(tokens.len() - 2);
+            }
+            parser::parse_expression(tokens).and_then(|expr| interp::eval_expr(&expr))
+        }
     };
 
-    ecc::compile_and_link(file_name);
+    result.map_err(|e| vec![e])
+}
+
+/// Print the extended explanation for an error code and exit.
+fn explain(program_name: &str, code: &str, error_format: ErrorFormat) -> ! {
+    match codes::explain(code) {
+        Some(explanation) => {
+            print!("{explanation}");
+            std::process::exit(0);
+        }
+        None => fail(
+            program_name,
+            &format!("{code} is not a valid error code"),
+            error_format,
+        ),
+    }
+}
+
+/// Report an error with the command line and exit.
+fn fail(program_name: &str, message: &str, error_format: ErrorFormat) -> ! {
+    let diagnostic = Diagnostic::error(message);
+    match error_format {
+        ErrorFormat::Human => {
+            eprint!(
+                "{program_name}: {}",
+                diagnostics::render(&diagnostic, "", "")
+            )
+        }
+        ErrorFormat::Json => eprintln!("{}", diagnostics::to_json(&diagnostic, "", "")),
+    }
+
+    std::process::exit(1);
 }
@@ -0,0 +1,683 @@
+use std::path::{Path, PathBuf};
+
+/// Everything that can be configured about a compilation.
+///
+/// The defaults match what the compiler did before it could be configured at all: unoptimized
+/// x86-64 Linux code, linked into an executable. Options are changed with the `with_*` methods.
+///
+/// # Examples
+///
+/// ```
+/// use ecc::options::{CompileOptions, Emit, OptLevel};
+///
+/// let options = CompileOptions::new()
+///     .with_opt_level(OptLevel::O2)
+///     .with_emit(Emit::Assembly)
+///     .with_include_path("include");
+///
+/// assert_eq!(options.emit, Emit::Assembly);
+/// assert_eq!(options.include_paths.len(), 1);
+/// ```
+#[derive(Clone, Default, Debug)]
+pub struct CompileOptions {
+    /// The machine the generated code is meant to run on.
+    pub target: Target,
+
+    /// How hard the compiler should try to make the generated code fast.
+    pub opt_level: OptLevel,
+
+    /// Which warnings are reported, and how.
+    pub warnings: Warnings,
+
+    /// What the compiler should produce.
+    pub emit: Emit,
+
+    /// Whether to generate debug information, like `-g`.
+    pub debug_info: bool,
+
+    /// Whether to quote the source code in comments in the generated assembly, like
+    /// `-fverbose-asm`.
+    pub source_comments: bool,
+
+    /// Whether the generated code checks for undefined behavior as it runs, like
+    /// `-fsanitize=undefined`.
+    pub ub_checks: bool,
+
+    /// Whether signed arithmetic is defined to wrap around on overflow, like `-fwrapv`.
+    ///
+    /// Overflow is undefined behavior otherwise, which optimizations are allowed to assume never
+    /// happens. With this set, they have to give the two's complement answer instead, and
+    /// overflow is not reported as undefined behavior.
+    pub wrapv: bool,
+
+    /// Whether the program is built to be profiled with `gprof`, like `-pg`.
+    pub profiling: bool,
+
+    /// Whether the program is linked without the C library and brings its own `_start`, like
+    /// `-nostdlib`.
+    pub freestanding: bool,
+
+    /// Whether to write how much stack each function uses to a `.su` file next to the output,
+    /// like `-fstack-usage`.
+    pub stack_usage: bool,
+
+    /// Whether to print how many bytes each section and function of the generated code takes up,
+    /// like `--print-size`.
+    pub print_size: bool,
+
+    /// The optimization passes to run over the generated code, by name and in order, like
+    /// `--passes=fold,dce`. If this is not set, the passes are picked by the optimization level.
+    pub passes: Option<Vec<String>>,
+
+    /// Whether to print the generated code to stderr after every pass, like `--print-after-all`.
+    pub print_after_passes: bool,
+
+    /// The version of the C standard that the source is written in, like `--std=c99`.
+    pub standard: Standard,
+
+    /// Whether trigraphs like `??<` are replaced by the character they stand for, like
+    /// `-trigraphs`.
+    pub trigraphs: bool,
+
+    /// What to do when the source uses a GNU extension, or a feature the standard does not have.
+    pub extensions: Extensions,
+
+    /// Whether to write a make rule listing the files the compilation reads, and how, like `-MD`.
+    pub dependencies: Option<Dependencies>,
+
+    /// The program that assembles and links the generated code, like `--link-with=gcc`.
+    ///
+    /// By default, it is the `gcc` for the target. Cross toolchains are named after the target,
+    /// like `x86_64-linux-musl-gcc`, so they have to be given here.
+    pub linker: Option<PathBuf>,
+
+    /// The program that puts object files in a static library, like `--archive-with=ar`.
+    ///
+    /// By default, it is the `ar` that goes with the linker, as worked out by
+    /// [`CompileOptions::archiver`].
+    pub archiver: Option<PathBuf>,
+
+    /// The directory the linker treats as the root of the target's file system, where it looks for
+    /// libraries and startup files, like `--sysroot`.
+    pub sysroot: Option<PathBuf>,
+
+    /// The directory that output files are written to, like `--out-dir`. By default, they are
+    /// written next to the source file.
+    pub out_dir: Option<PathBuf>,
+
+    /// Directories searched for included headers, in order.
+    ///
+    /// There is no preprocessor yet, so these are only recorded for now.
+    pub include_paths: Vec<PathBuf>,
+}
+
+impl CompileOptions {
+    /// Create the default set of options.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Set the machine to generate code for.
+    pub fn with_target(mut self, target: Target) -> Self {
+        self.target = target;
+        self
+    }
+
+    /// Set the optimization level.
+    pub fn with_opt_level(mut self, opt_level: OptLevel) -> Self {
+        self.opt_level = opt_level;
+        self
+    }
+
+    /// Set which warnings are reported.
+    pub fn with_warnings(mut self, warnings: Warnings) -> Self {
+        self.warnings = warnings;
+        self
+    }
+
+    /// Set what the compiler should produce.
+    pub fn with_emit(mut self, emit: Emit) -> Self {
+        self.emit = emit;
+        self
+    }
+
+    /// Set whether to generate debug information.
+    pub fn with_debug_info(mut self, debug_info: bool) -> Self {
+        self.debug_info = debug_info;
+        self
+    }
+
+    /// Set whether to quote the source code in comments in the generated assembly.
+    pub fn with_source_comments(mut self, source_comments: bool) -> Self {
+        self.source_comments = source_comments;
+        self
+    }
+
+    /// Set whether the generated code checks for undefined behavior.
+    pub fn with_ub_checks(mut self, ub_checks: bool) -> Self {
+        self.ub_checks = ub_checks;
+        self
+    }
+
+    /// Set whether signed arithmetic wraps around on overflow.
+    pub fn with_wrapv(mut self, wrapv: bool) -> Self {
+        self.wrapv = wrapv;
+        self
+    }
+
+    /// Set whether the program is built to be profiled.
+    pub fn with_profiling(mut self, profiling: bool) -> Self {
+        self.profiling = profiling;
+        self
+    }
+
+    /// Set whether the program is linked without the C library.
+    pub fn with_freestanding(mut self, freestanding: bool) -> Self {
+        self.freestanding = freestanding;
+        self
+    }
+
+    /// Set whether to write a report of each function's stack usage.
+    pub fn with_stack_usage(mut self, stack_usage: bool) -> Self {
+        self.stack_usage = stack_usage;
+        self
+    }
+
+    /// Set whether to print the size of the generated code.
+    pub fn with_print_size(mut self, print_size: bool) -> Self {
+        self.print_size = print_size;
+        self
+    }
+
+    /// Set which optimization passes to run, instead of the ones the optimization level picks.
+    pub fn with_passes<I, S>(mut self, passes: I) -> Self
+    where
+        I: IntoIterator<Item = S>,
+        S: Into<String>,
+    {
+        self.passes = Some(passes.into_iter().map(Into::into).collect());
+        self
+    }
+
+    /// Set whether to print the generated code after every pass.
+    pub fn with_print_after_passes(mut self, print_after_passes: bool) -> Self {
+        self.print_after_passes = print_after_passes;
+        self
+    }
+
+    /// Set the version of the C standard that the source is written in.
+    pub fn with_standard(mut self, standard: Standard) -> Self {
+        self.standard = standard;
+        self
+    }
+
+    /// Set whether trigraphs are replaced.
+    pub fn with_trigraphs(mut self, trigraphs: bool) -> Self {
+        self.trigraphs = trigraphs;
+        self
+    }
+
+    /// Set what to do when the source uses a feature the standard does not have.
+    pub fn with_extensions(mut self, extensions: Extensions) -> Self {
+        self.extensions = extensions;
+        self
+    }
+
+    /// Set whether and how to write a make rule listing the files the compilation reads.
+    pub fn with_dependencies(mut self, dependencies: Dependencies) -> Self {
+        self.dependencies = Some(dependencies);
+        self
+    }
+
+    /// Set the program that assembles and links the generated code.
+    pub fn with_linker<P>(mut self, linker: P) -> Self
+    where
+        P: Into<PathBuf>,
+    {
+        self.linker = Some(linker.into());
+        self
+    }
+
+    /// Set the program that puts object files in a static library.
+    pub fn with_archiver<P>(mut self, archiver: P) -> Self
+    where
+        P: Into<PathBuf>,
+    {
+        self.archiver = Some(archiver.into());
+        self
+    }
+
+    /// The program that puts object files in a static library.
+    ///
+    /// Unless one is asked for, it comes from the same toolchain as the linker. A cross `gcc`
+    /// named after its target, like `x86_64-linux-musl-gcc`, has an `ar` named the same way next
+    /// to it. Any other linker gets the target's own archiver.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use std::path::Path;
+    ///
+    /// use ecc::options::CompileOptions;
+    ///
+    /// assert_eq!(CompileOptions::new().archiver(), Path::new("ar"));
+    ///
+    /// let options = CompileOptions::new().with_linker("/opt/cross/bin/x86_64-linux-musl-gcc");
+    /// assert_eq!(options.archiver(), Path::new("/opt/cross/bin/x86_64-linux-musl-ar"));
+    ///
+    /// let options = options.with_archiver("llvm-ar");
+    /// assert_eq!(options.archiver(), Path::new("llvm-ar"));
+    /// ```
+    pub fn archiver(&self) -> PathBuf {
+        if let Some(archiver) = &self.archiver {
+            return archiver.clone();
+        }
+
+        let prefix = self
+            .linker
+            .as_deref()
+            .and_then(Path::file_name)
+            .and_then(|name| name.to_str())
+            .and_then(|name| name.strip_suffix("-gcc"));
+        match (&self.linker, prefix) {
+            (Some(linker), Some(prefix)) => linker.with_file_name(format!("{prefix}-ar")),
+            _ => PathBuf::from(self.target.default_archiver()),
+        }
+    }
+
+    /// Set the directory the linker treats as the root of the target's file system.
+    pub fn with_sysroot<P>(mut self, sysroot: P) -> Self
+    where
+        P: Into<PathBuf>,
+    {
+        self.sysroot = Some(sysroot.into());
+        self
+    }
+
+    /// Set the directory that output files are written to.
+    pub fn with_out_dir<P>(mut self, out_dir: P) -> Self
+    where
+        P: Into<PathBuf>,
+    {
+        self.out_dir = Some(out_dir.into());
+        self
+    }
+
+    /// The file that compiling the given source file writes, in the output directory if there is
+    /// one, or `None` if it only prints something.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use std::path::Path;
+    ///
+    /// use ecc::options::CompileOptions;
+    ///
+    /// let options = CompileOptions::new();
+    /// let source = Path::new("src/util.c");
+    /// assert_eq!(options.output_path(source).unwrap(), Path::new("src/util"));
+    ///
+    /// let options = options.with_out_dir("build");
+    /// assert_eq!(options.output_path(source).unwrap(), Path::new("build/util"));
+    /// ```
+    pub fn output_path(&self, source: &Path) -> Option<PathBuf> {
+        let output = self.emit.output_path(source)?;
+        Some(self.place_output(&output))
+    }
+
+    /// Move a file that would be written next to the source file into the output directory, if
+    /// there is one.
+    pub fn place_output(&self, file: &Path) -> PathBuf {
+        match (&self.out_dir, file.file_name()) {
+            (Some(out_dir), Some(name)) => out_dir.join(name),
+            _ => file.to_owned(),
+        }
+    }
+
+    /// Check that the options can be used together, returning what is wrong if they cannot.
+    ///
+    /// Profiling and the undefined behavior checks both call into the C library, so neither works
+    /// in a program linked without it.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use ecc::options::CompileOptions;
+    ///
+    /// let options = CompileOptions::new().with_profiling(true);
+    /// assert!(options.validate().is_ok());
+    ///
+    /// let options = options.with_freestanding(true);
+    /// assert_eq!(
+    ///     options.validate().unwrap_err(),
+    ///     "'-nostdlib' cannot be used with '-pg', which needs the C library"
+    /// );
+    /// ```
+    pub fn validate(&self) -> Result<(), String> {
+        if self.freestanding && (self.profiling || self.ub_checks) {
+            let other = if self.profiling {
+                "-pg"
+            } else {
+                "-fsanitize=undefined"
+            };
+            return Err(format!(
+                "'-nostdlib' cannot be used with '{other}', which needs the C library"
+            ));
+        }
+
+        Ok(())
+    }
+
+    /// Add a directory to the end of the include search path.
+    pub fn with_include_path<P>(mut self, path: P) -> Self
+    where
+        P: Into<PathBuf>,
+    {
+        self.include_paths.push(path.into());
+        self
+    }
+}
+
+/// A machine that code can be generated for.
+#[derive(Clone, Copy, PartialEq, Eq, Default, Debug)]
+pub enum Target {
+    /// 64-bit x86 running Linux, using the System V ABI.
+    #[default]
+    X86_64Linux,
+}
+
+impl Target {
+    /// The target triple, as understood by `gcc` and friends.
+    pub fn triple(self) -> &'static str {
+        match self {
+            Self::X86_64Linux => "x86_64-linux-gnu",
+        }
+    }
+
+    /// The program that assembles and links code for the target, unless another one is asked for.
+    ///
+    /// Every target is the host for now, so this is always the host's `gcc`.
+    pub fn default_linker(self) -> &'static str {
+        match self {
+            Self::X86_64Linux => "gcc",
+        }
+    }
+
+    /// The program that puts object files for the target in a static library, unless another
+    /// one is asked for.
+    ///
+    /// Every target is the host for now, so this is always the host's `ar`.
+    pub fn default_archiver(self) -> &'static str {
+        match self {
+            Self::X86_64Linux => "ar",
+        }
+    }
+}
+
+/// How much effort goes into optimizing the generated code.
+#[derive(Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Default, Debug)]
+pub enum OptLevel {
+    /// No optimization at all, corresponding to `-O0`.
+    #[default]
+    O0,
+
+    /// Cheap optimizations only, corresponding to `-O1`.
+    O1,
+
+    /// Every optimization the compiler knows, corresponding to `-O2`.
+    O2,
+}
+
+/// The dialect of C that the source is written in, which decides what is a keyword and which
+/// features can be used.
+///
+/// It is a version of the C standard, maybe with GNU extensions. Like with `gcc`, the extensions
+/// that do not take away any valid ISO C are there either way, like `__attribute__` and
+/// `__asm__`. The GNU dialects also make `asm` a keyword, which it is not in ISO C.
+///
+/// The default is the GNU dialect of the newest version, so that everything the compiler supports
+/// can be used.
+///
+/// # Examples
+///
+/// ```
+/// use ecc::options::{Standard, Version};
+///
+/// assert_eq!(Standard::from_name("c2x"), Some(Standard::C23));
+/// assert_eq!(Standard::from_name("gnu11"), Some(Standard::C11.with_gnu()));
+/// assert_eq!(Standard::default().version, Version::C23);
+/// assert!(Standard::default().gnu);
+/// ```
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub struct Standard {
+    /// The version of the C standard.
+    pub version: Version,
+
+    /// Whether the GNU dialect of it is used, like `--std=gnu11`.
+    pub gnu: bool,
+}
+
+impl Default for Standard {
+    fn default() -> Self {
+        Self::C23.with_gnu()
+    }
+}
+
+impl Standard {
+    /// ISO C89, like `--std=c89`.
+    pub const C89: Self = Self::iso(Version::C89);
+
+    /// ISO C99, like `--std=c99`.
+    pub const C99: Self = Self::iso(Version::C99);
+
+    /// ISO C11, like `--std=c11`.
+    pub const C11: Self = Self::iso(Version::C11);
+
+    /// ISO C17, like `--std=c17`.
+    pub const C17: Self = Self::iso(Version::C17);
+
+    /// ISO C23, like `--std=c2x`.
+    pub const C23: Self = Self::iso(Version::C23);
+
+    /// A version of the standard without GNU extensions.
+    pub const fn iso(version: Version) -> Self {
+        Self {
+            version,
+            gnu: false,
+        }
+    }
+
+    /// The GNU dialect of the same version.
+    pub const fn with_gnu(self) -> Self {
+        Self { gnu: true, ..self }
+    }
+
+    /// Whether everything in the given dialect is also in this one.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use ecc::options::Standard;
+    ///
+    /// assert!(Standard::C11.includes(Standard::C99));
+    /// assert!(Standard::C11.with_gnu().includes(Standard::C11));
+    /// assert!(!Standard::C23.includes(Standard::C89.with_gnu()));
+    /// ```
+    pub fn includes(self, other: Self) -> bool {
+        self.version >= other.version && (self.gnu || !other.gnu)
+    }
+
+    /// Find a dialect by the name `--std` takes for it, like `c99` or `gnu11`.
+    ///
+    /// Every name `gcc` accepts works, including the ones used before a version was published,
+    /// like `c2x`.
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name.strip_prefix("gnu") {
+            Some(version) => Version::from_name(&format!("c{version}"))
+                .map(|version| Self { version, gnu: true }),
+            None => Version::from_name(name).map(Self::iso),
+        }
+    }
+
+    /// The name `gcc` gives the version in its messages, like `C99`.
+    pub fn name(self) -> &'static str {
+        self.version.name()
+    }
+}
+
+/// A version of the C standard.
+///
+/// Later versions compare greater than earlier ones.
+#[derive(Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Debug)]
+pub enum Version {
+    /// ANSI C, which ISO adopted as C90, like `--std=c89`.
+    ///
+    /// Digraphs like `<%` came with an amendment in 1995, so they are not part of it.
+    C89,
+
+    /// ISO C99, which added `inline` and `//` comments, like `--std=c99`.
+    C99,
+
+    /// ISO C11, like `--std=c11`.
+    C11,
+
+    /// ISO C17, which only fixed defects in C11, like `--std=c17`.
+    C17,
+
+    /// ISO C23, which made `bool`, `true` and `false` keywords, like `--std=c2x`.
+    C23,
+}
+
+impl Version {
+    /// Find a version by the name `--std` takes for its ISO dialect, like `c99`.
+    fn from_name(name: &str) -> Option<Self> {
+        match name {
+            "c89" | "c90" | "ansi" | "iso9899:1990" => Some(Self::C89),
+            "c99" | "c9x" | "iso9899:1999" => Some(Self::C99),
+            "c11" | "c1x" | "iso9899:2011" => Some(Self::C11),
+            "c17" | "c18" | "iso9899:2017" | "iso9899:2018" => Some(Self::C17),
+            "c23" | "c2x" | "iso9899:2024" => Some(Self::C23),
+            _ => None,
+        }
+    }
+
+    /// The name `gcc` gives the version in its messages, like `C99`.
+    pub fn name(self) -> &'static str {
+        match self {
+            Self::C89 => "C89",
+            Self::C99 => "C99",
+            Self::C11 => "C11",
+            Self::C17 => "C17",
+            Self::C23 => "C23",
+        }
+    }
+}
+
+/// What to do with features that are not part of the chosen standard, like `__attribute__`, or
+/// `_Generic` in C99.
+///
+/// Like `gcc`, the compiler accepts them quietly unless asked to be pedantic.
+#[derive(Clone, Copy, PartialEq, Eq, Default, Debug)]
+pub enum Extensions {
+    /// Accept them, like `-fgnu-extensions`.
+    #[default]
+    Gnu,
+
+    /// Accept them with a warning, like `-pedantic`.
+    Warn,
+
+    /// Reject them, like `-pedantic-errors` and `-fno-gnu-extensions`.
+    Deny,
+}
+
+/// How to write the make rule that lists the files a compilation reads.
+///
+/// See [`crate::depfile`] for what the rule looks like.
+#[derive(Clone, PartialEq, Eq, Default, Debug)]
+pub struct Dependencies {
+    /// The file to write the rule to, like `-MF`.
+    ///
+    /// By default, it goes next to the source file with the `.d` extension, or to stdout with
+    /// [`Emit::Dependencies`].
+    pub file: Option<PathBuf>,
+
+    /// The target of the rule, like `-MT`. By default, it is the file the compilation writes.
+    pub target: Option<String>,
+
+    /// Whether every header gets an empty rule of its own, like `-MP`.
+    pub phony_headers: bool,
+}
+
+/// Settings controlling which warnings are reported.
+#[derive(Clone, PartialEq, Eq, Default, Debug)]
+pub struct Warnings {
+    /// Report warnings that are off by default, like `-Wall`.
+    pub all: bool,
+
+    /// Treat every warning as an error, like `-Werror`.
+    pub as_errors: bool,
+
+    /// Lints turned on or off by name, like `-Wname` and `-Wno-name`, in the order they were
+    /// given. Later entries win over earlier ones.
+    pub overrides: Vec<(String, bool)>,
+}
+
+/// What the compiler should produce.
+#[derive(Clone, Copy, PartialEq, Eq, Default, Debug)]
+pub enum Emit {
+    /// A linked executable next to the source file, or in the output directory.
+    #[default]
+    Executable,
+
+    /// Assembly with the `.s` extension, next to the source file or in the output directory.
+    Assembly,
+
+    /// A static library named `lib<name>.a` after the source file, next to it or in the output
+    /// directory, holding the compiled code for other programs to link against.
+    StaticLib,
+
+    /// A dump of the syntax tree, printed to stdout.
+    Ast,
+
+    /// A GraphViz drawing of the syntax tree, printed to stdout.
+    AstDot,
+
+    /// A GraphViz drawing of each function's control flow graph, printed to stdout.
+    CfgDot,
+
+    /// The generated code as the compiler sees it, printed to stdout.
+    Ir,
+
+    /// A make rule listing the files the source reads, printed to stdout, like `-M`. Nothing is
+    /// compiled.
+    Dependencies,
+}
+
+impl Emit {
+    /// The file that compiling the given source file writes, if it writes one next to it rather
+    /// than printing to stdout.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use std::path::Path;
+    ///
+    /// use ecc::options::Emit;
+    ///
+    /// let source = Path::new("src/util.c");
+    /// assert_eq!(Emit::Executable.output_path(source).unwrap(), Path::new("src/util"));
+    /// assert_eq!(Emit::StaticLib.output_path(source).unwrap(), Path::new("src/libutil.a"));
+    /// assert_eq!(Emit::Ast.output_path(source), None);
+    /// ```
+    pub fn output_path(self, source: &Path) -> Option<PathBuf> {
+        match self {
+            Self::Executable => Some(source.with_extension("")),
+            Self::Assembly => Some(source.with_extension("s")),
+            Self::StaticLib => {
+                let name = source.file_stem().unwrap_or_default().to_string_lossy();
+                Some(source.with_file_name(format!("lib{name}.a")))
+            }
+            Self::Ast | Self::AstDot | Self::CfgDot | Self::Ir | Self::Dependencies => None,
+        }
+    }
+}
@@ -1,45 +1,94 @@
 use crate::ast;
-use crate::token::{Token, TokenKind};
+use crate::diagnostics::{Diagnostic, codes, edit_distance};
+use crate::options::Standard;
+use crate::span::Span;
+use crate::token::{self, Token, TokenKind};
+use crate::types::Type;
 
-/// An error that can be generated while parsing.
-#[derive(Clone, Debug)]
-pub struct ParseError {
-    pub token: Option<Token>,
-    pub message: String,
-}
+/// A simple type alias for a [`Result`] whose [`Err`] variant contains a [`Diagnostic`].
+pub type ParseResult<T> = Result<T, Diagnostic>;
 
-impl ParseError {
-    /// Create a new parse error.
-    fn new(token: Option<Token>, message: impl Into<String>) -> Self {
-        Self {
-            token,
-            message: message.into(),
-        }
-    }
+/// Parse a stream of tokens into a program.
+///
+/// The tokens are taken from the stream one at a time as the parser needs them, so they do not have
+/// to be collected first. The parser decides what to do from the current token alone, which is the
+/// only one it has taken from the stream without consuming it.
+///
+/// # Examples
+///
+/// ```
+/// use ecc::lexer::Lexer;
+/// use ecc::parser;
+///
+/// // Lex the program while it is being parsed, stopping at the first error.
+/// let tokens = Lexer::new("int main(void) { return 0; }").map_while(Result::ok);
+/// let program = parser::parse_token_stream(tokens).unwrap();
+///
+/// assert_eq!(program.function.name, "main");
+/// ```
+pub fn parse_token_stream<'a, T>(stream: T) -> ParseResult<ast::Program>
+where
+    T: IntoIterator<Item = Token<'a>>,
+{
+    Parser::new(stream.into_iter()).parse_program()
+}
 
-    /// Create a new parse error at the specified token.
-    fn at_token(token: Token, message: impl Into<String>) -> Self {
-        Self::new(Some(token), message)
-    }
+/// Parse a stream of tokens into a single function definition.
+///
+/// Like [`parse_token_stream`], every token must be used, so trailing tokens are an error.
+pub fn parse_function<'a, T>(stream: T) -> ParseResult<ast::Function>
+where
+    T: IntoIterator<Item = Token<'a>>,
+{
+    parse_whole_stream(stream, Parser::parse_function)
+}
 
-    /// Create a new end of file parse error.
-    fn end_of_file(message: impl Into<String>) -> Self {
-        Self::new(None, message)
-    }
+/// Parse a stream of tokens into a single statement.
+///
+/// Like [`parse_token_stream`], every token must be used, so trailing tokens are an error.
+pub fn parse_statement<'a, T>(stream: T) -> ParseResult<ast::Statement>
+where
+    T: IntoIterator<Item = Token<'a>>,
+{
+    parse_whole_stream(stream, Parser::parse_statement)
 }
 
-/// A simple type alias for a [`Result`] whose [`Err`] variant contains a [`ParseError`].
-pub type ParseResult<T> = Result<T, ParseError>;
+/// Parse a stream of tokens into a single expression.
+///
+/// Like [`parse_token_stream`], every token must be used, so trailing tokens are an error.
+///
+/// # Examples
+///
+/// ```
+/// use ecc::ast::{BinaryOp, Expr};
+/// use ecc::{lexer, parser};
+///
+/// let tokens = lexer::tokenize("1 + 2 * 3").unwrap();
+/// let expr = parser::parse_expression(tokens).unwrap();
+///
+/// assert!(matches!(expr, Expr::Binary { operator: BinaryOp::Plus, .. }));
+/// ```
+pub fn parse_expression<'a, T>(stream: T) -> ParseResult<ast::Expr>
+where
+    T: IntoIterator<Item = Token<'a>>,
+{
+    parse_whole_stream(stream, |parser| parser.parse_expression())
+}
 
-/// Parse a stream of tokens into a program.
-pub fn parse_token_stream<T>(stream: T) -> ParseResult<ast::Program>
+/// Run the given parse method on a stream of tokens, making sure that it uses all of them.
+fn parse_whole_stream<'a, T, R>(
+    stream: T,
+    parse: impl FnOnce(&mut Parser<'a, T::IntoIter>) -> ParseResult<R>,
+) -> ParseResult<R>
 where
-    T: IntoIterator<Item = Token>,
+    T: IntoIterator<Item = Token<'a>>,
 {
-    let tokens: Vec<_> = stream.into_iter().collect();
-    let mut parser = Parser::new(tokens);
+    let mut parser = Parser::new(stream.into_iter());
+
+    let node = parse(&mut parser)?;
+    parser.expect_end_of_file()?;
 
-    parser.parse_program()
+    Ok(node)
 }
 
 /// A level of operator precedence.
@@ -47,7 +96,7 @@ where
 /// The order of the members in this enum is very important. The members lower down are the
 /// precedences that bind the tightest. For example, [`Precedence::Prefix`] is lower than
 /// [`Precedence::Product`] since unary (prefix) operators bind more tightly than multiplication
-/// and division.
+/// and division. C has fifteen levels, and each new one goes in its place in this list.
 #[derive(Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Debug)]
 enum Precedence {
     Lowest,
@@ -56,43 +105,249 @@ enum Precedence {
     Prefix,
 }
 
-fn get_prefix_precedence(kind: TokenKind) -> Precedence {
-    match kind {
-        TokenKind::OperatorBang => Precedence::Prefix,
-        TokenKind::OperatorMinus => Precedence::Prefix,
-        TokenKind::OperatorTilde => Precedence::Prefix,
-        _ => Precedence::Lowest,
+impl Precedence {
+    /// How strongly an operator with this precedence holds on to its operands.
+    ///
+    /// The powers are spaced two apart, so that associativity can make one side of an operator
+    /// hold on slightly more strongly than the other. See [`InfixOperator::binding_power`].
+    fn binding_power(self) -> u8 {
+        self as u8 * 2
     }
 }
 
-fn get_infix_precedence(kind: TokenKind) -> Precedence {
-    match kind {
-        TokenKind::OperatorPlus => Precedence::Sum,
-        TokenKind::OperatorMinus => Precedence::Sum,
-        TokenKind::OperatorStar => Precedence::Product,
-        TokenKind::OperatorSlash => Precedence::Product,
-        TokenKind::OperatorPercent => Precedence::Product,
-        _ => Precedence::Lowest,
+/// Which way operators of the same precedence group.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+enum Associativity {
+    /// `a - b - c` means `(a - b) - c`.
+    Left,
+
+    /// `a = b = c` means `a = (b = c)`.
+    Right,
+}
+
+/// An operator that goes between its two operands.
+struct InfixOperator {
+    token: TokenKind,
+    operator: ast::BinaryOp,
+    precedence: Precedence,
+    associativity: Associativity,
+}
+
+impl InfixOperator {
+    /// How strongly the operator holds on to the operands on its left and on its right.
+    ///
+    /// An operand sitting between two operators goes to the one that holds on to it more
+    /// strongly. Operators of the same precedence tie, so associativity breaks the tie by making
+    /// the operator hold on to one side slightly more strongly: in `a - b - c`, the first `-`
+    /// holds on to `b` more strongly than the second one does, so it gets it.
+    fn binding_power(&self) -> (u8, u8) {
+        let power = self.precedence.binding_power();
+        match self.associativity {
+            Associativity::Left => (power, power + 1),
+            Associativity::Right => (power + 1, power),
+        }
     }
 }
 
+/// Every infix operator. Adding an operator to the grammar is a matter of adding it here.
+const INFIX_OPERATORS: &[InfixOperator] = &[
+    InfixOperator {
+        token: TokenKind::OperatorPlus,
+        operator: ast::BinaryOp::Plus,
+        precedence: Precedence::Sum,
+        associativity: Associativity::Left,
+    },
+    InfixOperator {
+        token: TokenKind::OperatorMinus,
+        operator: ast::BinaryOp::Minus,
+        precedence: Precedence::Sum,
+        associativity: Associativity::Left,
+    },
+    InfixOperator {
+        token: TokenKind::OperatorStar,
+        operator: ast::BinaryOp::Times,
+        precedence: Precedence::Product,
+        associativity: Associativity::Left,
+    },
+    InfixOperator {
+        token: TokenKind::OperatorSlash,
+        operator: ast::BinaryOp::Divide,
+        precedence: Precedence::Product,
+        associativity: Associativity::Left,
+    },
+    InfixOperator {
+        token: TokenKind::OperatorPercent,
+        operator: ast::BinaryOp::Mod,
+        precedence: Precedence::Product,
+        associativity: Associativity::Left,
+    },
+];
+
+/// Every prefix operator, which all have [`Precedence::Prefix`].
+const PREFIX_OPERATORS: &[(TokenKind, ast::UnaryOp)] = &[
+    (TokenKind::OperatorBang, ast::UnaryOp::NegateLogical),
+    (TokenKind::OperatorMinus, ast::UnaryOp::NegateArith),
+    (TokenKind::OperatorTilde, ast::UnaryOp::Compliment),
+];
+
+fn infix_operator(kind: TokenKind) -> Option<&'static InfixOperator> {
+    INFIX_OPERATORS
+        .iter()
+        .find(|operator| operator.token == kind)
+}
+
+fn prefix_operator(kind: TokenKind) -> Option<ast::UnaryOp> {
+    PREFIX_OPERATORS
+        .iter()
+        .find(|&&(token, _)| token == kind)
+        .map(|&(_, operator)| operator)
+}
+
+/// The specifiers a function has been declared with so far.
+#[derive(Default)]
+struct Specifiers {
+    is_static: bool,
+    inline: bool,
+    noreturn: bool,
+    aligned: Option<u32>,
+    unused: bool,
+    ignored_attributes: Vec<ast::Attribute>,
+}
+
 /// The parser.
-struct Parser {
-    tokens: Vec<Token>,
-    current: usize,
+///
+/// It only holds on to the token it is pointing to and the one before it, and takes the rest from
+/// the stream as it goes.
+struct Parser<'a, I> {
+    tokens: I,
+
+    /// The token the parser is pointing to, or `None` at the end of the stream.
+    current: Option<Token<'a>>,
+    previous: Option<Token<'a>>,
+
+    /// Where the source ends, if the stream ended with an end of file token that says so.
+    end_of_file: Option<Span>,
 }
 
-impl Parser {
-    fn new(tokens: Vec<Token>) -> Self {
-        Self { tokens, current: 0 }
+impl<'a, I: Iterator<Item = Token<'a>>> Parser<'a, I> {
+    /// Create a parser for the given tokens.
+    ///
+    /// If the tokens end with an end of file token, it is not parsed, and only used to decide
+    /// where errors about running out of tokens should point.
+    fn new(tokens: I) -> Self {
+        let mut parser = Self {
+            tokens,
+            current: None,
+            previous: None,
+            end_of_file: None,
+        };
+        parser.current = parser.next_from_stream();
+
+        parser
+    }
+
+    /// Take the next token from the stream, keeping the end of file token out of the way.
+    fn next_from_stream(&mut self) -> Option<Token<'a>> {
+        let token = self.tokens.next()?;
+        if token.kind == TokenKind::SpecialEndOfFile {
+            self.end_of_file = Some(token.span);
+            return None;
+        }
+
+        Some(token)
+    }
+
+    /// Create a syntax error pointing at the given token.
+    ///
+    /// If the token is an identifier only because the chosen version of C does not have it as a
+    /// keyword yet, the error says so.
+    fn error_at_token(token: &Token, message: impl Into<String>) -> Diagnostic {
+        let error = Diagnostic::error(message)
+            .with_code(codes::UNEXPECTED_TOKEN)
+            .with_span(token.span);
+
+        let standard = token::keyword_standard(token.lexeme);
+        if token.kind == TokenKind::LiteralIdentifier && standard != Standard::C89 {
+            let note = if standard.gnu {
+                format!(
+                    "`{}` is only a keyword in the GNU dialects of C, like `--std=gnu11`",
+                    token.lexeme
+                )
+            } else {
+                format!(
+                    "`{}` is only a keyword from {} on",
+                    token.lexeme,
+                    standard.name()
+                )
+            };
+            return error.with_note(note);
+        }
+
+        error
+    }
+
+    /// Create a syntax error for running out of tokens.
+    ///
+    /// The error points at the end of the source, and the message says what the parser was
+    /// looking for when it got there.
+    fn error_at_end(&self, message: impl Into<String>) -> Diagnostic {
+        // Without an end of file token, the source ends where the last token does.
+        let end_of_file = self.end_of_file.unwrap_or_else(|| match self.previous {
+            Some(token) => Span::new(token.span.end, token.span.end),
+            None => Span::default(),
+        });
+
+        Diagnostic::error("unexpected end of file")
+            .with_code(codes::UNEXPECTED_END_OF_FILE)
+            .with_span(end_of_file)
+            .with_note(message)
+    }
+
+    /// Suggest inserting a delimiter of the given kind right after the previous token.
+    ///
+    /// Forgetting a delimiter (usually a semicolon) is the most common syntax error, and the place
+    /// it belongs is right after the code that comes before it, not where the parser noticed.
+    fn suggest_insertion(&self, error: Diagnostic, kind: TokenKind) -> Diagnostic {
+        let (Some(spelling), Some(previous)) = (kind.spelling(), self.previous()) else {
+            return error;
+        };
+
+        let end = previous.span.end;
+        error.with_suggestion(
+            Span::new(end, end),
+            spelling,
+            format!("insert '{spelling}' here"),
+        )
+    }
+
+    /// Suggest replacing an identifier that looks like a misspelled keyword.
+    fn suggest_keyword(error: Diagnostic, found: &Token, keyword: TokenKind) -> Diagnostic {
+        let Some(spelling) = keyword.spelling() else {
+            return error;
+        };
+
+        if found.kind != TokenKind::LiteralIdentifier || edit_distance(found.lexeme, spelling) > 2 {
+            return error;
+        }
+
+        error.with_suggestion(found.span, spelling, format!("did you mean '{spelling}'?"))
+    }
+
+    /// Create a syntax error pointing at the given token, or at the end if there is none.
+    fn error_at(&self, token: Option<&Token>, message: impl Into<String>) -> Diagnostic {
+        match token {
+            Some(token) => Self::error_at_token(token, message),
+            None => self.error_at_end(message),
+        }
     }
 
     /// Advance the parser and return the next token.
     ///
     /// If the parser has reached the end of the token stream, [`None`] is returned.
-    fn advance(&mut self) -> Option<Token> {
-        let token = self.tokens.get(self.current).cloned()?;
-        self.current += 1;
+    fn advance(&mut self) -> Option<Token<'a>> {
+        let token = self.current?;
+        self.previous = Some(token);
+        self.current = self.next_from_stream();
         Some(token)
     }
 
@@ -101,35 +356,51 @@ impl Parser {
     /// This helper macro checks the kind of the token that the lexer is currently pointing to. If the
     /// kind matches the given pattern, then an [`Ok`] variant containing the peeked token is returned.
     /// If the pattern did not match, an [`Err`] variant is returned.
-    fn advance_expect(&mut self, kind: TokenKind) -> ParseResult<Token> {
+    fn advance_expect(&mut self, kind: TokenKind) -> ParseResult<Token<'a>> {
         let message = format!("expected {kind}");
 
         let Some(token) = self.peek() else {
-            return Err(ParseError::end_of_file(message));
+            let error = self.error_at_end(message);
+            return Err(self.suggest_insertion(error, kind));
         };
 
         if token.kind != kind {
-            return Err(ParseError::at_token(token.clone(), message));
+            let error = Self::error_at_token(token, message);
+            let error = if kind.is_delimiter() {
+                self.suggest_insertion(error, kind)
+            } else if kind.is_keyword() {
+                Self::suggest_keyword(error, token, kind)
+            } else {
+                error
+            };
+
+            return Err(error);
         }
 
         Ok(self.advance().unwrap())
     }
 
     /// Get the next token, or an error if there is none.
-    fn advance_expect_anything(&mut self, message: impl Into<String>) -> ParseResult<Token> {
-        self.advance().ok_or(ParseError::end_of_file(message))
+    fn advance_expect_anything(&mut self, message: impl Into<String>) -> ParseResult<Token<'a>> {
+        let token = self.advance();
+        token.ok_or_else(|| self.error_at_end(message))
     }
 
     /// Get the token the parser is currently pointing to.
     ///
     /// If the parser has reached the end of the token stream and is pointing to nothing, a null
     /// optional is returned.
-    fn peek(&self) -> Option<&Token> {
-        self.tokens.get(self.current)
+    fn peek(&self) -> Option<&Token<'a>> {
+        self.current.as_ref()
     }
 
-    fn peek_expect_anything(&self, message: String) -> ParseResult<&Token> {
-        self.peek().ok_or(ParseError::end_of_file(message))
+    /// Get the token right before the one the parser is currently pointing to.
+    fn previous(&self) -> Option<&Token<'a>> {
+        self.previous.as_ref()
+    }
+
+    fn peek_expect_anything(&self, message: String) -> ParseResult<&Token<'a>> {
+        self.peek().ok_or_else(|| self.error_at_end(message))
     }
 
     /// Parse a program.
@@ -138,36 +409,263 @@ impl Parser {
     /// that there are no more tokens to be processed. If there are, an exception is thrown.
     fn parse_program(&mut self) -> ParseResult<ast::Program> {
         let function = self.parse_function()?;
-        if let Some(token) = self.peek() {
-            Err(ParseError::at_token(token.clone(), "expected end of file"))
-        } else {
-            Ok(ast::Program { function })
+        self.expect_end_of_file()
+            .map_err(|e| e.with_note("a program consists of a single function"))?;
+
+        Ok(ast::Program { function })
+    }
+
+    /// Make sure that every token has been parsed.
+    fn expect_end_of_file(&self) -> ParseResult<()> {
+        match self.peek() {
+            Some(token) => Err(Self::error_at_token(token, "expected end of file")
+                .with_code(codes::TRAILING_TOKENS)),
+            None => Ok(()),
         }
     }
 
     /// Parse a function declaration.
     ///
     /// This method parses the return type, function name, parameter list, and body of a function.
+    /// Specifiers like `static` and `_Noreturn` can come before or after the return type.
     fn parse_function(&mut self) -> ParseResult<ast::Function> {
-        self.advance_expect(TokenKind::KeywordInt)?;
+        let mut specifiers = Specifiers::default();
+        self.parse_function_specifiers(&mut specifiers)?;
+        let (return_type, bool_keyword) = self.parse_return_type()?;
+        self.parse_function_specifiers(&mut specifiers)?;
 
-        let name = self.parse_identifier()?;
+        let (name, name_span) = self.parse_identifier()?;
 
         self.advance_expect(TokenKind::DelimParenLeft)?;
         self.advance_expect(TokenKind::KeywordVoid)?;
         self.advance_expect(TokenKind::DelimParenRight)?;
         self.advance_expect(TokenKind::DelimBraceLeft)?;
 
-        let return_statement = self.parse_statement()?;
+        let mut body = Vec::new();
+        while let Some(token) = self.peek()
+            && token.kind != TokenKind::DelimBraceRight
+        {
+            body.push(self.parse_statement()?);
+        }
 
         self.advance_expect(TokenKind::DelimBraceRight)?;
 
         Ok(ast::Function {
             name,
-            body: vec![return_statement],
+            name_span,
+            is_static: specifiers.is_static,
+            inline: specifiers.inline,
+            noreturn: specifiers.noreturn,
+            aligned: specifiers.aligned,
+            unused: specifiers.unused,
+            ignored_attributes: specifiers.ignored_attributes,
+            return_type,
+            bool_keyword,
+            body,
         })
     }
 
+    /// Parse the type a function returns, which is `int`, `bool` or `void`, along with the
+    /// keyword it is spelled with if it is `bool`.
+    fn parse_return_type(&mut self) -> ParseResult<(Type, ast::BoolKeyword)> {
+        match self.parse_type_name() {
+            Some((ty, token)) => Ok((ty, Self::bool_keyword(&token))),
+            None => {
+                self.advance_expect(TokenKind::KeywordInt)?;
+                Ok((Type::Int, ast::BoolKeyword::default()))
+            }
+        }
+    }
+
+    /// Parse the next type name, if there is one, along with the token that names it.
+    ///
+    /// Every type that can be named is spelled with a single keyword for now.
+    fn parse_type_name(&mut self) -> Option<(Type, Token<'a>)> {
+        let ty = match self.peek()?.kind {
+            TokenKind::KeywordInt => Type::Int,
+            TokenKind::KeywordBool => Type::Bool,
+            TokenKind::KeywordVoid => Type::Void,
+            _ => return None,
+        };
+
+        self.advance().map(|token| (ty, token))
+    }
+
+    /// Which keyword a type name token is, if it names `bool`.
+    fn bool_keyword(token: &Token) -> ast::BoolKeyword {
+        match token.lexeme {
+            "_Bool" => ast::BoolKeyword::UnderscoreBool,
+            _ => ast::BoolKeyword::Bool,
+        }
+    }
+
+    /// Parse any number of `static`, `inline` and `_Noreturn` specifiers, and `__attribute__`s.
+    ///
+    /// Function specifiers can be repeated, but a storage class cannot.
+    fn parse_function_specifiers(&mut self, specifiers: &mut Specifiers) -> ParseResult<()> {
+        loop {
+            let Some(token) = self.peek() else {
+                return Ok(());
+            };
+
+            match token.kind {
+                TokenKind::KeywordStatic if specifiers.is_static => {
+                    return Err(Self::error_at_token(token, "duplicate `static`"));
+                }
+                TokenKind::KeywordStatic => specifiers.is_static = true,
+                TokenKind::KeywordInline => specifiers.inline = true,
+                TokenKind::KeywordNoreturn => specifiers.noreturn = true,
+                TokenKind::KeywordAttribute => {
+                    self.parse_attributes(specifiers)?;
+                    continue;
+                }
+                _ => return Ok(()),
+            }
+            self.advance();
+        }
+    }
+
+    /// Parse a GNU `__attribute__((...))`, which holds a comma separated list of attributes.
+    fn parse_attributes(&mut self, specifiers: &mut Specifiers) -> ParseResult<()> {
+        self.advance_expect(TokenKind::KeywordAttribute)?;
+        self.advance_expect(TokenKind::DelimParenLeft)?;
+        self.advance_expect(TokenKind::DelimParenLeft)?;
+
+        while self
+            .peek()
+            .is_some_and(|token| token.kind != TokenKind::DelimParenRight)
+        {
+            self.parse_attribute(specifiers)?;
+            if self
+                .peek()
+                .is_none_or(|token| token.kind != TokenKind::DelimComma)
+            {
+                break;
+            }
+            self.advance();
+        }
+
+        self.advance_expect(TokenKind::DelimParenRight)?;
+        self.advance_expect(TokenKind::DelimParenRight)?;
+        Ok(())
+    }
+
+    /// Parse a single attribute, with its arguments if it has any.
+    ///
+    /// Every attribute can also be spelled with two underscores on either side, like
+    /// `__noreturn__`, so that headers work even if a macro called `noreturn` is defined. Only
+    /// `noreturn`, `aligned` and `unused` mean anything so far. Any other attribute is kept, so
+    /// that a lint can say it was ignored and the formatter can print it back, but its arguments
+    /// are only kept as text.
+    fn parse_attribute(&mut self, specifiers: &mut Specifiers) -> ParseResult<()> {
+        let attribute = self.advance_expect(TokenKind::LiteralIdentifier)?;
+        let name = attribute
+            .lexeme
+            .strip_prefix("__")
+            .and_then(|name| name.strip_suffix("__"))
+            .unwrap_or(attribute.lexeme);
+        let has_arguments = self
+            .peek()
+            .is_some_and(|token| token.kind == TokenKind::DelimParenLeft);
+
+        match name {
+            "noreturn" | "unused" if has_arguments => {
+                return Err(Self::error_at_token(
+                    self.peek().unwrap(),
+                    format!("the `{name}` attribute does not take arguments"),
+                ));
+            }
+            "noreturn" => specifiers.noreturn = true,
+            "unused" => specifiers.unused = true,
+            "aligned" => {
+                // Without an argument, the most that anything needs, which is 16 on x86-64.
+                let mut align = 16;
+                if has_arguments {
+                    self.advance();
+                    let integer = self.advance_expect(TokenKind::LiteralInteger)?;
+                    align = Self::parse_alignment(&integer)?;
+                    self.advance_expect(TokenKind::DelimParenRight)?;
+                }
+
+                // The strictest alignment that is asked for wins.
+                specifiers.aligned = specifiers.aligned.max(Some(align));
+            }
+            _ => {
+                let arguments = if has_arguments {
+                    Some(self.parse_parenthesized_text()?)
+                } else {
+                    None
+                };
+
+                let end = self.previous().unwrap().span.end;
+                specifiers.ignored_attributes.push(ast::Attribute {
+                    name: attribute.lexeme.to_owned(),
+                    arguments,
+                    span: Span::new(attribute.span.start, end),
+                });
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Get the alignment that an `aligned` attribute asks for, which has to be a power of two.
+    fn parse_alignment(integer: &Token) -> ParseResult<u32> {
+        let error = |message: String| {
+            Diagnostic::error(message)
+                .with_code(codes::INVALID_ALIGNMENT)
+                .with_span(integer.span)
+        };
+
+        // The literal is all digits, so it only fails to parse if it is too large.
+        let Ok(align) = integer.lexeme.parse::<u32>() else {
+            return Err(error(format!(
+                "requested alignment `{}` is too large",
+                integer.lexeme
+            ))
+            .with_note(format!(
+                "the largest alignment is {}",
+                1u32 << (u32::BITS - 1)
+            )));
+        };
+        if !align.is_power_of_two() {
+            return Err(error(format!(
+                "requested alignment `{align}` is not a power of two"
+            )));
+        }
+
+        Ok(align)
+    }
+
+    /// Parse a parenthesized list of tokens, including any parentheses nested inside of it,
+    /// without looking at what they mean.
+    ///
+    /// The text between the outer parentheses is returned, with a single space wherever there was
+    /// whitespace between two tokens.
+    fn parse_parenthesized_text(&mut self) -> ParseResult<String> {
+        let mut previous = self.advance_expect(TokenKind::DelimParenLeft)?;
+        let mut text = String::new();
+
+        let mut depth = 1;
+        loop {
+            let token = self.advance_expect_anything("expected `)`")?;
+            match token.kind {
+                TokenKind::DelimParenLeft => depth += 1,
+                TokenKind::DelimParenRight => depth -= 1,
+                _ => {}
+            }
+            if depth == 0 {
+                return Ok(text);
+            }
+
+            if !text.is_empty() && token.span.start > previous.span.end {
+                text.push(' ');
+            }
+            text.push_str(token.lexeme);
+            previous = token;
+        }
+    }
+
     /// Parse the next statement.
     ///
     /// This method looks at the next token in the stream and decides based on that what kind of
@@ -176,32 +674,124 @@ impl Parser {
         let token = self.peek();
         match token.map(|t| t.kind) {
             Some(TokenKind::KeywordReturn) => self.parse_return(),
-            _ => Err(ParseError::new(token.cloned(), "expected statement")),
+            Some(TokenKind::KeywordAsm) => self.parse_asm(),
+            Some(TokenKind::LiteralIdentifier) => {
+                let token = token.unwrap();
+                let error = Self::error_at_token(token, "expected statement");
+                Err(Self::suggest_keyword(
+                    error,
+                    token,
+                    TokenKind::KeywordReturn,
+                ))
+            }
+            _ => Err(self.error_at(token, "expected statement")),
         }
     }
 
     /// Parse the next return statement.
     ///
-    /// This method expects a return keyword followed by an expression and then a semicolon.
+    /// This method expects a return keyword followed by an expression and then a semicolon. The
+    /// expression can be left out, for functions that return `void`.
     fn parse_return(&mut self) -> ParseResult<ast::Statement> {
         self.advance_expect(TokenKind::KeywordReturn)?;
-        let return_value = self.parse_expression(Precedence::Lowest)?;
+        let return_value = if self
+            .peek()
+            .is_some_and(|token| token.kind == TokenKind::DelimSemicolon)
+        {
+            None
+        } else {
+            Some(self.parse_expression()?)
+        };
         self.advance_expect(TokenKind::DelimSemicolon)?;
         Ok(ast::Statement::Return(return_value))
     }
 
-    /// Parse the next expression.
+    /// Parse the next basic `asm` statement.
     ///
-    /// This method looks at the next token in the stream and decides based on that what kind of
-    /// expression to parse. In the future, this method may take advantage of Pratt parsing.
-    fn parse_expression(&mut self, prec: Precedence) -> ParseResult<ast::Expr> {
-        let token = self.peek_expect_anything("expected expression".to_string())?;
-        let mut left = self.parse_prefix(token.clone())?;
+    /// Only the basic form is supported: a single string literal, without the output, input, and
+    /// clobber lists that extended `asm` takes after it. Basic `asm` is always `volatile`, so
+    /// saying so changes nothing.
+    fn parse_asm(&mut self) -> ParseResult<ast::Statement> {
+        let keyword = self.advance_expect(TokenKind::KeywordAsm)?;
+        if self
+            .peek()
+            .is_some_and(|token| token.kind == TokenKind::KeywordVolatile)
+        {
+            self.advance();
+        }
+        self.advance_expect(TokenKind::DelimParenLeft)?;
+        let literal = self.advance_expect(TokenKind::LiteralString)?;
+        let template = Self::unescape(&literal)?;
 
-        while let Some(token) = self.peek()
-            && prec < get_infix_precedence(token.kind)
+        self.advance_expect(TokenKind::DelimParenRight)?;
+        let semicolon = self.advance_expect(TokenKind::DelimSemicolon)?;
+        Ok(ast::Statement::Asm {
+            template,
+            span: Span::new(keyword.span.start, semicolon.span.end),
+        })
+    }
+
+    /// Work out the text that a string literal stands for, replacing its escape sequences.
+    ///
+    /// Only the simple escape sequences, like `\n` and `\"`, are supported so far.
+    fn unescape(literal: &Token) -> ParseResult<String> {
+        let quoted = &literal.lexeme[1..literal.lexeme.len() - 1];
+        let mut text = String::with_capacity(quoted.len());
+        let mut chars = quoted.chars();
+
+        while let Some(c) = chars.next() {
+            if c != '\\' {
+                text.push(c);
+                continue;
+            }
+
+            text.push(match chars.next() {
+                Some(c @ ('\'' | '"' | '?' | '\\')) => c,
+                Some('a') => '\x07',
+                Some('b') => '\x08',
+                Some('f') => '\x0c',
+                Some('n') => '\n',
+                Some('r') => '\r',
+                Some('t') => '\t',
+                Some('v') => '\x0b',
+                Some(c) => {
+                    return Err(
+                        Diagnostic::error(format!("unsupported escape sequence `\\{c}`"))
+                            .with_code(codes::UNSUPPORTED_ESCAPE_SEQUENCE)
+                            .with_span(literal.span),
+                    );
+                }
+                None => unreachable!("the lexer does not end a string after a backslash"),
+            });
+        }
+
+        Ok(text)
+    }
+
+    /// Parse the next expression.
+    fn parse_expression(&mut self) -> ParseResult<ast::Expr> {
+        self.parse_operand(Precedence::Lowest.binding_power())
+    }
+
+    /// Parse the next expression, stopping at the first infix operator that does not hold on to
+    /// its left operand at least as strongly as `min_power`.
+    ///
+    /// This is a Pratt parser: a prefix expression is parsed, and then as long as the next
+    /// operator holds on to it strongly enough, it becomes the left operand of that operator.
+    /// Every operator's precedence and associativity comes from [`INFIX_OPERATORS`].
+    fn parse_operand(&mut self, min_power: u8) -> ParseResult<ast::Expr> {
+        let token = *self.peek_expect_anything("expected expression".to_string())?;
+        let mut left = self.parse_prefix(token)?;
+
+        while let Some(&token) = self.peek()
+            && let Some(operator) = infix_operator(token.kind)
         {
-            left = self.parse_infix(token.clone(), left)?;
+            let (left_power, right_power) = operator.binding_power();
+            if left_power < min_power {
+                break;
+            }
+
+            left = self.parse_binary(operator.operator, left, right_power)?;
         }
 
         Ok(left)
@@ -210,43 +800,34 @@ impl Parser {
     fn parse_prefix(&mut self, token: Token) -> ParseResult<ast::Expr> {
         match token.kind {
             TokenKind::DelimParenLeft => self.parse_group(),
-            TokenKind::LiteralIdentifier => todo!(),
+            TokenKind::KeywordGeneric => self.parse_generic(),
+            TokenKind::LiteralIdentifier => {
+                Err(Self::error_at_token(&token, "expected expression")
+                    .with_note("variables are not supported yet"))
+            }
             TokenKind::LiteralInteger => self.parse_integer(),
-            TokenKind::OperatorBang => self.parse_unary(ast::UnaryOp::NegateLogical),
-            TokenKind::OperatorMinus => self.parse_unary(ast::UnaryOp::NegateArith),
-            TokenKind::OperatorTilde => self.parse_unary(ast::UnaryOp::Compliment),
-            _ => Err(ParseError::at_token(token, "expected prefix operator")),
-        }
-    }
-
-    /// Parse an infix expression.
-    ///
-    /// The `kind` is the kind of token that the parser is currently looking at. The `left` is the
-    /// portion of the expression that has been parsed so far, e.g. the left half of the binary
-    /// operation.
-    fn parse_infix(&mut self, token: Token, left: ast::Expr) -> ParseResult<ast::Expr> {
-        match token.kind {
-            TokenKind::OperatorMinus => self.parse_binary(ast::BinaryOp::Minus, left),
-            TokenKind::OperatorPlus => self.parse_binary(ast::BinaryOp::Plus, left),
-            TokenKind::OperatorSlash => self.parse_binary(ast::BinaryOp::Divide, left),
-            TokenKind::OperatorStar => self.parse_binary(ast::BinaryOp::Times, left),
-            TokenKind::OperatorPercent => self.parse_binary(ast::BinaryOp::Mod, left),
-            _ => Err(ParseError::at_token(token, "expected infix operator")),
+            TokenKind::KeywordTrue | TokenKind::KeywordFalse => self.parse_boolean(),
+            TokenKind::KeywordNullptr => Err(Self::error_at_token(&token, "expected expression")
+                .with_note("`nullptr` is not supported yet, since there are no pointers")),
+            kind => match prefix_operator(kind) {
+                Some(operator) => self.parse_unary(operator),
+                None => Err(Self::error_at_token(&token, "expected prefix operator")),
+            },
         }
     }
 
     /// Parse the next unary expression.
     ///
     /// This method parses a unary expression with the given operator. The next token is skipped
-    /// (it is assumed to correspond to the operator passed) and an expr3 % (2 + 1ession is parsed. From the
+    /// (it is assumed to correspond to the operator passed) and an expression is parsed. From the
     /// operator and the parsed expression, a new unary expression is constructed.
     fn parse_unary(&mut self, op: ast::UnaryOp) -> ParseResult<ast::Expr> {
         let token = self.advance_expect_anything("expected unary operator")?;
-        let prec = get_prefix_precedence(token.kind);
-        let operand = self.parse_expression(prec)?;
+        let operand = self.parse_operand(Precedence::Prefix.binding_power())?;
 
         Ok(ast::Expr::Unary {
             operator: op,
+            span: token.span.to(operand.span()),
             operand: Box::new(operand),
         })
     }
@@ -255,19 +836,78 @@ impl Parser {
     ///
     /// This method recieves the binary operation that is currently being parsed as well as the
     /// left hand side of the expression. It assumes that the parser is currently pointing to a
-    /// binary operator token which corresponds to the given `op`.
-    fn parse_binary(&mut self, op: ast::BinaryOp, left: ast::Expr) -> ParseResult<ast::Expr> {
-        let token = self.advance_expect_anything("expected binary operator")?;
-        let prec = get_infix_precedence(token.kind);
-        let right = self.parse_expression(prec)?;
+    /// binary operator token which corresponds to the given `op`. The right hand side is parsed
+    /// with the operator's binding power on that side.
+    fn parse_binary(
+        &mut self,
+        op: ast::BinaryOp,
+        left: ast::Expr,
+        right_power: u8,
+    ) -> ParseResult<ast::Expr> {
+        self.advance_expect_anything("expected binary operator")?;
+        let right = self.parse_operand(right_power)?;
 
         Ok(ast::Expr::Binary {
             operator: op,
+            span: left.span().to(right.span()),
             left: Box::new(left),
             right: Box::new(right),
         })
     }
 
+    /// Parse the next `_Generic` selection.
+    ///
+    /// Every association is kept. Which one the selection stands for depends on the type of the
+    /// controlling expression, which is for semantic analysis to work out.
+    fn parse_generic(&mut self) -> ParseResult<ast::Expr> {
+        let keyword = self.advance_expect(TokenKind::KeywordGeneric)?;
+        self.advance_expect(TokenKind::DelimParenLeft)?;
+        let controlling = self.parse_expression()?;
+
+        let mut associations = Vec::new();
+        while self
+            .peek()
+            .is_some_and(|token| token.kind == TokenKind::DelimComma)
+        {
+            self.advance();
+            let (ty, token) = match self.parse_type_name() {
+                Some((Type::Void, token)) => {
+                    return Err(Self::error_at_token(
+                        &token,
+                        "`void` is not a complete object type, so nothing can be associated \
+                         with it",
+                    ));
+                }
+                Some((ty, token)) => (Some(ty), token),
+                None => {
+                    let token = self.advance_expect_anything("expected type name or `default`")?;
+                    if token.kind != TokenKind::KeywordDefault {
+                        return Err(Self::error_at_token(
+                            &token,
+                            "expected type name or `default`",
+                        ));
+                    }
+                    (None, token)
+                }
+            };
+
+            self.advance_expect(TokenKind::DelimColon)?;
+            associations.push(ast::Association {
+                ty,
+                bool_keyword: Self::bool_keyword(&token),
+                type_span: token.span,
+                expr: self.parse_expression()?,
+            });
+        }
+
+        let paren = self.advance_expect(TokenKind::DelimParenRight)?;
+        Ok(ast::Expr::Generic {
+            controlling: Box::new(controlling),
+            associations,
+            span: keyword.span.to(paren.span),
+        })
+    }
+
     /// Parse the next group expression.
     ///
     /// This method parses an opening parenthesis, followed by an expression with reset precedence,
@@ -275,23 +915,63 @@ impl Parser {
     /// expression as a single unit.
     fn parse_group(&mut self) -> ParseResult<ast::Expr> {
         self.advance_expect(TokenKind::DelimParenLeft)?;
-        let expr = self.parse_expression(Precedence::Lowest)?;
+        let expr = self.parse_expression()?;
         self.advance_expect(TokenKind::DelimParenRight)?;
         Ok(expr)
     }
 
     /// Parse the next identifier.
     ///
-    /// This method expects an identifier token.
-    fn parse_identifier(&mut self) -> ParseResult<String> {
+    /// This method expects an identifier token, and returns its name along with where it was.
+    fn parse_identifier(&mut self) -> ParseResult<(String, Span)> {
         let ident = self.advance_expect(TokenKind::LiteralIdentifier)?;
-        Ok(ident.lexeme.clone())
+        Ok((ident.lexeme.to_owned(), ident.span))
     }
 
     /// Parse the next integer literal.
+    ///
+    /// Literals that do not fit in an `int` would get a wider type in C, but there are no wider
+    /// types yet, so they are an error.
     fn parse_integer(&mut self) -> ParseResult<ast::Expr> {
         let integer = self.advance_expect(TokenKind::LiteralInteger)?;
-        let value: i32 = integer.lexeme.parse().unwrap();
-        Ok(ast::Expr::Integer(value))
+        let Ok(value) = integer.lexeme.parse::<i32>() else {
+            return Err(Self::integer_too_large(&integer));
+        };
+
+        Ok(ast::Expr::Integer {
+            value,
+            span: integer.span,
+        })
+    }
+
+    /// Parse the next `true` or `false`.
+    fn parse_boolean(&mut self) -> ParseResult<ast::Expr> {
+        let token = self.advance_expect_anything("expected `true` or `false`")?;
+        Ok(ast::Expr::Boolean {
+            value: token.kind == TokenKind::KeywordTrue,
+            span: token.span,
+        })
+    }
+
+    /// Create the error for an integer literal that does not fit in an `int`.
+    fn integer_too_large(integer: &Token) -> Diagnostic {
+        let mut diagnostic = Diagnostic::error(format!(
+            "integer literal `{}` is too large for `int`",
+            integer.lexeme
+        ))
+        .with_code(codes::INTEGER_TOO_LARGE)
+        .with_span(integer.span)
+        .with_note(format!("the largest `int` is {}", i32::MAX));
+
+        if integer.lexeme.parse::<i64>().is_ok() {
+            diagnostic =
+                diagnostic.with_note("C would make it a `long`, which is not supported yet");
+        }
+
+        if integer.lexeme.parse::<i64>() == Ok(i32::MAX as i64 + 1) {
+            diagnostic = diagnostic.with_help("to write the smallest `int`, use `-2147483647 - 1`");
+        }
+
+        diagnostic
     }
 }
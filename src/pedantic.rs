@@ -0,0 +1,84 @@
+//! Pedantic checks: diagnostics about features that the chosen standard does not have.
+//!
+//! GNU extensions like `__attribute__` and `asm`, and keywords that later standards added with
+//! names reserved in earlier ones, like `_Generic` in C99, are accepted quietly by default, just
+//! like `gcc` does. With `-pedantic`, every use of one is warned about, and with
+//! `-pedantic-errors` it is an error. The checks look at tokens rather than the syntax tree, since
+//! most of these features leave nothing behind once they are parsed.
+//!
+//! Keywords like `inline` and `bool` need no check, because in standards without them the lexer
+//! does not treat them as keywords in the first place.
+
+use crate::diagnostics::{Diagnostic, Level};
+use crate::options::{CompileOptions, Extensions, Standard};
+use crate::token::{Token, TokenKind};
+
+/// Look through the tokens of a program for features that are not part of the standard the options
+/// ask for, returning a diagnostic for each one.
+///
+/// Nothing is returned unless the options ask to be pedantic. The diagnostics are errors with
+/// [`Extensions::Deny`] or `-Werror`, and warnings otherwise.
+///
+/// # Examples
+///
+/// ```
+/// use ecc::options::{CompileOptions, Extensions, Standard};
+/// use ecc::{lexer, pedantic};
+///
+/// let source = r#"int main(void) { asm("nop"); return _Generic(0, int: 1); }"#;
+/// let tokens = lexer::tokenize(source).unwrap();
+///
+/// let options = CompileOptions::new();
+/// assert!(pedantic::check(&tokens, &options).is_empty());
+///
+/// let options = options.with_extensions(Extensions::Warn);
+/// assert_eq!(pedantic::check(&tokens, &options).len(), 1);
+///
+/// let options = options.with_standard(Standard::C99);
+/// assert_eq!(pedantic::check(&tokens, &options).len(), 2);
+/// ```
+pub fn check(tokens: &[Token], options: &CompileOptions) -> Vec<Diagnostic> {
+    if options.extensions == Extensions::Gnu {
+        return Vec::new();
+    }
+
+    let as_errors = options.extensions == Extensions::Deny || options.warnings.as_errors;
+    tokens
+        .iter()
+        .filter_map(|token| {
+            let message = match token.kind {
+                TokenKind::KeywordAsm => "`asm` statements are a GNU extension".to_owned(),
+                TokenKind::KeywordAttribute => "`__attribute__` is a GNU extension".to_owned(),
+                _ => {
+                    let since = introduced_in(token)?;
+                    if options.standard.includes(since) {
+                        return None;
+                    }
+
+                    format!(
+                        "`{}` is not part of {}, only of {} and later",
+                        token.lexeme,
+                        options.standard.name(),
+                        since.name()
+                    )
+                }
+            };
+
+            let mut diagnostic = Diagnostic::warning(message).with_span(token.span);
+            if as_errors {
+                diagnostic.level = Level::Error;
+            }
+            Some(diagnostic)
+        })
+        .collect()
+}
+
+/// The standard that added the keyword a token spells, if it is one of the keywords that are
+/// lexed as keywords before they were added.
+fn introduced_in(token: &Token) -> Option<Standard> {
+    match (token.kind, token.lexeme) {
+        (TokenKind::KeywordBool, "_Bool") => Some(Standard::C99),
+        (TokenKind::KeywordGeneric | TokenKind::KeywordNoreturn, _) => Some(Standard::C11),
+        _ => None,
+    }
+}
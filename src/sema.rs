@@ -0,0 +1,301 @@
+//! Semantic analysis.
+//!
+//! This is where the compiler works out what the names in a program refer to. The result is a
+//! [`SymbolTable`], which the rest of the compiler and outside tools like editors and linters can
+//! query for what a name means.
+
+use crate::ast::{self, Statement, Visitor, visit};
+use crate::diagnostics::{Diagnostic, codes};
+use crate::span::Span;
+use crate::types::Type;
+
+/// Analyze a program, collecting every declaration in it.
+///
+/// # Examples
+///
+/// ```
+/// use ecc::sema::{self, SymbolKind};
+/// use ecc::{lexer, parser};
+///
+/// let tokens = lexer::tokenize("int main(void) { return 0; }").unwrap();
+/// let program = parser::parse_token_stream(tokens).unwrap();
+/// let symbols = sema::analyze(&program);
+///
+/// let main = symbols.lookup("main").unwrap();
+/// assert_eq!(main.kind, SymbolKind::Function);
+/// assert_eq!(main.ty.to_string(), "int (void)");
+/// assert_eq!(symbols.symbol_at(5).unwrap().name, "main");
+/// ```
+pub fn analyze(program: &ast::Program) -> SymbolTable {
+    let mut symbols = SymbolTable::default();
+    symbols.declare_function(&program.function);
+    symbols
+}
+
+/// Check that a program makes sense, returning an error for each place it does not.
+///
+/// For now, this means every `return` statement has a value exactly when its function does not
+/// return `void`, and every `_Generic` selection has exactly one association it can stand for.
+/// There are no calls yet, so a `void` value cannot turn up in an expression any other way.
+///
+/// # Examples
+///
+/// ```
+/// use ecc::sema;
+/// use ecc::{lexer, parser};
+///
+/// let tokens = lexer::tokenize("void f(void) { return 1; }").unwrap();
+/// let program = parser::parse_token_stream(tokens).unwrap();
+/// let errors = sema::check(&program);
+///
+/// assert_eq!(errors.len(), 1);
+/// assert_eq!(errors[0].message, "`f` returns `void`, so it cannot return a value");
+/// ```
+pub fn check(program: &ast::Program) -> Vec<Diagnostic> {
+    let function = &program.function;
+    let returns_void = function.return_type == Type::Void;
+
+    let mut errors: Vec<_> = function
+        .body
+        .iter()
+        .filter_map(|statement| match statement {
+            Statement::Return(expr) => Some(expr),
+            Statement::Asm { .. } => None,
+        })
+        .filter_map(|expr| match expr {
+            Some(expr) if returns_void => Some(
+                Diagnostic::error(format!(
+                    "`{}` returns `void`, so it cannot return a value",
+                    function.name
+                ))
+                .with_code(codes::RETURN_TYPE_MISMATCH)
+                .with_span(expr.span()),
+            ),
+            None if !returns_void => Some(
+                Diagnostic::error(format!(
+                    "`{}` returns `{}`, so it must return a value",
+                    function.name, function.return_type
+                ))
+                .with_code(codes::RETURN_TYPE_MISMATCH)
+                .with_span(function.name_span),
+            ),
+            _ => None,
+        })
+        .collect();
+
+    let mut selections = SelectionChecker(Vec::new());
+    selections.visit_program(program);
+    errors.extend(selections.0);
+    errors
+}
+
+/// Finds `_Generic` selections that have more than one association for a type, or none for the
+/// type of their controlling expression.
+struct SelectionChecker(Vec<Diagnostic>);
+
+impl Visitor for SelectionChecker {
+    fn visit_expr(&mut self, expr: &ast::Expr) {
+        if let ast::Expr::Generic {
+            controlling,
+            associations,
+            span,
+        } = expr
+        {
+            for (i, association) in associations.iter().enumerate() {
+                if associations[..i]
+                    .iter()
+                    .any(|earlier| earlier.ty == association.ty)
+                {
+                    let name = association
+                        .ty
+                        .as_ref()
+                        .map_or("default".to_owned(), Type::to_string);
+                    self.0.push(
+                        Diagnostic::error(format!("more than one association for `{name}`"))
+                            .with_code(codes::DUPLICATE_GENERIC_ASSOCIATION)
+                            .with_span(association.type_span),
+                    );
+                }
+            }
+
+            if select(controlling, associations).is_none() {
+                self.0.push(
+                    Diagnostic::error(format!(
+                        "no association matches the controlling expression's type, `{}`",
+                        type_of(controlling)
+                    ))
+                    .with_code(codes::NO_GENERIC_ASSOCIATION)
+                    .with_span(*span),
+                );
+            }
+        }
+
+        visit::walk_expr(self, expr);
+    }
+}
+
+/// Work out the type of an expression.
+///
+/// Every operator converts its operands to `int` before it does anything, and gives an `int`
+/// back, so for now every other expression is an `int`. That includes `true` and `false`, which
+/// the parser has already turned into integer literals. A `_Generic` selection has the type of
+/// the association it stands for.
+///
+/// # Examples
+///
+/// ```
+/// use ecc::sema;
+/// use ecc::types::Type;
+/// use ecc::{lexer, parser};
+///
+/// let tokens = lexer::tokenize("-(1 + 2)").unwrap();
+/// let expr = parser::parse_expression(tokens).unwrap();
+///
+/// assert_eq!(sema::type_of(&expr), Type::Int);
+/// ```
+pub fn type_of(expr: &ast::Expr) -> Type {
+    match expr {
+        ast::Expr::Integer { .. } | ast::Expr::Unary { .. } | ast::Expr::Binary { .. } => Type::Int,
+        ast::Expr::Boolean { .. } => Type::Bool,
+        ast::Expr::Generic {
+            controlling,
+            associations,
+            ..
+        } => {
+            // A selection without a match is an error that `check` reports, and it has to have
+            // some type until then.
+            select(controlling, associations).map_or(Type::Int, type_of)
+        }
+    }
+}
+
+/// Choose the expression that a `_Generic` selection stands for: the one associated with the
+/// type of the controlling expression, or with `default` if there is none.
+///
+/// The controlling expression is never evaluated. Only its type matters.
+///
+/// # Examples
+///
+/// ```
+/// use ecc::ast::Expr;
+/// use ecc::{interp, lexer, parser, sema};
+///
+/// let tokens = lexer::tokenize("_Generic(1 / 0, bool: 3, int: 4, default: 5)").unwrap();
+/// let Expr::Generic { controlling, associations, .. } = parser::parse_expression(tokens).unwrap()
+/// else {
+///     unreachable!()
+/// };
+///
+/// let selected = sema::select(&controlling, &associations).unwrap();
+/// assert_eq!(interp::eval_expr(selected).unwrap(), 4);
+/// ```
+pub fn select<'a>(
+    controlling: &ast::Expr,
+    associations: &'a [ast::Association],
+) -> Option<&'a ast::Expr> {
+    let ty = type_of(controlling);
+    let matching = associations
+        .iter()
+        .find(|association| association.ty.as_ref() == Some(&ty));
+    let default = || {
+        associations
+            .iter()
+            .find(|association| association.ty.is_none())
+    };
+
+    matching
+        .or_else(default)
+        .map(|association| &association.expr)
+}
+
+/// Everything declared in a program.
+#[derive(Clone, Default, Debug)]
+pub struct SymbolTable {
+    symbols: Vec<Symbol>,
+}
+
+impl SymbolTable {
+    /// Find the symbol with the given name.
+    pub fn lookup(&self, name: &str) -> Option<&Symbol> {
+        self.symbols.iter().find(|symbol| symbol.name == name)
+    }
+
+    /// Find the symbol whose name is at the given byte offset into the source.
+    ///
+    /// An offset just past the end of a name still counts as being on it, since that is where an
+    /// editor's cursor sits after typing the name.
+    pub fn symbol_at(&self, offset: usize) -> Option<&Symbol> {
+        self.symbols
+            .iter()
+            .find(|symbol| symbol.span.start <= offset && offset <= symbol.span.end)
+    }
+
+    /// Iterate over every symbol, in the order they were declared.
+    pub fn iter(&self) -> impl Iterator<Item = &Symbol> {
+        self.symbols.iter()
+    }
+
+    /// The number of symbols in the table.
+    pub fn len(&self) -> usize {
+        self.symbols.len()
+    }
+
+    /// Return true if nothing has been declared.
+    pub fn is_empty(&self) -> bool {
+        self.symbols.is_empty()
+    }
+
+    fn declare_function(&mut self, function: &ast::Function) {
+        self.symbols.push(Symbol {
+            name: function.name.clone(),
+            kind: SymbolKind::Function,
+            ty: Type::Function {
+                return_type: Box::new(function.return_type.clone()),
+                params: Vec::new(),
+            },
+            storage: if function.is_static {
+                Storage::Static
+            } else {
+                Storage::Extern
+            },
+            span: function.name_span,
+        });
+    }
+}
+
+/// A declared name.
+#[derive(Clone, PartialEq, Eq, Debug)]
+pub struct Symbol {
+    /// The name itself.
+    pub name: String,
+
+    /// What sort of thing was declared.
+    pub kind: SymbolKind,
+
+    /// The declared type.
+    pub ty: Type,
+
+    /// The storage class, which decides where the symbol lives and who can see it.
+    pub storage: Storage,
+
+    /// Where the name appears in its declaration.
+    pub span: Span,
+}
+
+/// What sort of thing a symbol is.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum SymbolKind {
+    /// A function.
+    Function,
+}
+
+/// The storage class of a symbol.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum Storage {
+    /// Visible to other translation units. This is what functions get when no storage class is
+    /// written.
+    Extern,
+
+    /// Only visible in the translation unit it is declared in.
+    Static,
+}
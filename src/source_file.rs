@@ -0,0 +1,104 @@
+//! Reading source files from disk.
+//!
+//! Small files are read into a string, but big ones, like generated tables, are mapped into memory
+//! instead, so that the lexer works on the pages of the file itself rather than on a copy of them.
+//! Tokens borrow their text from the source either way, so nothing is copied after that.
+//!
+//! A mapped file is only as stable as the file on disk, which is why opening one is `unsafe`.
+
+use std::fs::File;
+use std::io::{self, Read};
+use std::ops::Deref;
+use std::path::Path;
+
+use memmap2::Mmap;
+
+/// How big a file has to be, in bytes, before it is mapped instead of read.
+///
+/// Mapping a file costs a few system calls and page faults of its own, which reading a small file
+/// into a buffer is faster than.
+const MAP_THRESHOLD: u64 = 1 << 20;
+
+/// The text of a source file, which dereferences to a [`str`].
+///
+/// # Examples
+///
+/// ```
+/// use ecc::source_file::SourceFile;
+///
+/// let path = std::env::temp_dir().join("source_file_example.c");
+/// std::fs::write(&path, "int main(void) { return 0; }").unwrap();
+///
+/// // SAFETY: Nothing else knows about the file, so nothing changes it while it is open.
+/// let source = unsafe { SourceFile::open(&path) }.unwrap();
+/// assert!(source.starts_with("int main"));
+/// assert!(!source.is_mapped());
+/// # std::fs::remove_file(path).unwrap();
+/// ```
+pub struct SourceFile {
+    contents: Contents,
+}
+
+enum Contents {
+    Read(String),
+
+    /// A map whose bytes have been checked to be UTF-8.
+    Mapped(Mmap),
+}
+
+impl SourceFile {
+    /// Open the file at the given path, mapping it into memory if it is big.
+    ///
+    /// The file has to be UTF-8, and an error with [`io::ErrorKind::InvalidData`] is returned if
+    /// it is not.
+    ///
+    /// # Safety
+    ///
+    /// The file must not be written to or truncated, by this process or any other, until the
+    /// returned `SourceFile` is dropped. A mapped file shows whatever is on disk, so a change
+    /// after it was checked can put bytes that are not UTF-8 behind a `&str`, and a truncation
+    /// makes reading the pages past the new end raise `SIGBUS`.
+    pub unsafe fn open(path: impl AsRef<Path>) -> io::Result<Self> {
+        let mut file = File::open(path)?;
+        let size = file.metadata()?.len();
+
+        if size < MAP_THRESHOLD {
+            let mut text = String::with_capacity(size as usize);
+            file.read_to_string(&mut text)?;
+            return Ok(Self {
+                contents: Contents::Read(text),
+            });
+        }
+
+        // SAFETY: The caller promises that the file does not change while it is mapped.
+        let map = unsafe { Mmap::map(&file)? };
+        std::str::from_utf8(&map).map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))?;
+
+        Ok(Self {
+            contents: Contents::Mapped(map),
+        })
+    }
+
+    /// Whether the file was mapped into memory instead of being read.
+    pub fn is_mapped(&self) -> bool {
+        matches!(self.contents, Contents::Mapped(_))
+    }
+
+    /// The text of the file.
+    pub fn as_str(&self) -> &str {
+        match &self.contents {
+            Contents::Read(text) => text,
+            // SAFETY: The bytes were checked to be UTF-8 when the file was opened, and the caller of
+            // `open` promised that they have not changed since.
+            Contents::Mapped(map) => unsafe { std::str::from_utf8_unchecked(map) },
+        }
+    }
+}
+
+impl Deref for SourceFile {
+    type Target = str;
+
+    fn deref(&self) -> &str {
+        self.as_str()
+    }
+}
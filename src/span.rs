@@ -0,0 +1,36 @@
+/// A region of source code.
+///
+/// Spans are byte offsets into the source string, from `start` up to (but not including) `end`.
+/// Line and column information is recovered from the source only when a span is rendered, which
+/// keeps spans small and cheap to copy around.
+#[derive(Clone, Copy, PartialEq, Eq, Default, Debug)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Span {
+    /// The offset of the first byte in the span.
+    pub start: usize,
+
+    /// The offset one past the last byte in the span.
+    pub end: usize,
+}
+
+impl Span {
+    /// Create a span covering the bytes from `start` up to (but not including) `end`.
+    pub fn new(start: usize, end: usize) -> Self {
+        Self { start, end }
+    }
+
+    /// The number of bytes covered by the span.
+    pub fn len(&self) -> usize {
+        self.end - self.start
+    }
+
+    /// Return true if the span does not cover any bytes.
+    pub fn is_empty(&self) -> bool {
+        self.start == self.end
+    }
+
+    /// Create the smallest span that covers both this span and `other`.
+    pub fn to(self, other: Span) -> Span {
+        Span::new(self.start.min(other.start), self.end.max(other.end))
+    }
+}
@@ -1,23 +1,44 @@
+use crate::options::Standard;
+use crate::span::Span;
+
 /// The kind of a token.
 ///
 /// This enum represents the kind associated with a token. While the lexer separates the source
 /// code into tokens, it also assigns a kind to the lexeme so that the parser can check at a glance
 /// what kind of token it is looking at.
 #[allow(missing_docs)]
-#[derive(Clone, Copy, PartialEq, Eq,Debug)]
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub enum TokenKind {
     DelimBraceLeft,
     DelimBraceRight,
+    DelimBracketLeft,
+    DelimBracketRight,
+    DelimColon,
+    DelimComma,
     DelimParenLeft,
     DelimParenRight,
     DelimSemicolon,
 
+    KeywordAsm,
+    KeywordAttribute,
+    KeywordBool,
+    KeywordDefault,
+    KeywordFalse,
+    KeywordGeneric,
+    KeywordInline,
     KeywordInt,
+    KeywordNoreturn,
+    KeywordNullptr,
     KeywordReturn,
+    KeywordStatic,
+    KeywordTrue,
     KeywordVoid,
+    KeywordVolatile,
 
     LiteralIdentifier,
     LiteralInteger,
+    LiteralString,
 
     OperatorBang,
     OperatorMinus,
@@ -27,7 +48,9 @@ pub enum TokenKind {
     OperatorStar,
     OperatorTilde,
 
-    SpecialError,
+    SpecialComment,
+    SpecialEndOfFile,
+    SpecialLineDirective,
 }
 
 impl std::fmt::Display for TokenKind {
@@ -35,16 +58,33 @@ impl std::fmt::Display for TokenKind {
         match self {
             Self::DelimBraceLeft => write!(f, "'{{'"),
             Self::DelimBraceRight => write!(f, "'}}'"),
+            Self::DelimBracketLeft => write!(f, "'['"),
+            Self::DelimBracketRight => write!(f, "']'"),
+            Self::DelimColon => write!(f, "':'"),
+            Self::DelimComma => write!(f, "','"),
             Self::DelimParenLeft => write!(f, "'('"),
             Self::DelimParenRight => write!(f, "')'"),
             Self::DelimSemicolon => write!(f, "';'"),
 
+            Self::KeywordAsm => write!(f, "'asm'"),
+            Self::KeywordAttribute => write!(f, "'__attribute__'"),
+            Self::KeywordBool => write!(f, "'bool'"),
+            Self::KeywordDefault => write!(f, "'default'"),
+            Self::KeywordFalse => write!(f, "'false'"),
+            Self::KeywordGeneric => write!(f, "'_Generic'"),
+            Self::KeywordInline => write!(f, "'inline'"),
             Self::KeywordInt => write!(f, "'int'"),
+            Self::KeywordNoreturn => write!(f, "'_Noreturn'"),
+            Self::KeywordNullptr => write!(f, "'nullptr'"),
             Self::KeywordReturn => write!(f, "'return'"),
+            Self::KeywordStatic => write!(f, "'static'"),
+            Self::KeywordTrue => write!(f, "'true'"),
             Self::KeywordVoid => write!(f, "'void'"),
+            Self::KeywordVolatile => write!(f, "'volatile'"),
 
             Self::LiteralIdentifier => write!(f, "identifier"),
             Self::LiteralInteger => write!(f, "integer literal"),
+            Self::LiteralString => write!(f, "string literal"),
 
             Self::OperatorBang => write!(f, "'!'"),
             Self::OperatorMinus => write!(f, "'-'"),
@@ -54,20 +94,171 @@ impl std::fmt::Display for TokenKind {
             Self::OperatorStar => write!(f, "'*'"),
             Self::OperatorTilde => write!(f, "'~'"),
 
-            Self::SpecialError => write!(f, "error token"),
+            Self::SpecialComment => write!(f, "comment"),
+            Self::SpecialEndOfFile => write!(f, "end of file"),
+            Self::SpecialLineDirective => write!(f, "line directive"),
         }
     }
 }
 
+impl TokenKind {
+    /// Get the spelling of tokens of this kind, if they are always spelled the same way.
+    ///
+    /// Keywords, delimiters, and operators have a fixed spelling. Literals do not, so [`None`] is
+    /// returned for them.
+    pub fn spelling(&self) -> Option<&'static str> {
+        let spelling = match self {
+            Self::DelimBraceLeft => "{",
+            Self::DelimBraceRight => "}",
+            Self::DelimBracketLeft => "[",
+            Self::DelimBracketRight => "]",
+            Self::DelimColon => ":",
+            Self::DelimComma => ",",
+            Self::DelimParenLeft => "(",
+            Self::DelimParenRight => ")",
+            Self::DelimSemicolon => ";",
+
+            Self::KeywordAsm => "asm",
+            Self::KeywordAttribute => "__attribute__",
+            Self::KeywordBool => "bool",
+            Self::KeywordDefault => "default",
+            Self::KeywordFalse => "false",
+            Self::KeywordGeneric => "_Generic",
+            Self::KeywordInline => "inline",
+            Self::KeywordInt => "int",
+            Self::KeywordNoreturn => "_Noreturn",
+            Self::KeywordNullptr => "nullptr",
+            Self::KeywordReturn => "return",
+            Self::KeywordStatic => "static",
+            Self::KeywordTrue => "true",
+            Self::KeywordVoid => "void",
+            Self::KeywordVolatile => "volatile",
+
+            Self::LiteralIdentifier | Self::LiteralInteger | Self::LiteralString => return None,
+            Self::SpecialComment | Self::SpecialEndOfFile | Self::SpecialLineDirective => {
+                return None;
+            }
+
+            Self::OperatorBang => "!",
+            Self::OperatorMinus => "-",
+            Self::OperatorPercent => "%",
+            Self::OperatorPlus => "+",
+            Self::OperatorSlash => "/",
+            Self::OperatorStar => "*",
+            Self::OperatorTilde => "~",
+        };
+
+        Some(spelling)
+    }
+
+    /// Return true if this kind of token is a keyword.
+    pub fn is_keyword(&self) -> bool {
+        matches!(
+            self,
+            Self::KeywordAsm
+                | Self::KeywordAttribute
+                | Self::KeywordBool
+                | Self::KeywordDefault
+                | Self::KeywordFalse
+                | Self::KeywordGeneric
+                | Self::KeywordInline
+                | Self::KeywordInt
+                | Self::KeywordNoreturn
+                | Self::KeywordNullptr
+                | Self::KeywordReturn
+                | Self::KeywordStatic
+                | Self::KeywordTrue
+                | Self::KeywordVoid
+                | Self::KeywordVolatile
+        )
+    }
+
+    /// Return true if this kind of token is an operator.
+    pub fn is_operator(&self) -> bool {
+        matches!(
+            self,
+            Self::OperatorBang
+                | Self::OperatorMinus
+                | Self::OperatorPercent
+                | Self::OperatorPlus
+                | Self::OperatorSlash
+                | Self::OperatorStar
+                | Self::OperatorTilde
+        )
+    }
+
+    /// Return true if this kind of token is a delimiter.
+    pub fn is_delimiter(&self) -> bool {
+        matches!(
+            self,
+            Self::DelimBraceLeft
+                | Self::DelimBraceRight
+                | Self::DelimBracketLeft
+                | Self::DelimBracketRight
+                | Self::DelimColon
+                | Self::DelimComma
+                | Self::DelimParenLeft
+                | Self::DelimParenRight
+                | Self::DelimSemicolon
+        )
+    }
+
+    /// Return true if this kind of token only shows up when comments are kept, and means nothing
+    /// to the parser.
+    pub fn is_trivia(&self) -> bool {
+        matches!(self, Self::SpecialComment | Self::SpecialLineDirective)
+    }
+}
+
+/// The first dialect of C in which the given lexeme is a keyword.
+///
+/// In dialects that do not [include](Standard::includes) it, it is an ordinary identifier.
+/// Keywords spelled with a leading underscore and a capital letter, like `_Generic`, are reserved
+/// for the implementation in every version, so they are always keywords. So are the GNU ones with
+/// two leading underscores, but `asm` is only a keyword in the GNU dialects.
+///
+/// # Examples
+///
+/// ```
+/// use ecc::options::Standard;
+/// use ecc::token;
+///
+/// assert_eq!(token::keyword_standard("inline"), Standard::C99);
+/// assert_eq!(token::keyword_standard("bool"), Standard::C23);
+/// assert_eq!(token::keyword_standard("_Bool"), Standard::C89);
+/// assert_eq!(token::keyword_standard("asm"), Standard::C89.with_gnu());
+/// assert_eq!(token::keyword_standard("__asm__"), Standard::C89);
+/// ```
+pub fn keyword_standard(lexeme: &str) -> Standard {
+    match lexeme {
+        "asm" => Standard::C89.with_gnu(),
+        "inline" => Standard::C99,
+        "bool" | "true" | "false" | "nullptr" => Standard::C23,
+        _ => Standard::C89,
+    }
+}
+
 /// Check if the given lexeme is a keyword.
 ///
 /// If the lexeme is a keyword, the returned token type will indicate which one it is. Otherwise,
 /// the returned token type is [`TokenKind::LiteralIdentifier`].
 pub fn check_keyword(lexeme: &str) -> TokenKind {
     match lexeme {
+        "asm" | "__asm__" => TokenKind::KeywordAsm,
+        "__attribute__" => TokenKind::KeywordAttribute,
+        "bool" | "_Bool" => TokenKind::KeywordBool,
+        "default" => TokenKind::KeywordDefault,
+        "false" => TokenKind::KeywordFalse,
+        "_Generic" => TokenKind::KeywordGeneric,
+        "inline" => TokenKind::KeywordInline,
         "int" => TokenKind::KeywordInt,
+        "_Noreturn" => TokenKind::KeywordNoreturn,
+        "nullptr" => TokenKind::KeywordNullptr,
         "return" => TokenKind::KeywordReturn,
+        "static" => TokenKind::KeywordStatic,
+        "true" => TokenKind::KeywordTrue,
         "void" => TokenKind::KeywordVoid,
+        "volatile" | "__volatile__" => TokenKind::KeywordVolatile,
         _ => TokenKind::LiteralIdentifier,
     }
 }
@@ -77,13 +268,20 @@ pub fn check_keyword(lexeme: &str) -> TokenKind {
 /// Tokens are the smallest unit of lexical information. They are analogous to words in spoken
 /// language. A token contains its kind, the corresponding substring of the source code (the
 /// lexeme), and the line and column info.
-#[derive(Clone, Debug)]
-pub struct Token {
+///
+/// The lexeme borrows from the source instead of being copied out of it, so tokens are cheap to
+/// make and to pass around, but cannot outlive the source they came from.
+#[derive(Clone, Copy, Debug)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Token<'a> {
     /// The kind of token this is. This information is helpful for the parser.
     pub kind: TokenKind,
 
     /// The corresponding string in the source code from which this token came.
-    pub lexeme: String,
+    pub lexeme: &'a str,
+
+    /// Where in the source code this token came from.
+    pub span: Span,
 
     /// The line of the source code that this token was on.
     pub line: usize,
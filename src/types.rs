@@ -0,0 +1,52 @@
+use std::fmt;
+
+/// The type of a value or declaration.
+#[derive(Clone, PartialEq, Eq, Debug)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum Type {
+    /// The `int` type.
+    Int,
+
+    /// The `bool` type, whose only values are 0 and 1. Converting any other value to it gives 1.
+    Bool,
+
+    /// The `void` type, which has no values. Functions that return nothing return it.
+    Void,
+
+    /// The type of a function.
+    Function {
+        /// What the function returns.
+        return_type: Box<Type>,
+
+        /// The types of the function's parameters, in order. A function declared with a `void`
+        /// parameter list has no parameters.
+        params: Vec<Type>,
+    },
+}
+
+impl fmt::Display for Type {
+    /// Write the type the way C spells it, e.g. `int (void)` for a function type.
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Int => write!(f, "int"),
+            Self::Bool => write!(f, "bool"),
+            Self::Void => write!(f, "void"),
+            Self::Function {
+                return_type,
+                params,
+            } => {
+                write!(f, "{return_type} (")?;
+                if params.is_empty() {
+                    write!(f, "void")?;
+                }
+                for (i, param) in params.iter().enumerate() {
+                    if i > 0 {
+                        write!(f, ", ")?;
+                    }
+                    write!(f, "{param}")?;
+                }
+                write!(f, ")")
+            }
+        }
+    }
+}
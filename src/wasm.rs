@@ -0,0 +1,25 @@
+//! The entry point for running the compiler in a browser.
+//!
+//! Built with the `wasm` feature, usually for `wasm32-unknown-unknown` without the `driver`
+//! feature, and wrapped with `wasm-bindgen` so that a web playground can show the generated
+//! assembly as the code is typed.
+
+use wasm_bindgen::prelude::*;
+
+use crate::diagnostics::{self, Diagnostic};
+use crate::options::CompileOptions;
+
+/// Compile C source code to assembly.
+///
+/// If the source has errors, the rendered diagnostics are thrown as a string instead.
+#[wasm_bindgen]
+pub fn compile_to_asm(source: &str) -> Result<String, JsValue> {
+    let mut emitted: Vec<Diagnostic> = Vec::new();
+    crate::compile_source(source, &CompileOptions::new(), &mut emitted).map_err(|_| {
+        let rendered: String = emitted
+            .iter()
+            .map(|d| diagnostics::render(d, "<source>", source))
+            .collect();
+        JsValue::from_str(&rendered)
+    })
+}
@@ -0,0 +1,265 @@
+//! Differential testing against the system C compiler.
+//!
+//! Every program in `tests/programs` is compiled with both `ecc` and `gcc`, and both executables
+//! are run. Anything that the two disagree on (the exit code or the output) is a bug in `ecc`,
+//! since `gcc` is assumed to get it right. The programs are also run with `ecc --interpret`, which
+//! has to agree on the exit code too.
+
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::process::{Command, Output};
+
+/// Where the test programs live.
+fn programs_dir() -> PathBuf {
+    Path::new(env!("CARGO_MANIFEST_DIR")).join("tests/programs")
+}
+
+/// Run a command to completion, panicking with its output if it fails.
+fn run_successfully(command: &mut Command) {
+    let output = command.output().expect("could not run command");
+    assert!(
+        output.status.success(),
+        "{command:?} failed:\n{}",
+        String::from_utf8_lossy(&output.stderr)
+    );
+}
+
+/// Compile a program with `ecc` and the given flags, returning the path to the executable.
+///
+/// `ecc` puts the executable next to the source, so the source is copied into its own directory
+/// first.
+fn compile_with_ecc(source: &Path, work_dir: &Path, flags: &[&str]) -> PathBuf {
+    let copy = work_dir.join("ecc").join(source.file_name().unwrap());
+    fs::create_dir_all(copy.parent().unwrap()).unwrap();
+    fs::copy(source, &copy).unwrap();
+
+    run_successfully(
+        Command::new(env!("CARGO_BIN_EXE_ecc"))
+            .args(flags)
+            .arg(&copy),
+    );
+    copy.with_extension("")
+}
+
+/// Compile a program with `gcc`, returning the path to the executable.
+fn compile_with_gcc(source: &Path, work_dir: &Path) -> PathBuf {
+    let executable = work_dir.join("gcc").join(source.file_stem().unwrap());
+    fs::create_dir_all(executable.parent().unwrap()).unwrap();
+
+    run_successfully(Command::new("gcc").arg("-o").arg(&executable).arg(source));
+    executable
+}
+
+/// Run a program with `ecc`'s interpreter.
+fn interpret_with_ecc(source: &Path) -> Output {
+    Command::new(env!("CARGO_BIN_EXE_ecc"))
+        .arg("--interpret")
+        .arg(source)
+        .output()
+        .expect("could not run ecc")
+}
+
+/// Run a compiled program in its own directory, so that anything it writes, like `gmon.out`,
+/// ends up there.
+fn run(executable: &Path) -> Output {
+    Command::new(executable)
+        .current_dir(executable.parent().unwrap())
+        .output()
+        .expect("could not run compiled program")
+}
+
+/// Every test program, in order.
+fn sources() -> Vec<PathBuf> {
+    let mut sources: Vec<_> = fs::read_dir(programs_dir())
+        .unwrap()
+        .map(|entry| entry.unwrap().path())
+        .filter(|path| path.extension().is_some_and(|extension| extension == "c"))
+        .collect();
+    sources.sort();
+    sources
+}
+
+/// Compile every test program with both compilers, `ecc` getting the given flags, and check that
+/// the executables behave the same.
+fn check_compiled_programs(work_dir: &str, flags: &[&str]) {
+    let work_dir = Path::new(env!("CARGO_TARGET_TMPDIR")).join(work_dir);
+    let sources = sources();
+
+    let mut mismatches = Vec::new();
+    for source in &sources {
+        let expected = run(&compile_with_gcc(source, &work_dir));
+        let actual = run(&compile_with_ecc(source, &work_dir, flags));
+
+        if expected.status.code() != actual.status.code() || expected.stdout != actual.stdout {
+            mismatches.push(format!(
+                "{}: gcc exited with {:?} and printed {:?}, ecc exited with {:?} and printed {:?}",
+                source.display(),
+                expected.status.code(),
+                String::from_utf8_lossy(&expected.stdout),
+                actual.status.code(),
+                String::from_utf8_lossy(&actual.stdout),
+            ));
+        }
+    }
+
+    assert!(!sources.is_empty(), "no test programs were found");
+    assert!(mismatches.is_empty(), "{}", mismatches.join("\n"));
+}
+
+#[test]
+fn programs_behave_like_gcc() {
+    check_compiled_programs("differential", &[]);
+}
+
+#[test]
+fn optimized_programs_behave_like_gcc() {
+    check_compiled_programs("differential-optimized", &["-O2"]);
+}
+
+/// None of the programs do anything undefined, so checking for it must not change anything.
+#[test]
+fn sanitized_programs_behave_like_gcc() {
+    check_compiled_programs("differential-sanitized", &["-fsanitize=undefined"]);
+    check_compiled_programs(
+        "differential-sanitized-optimized",
+        &["-O2", "-fsanitize=undefined"],
+    );
+}
+
+/// The programs are written in C99, so an older standard does not change what they mean.
+#[test]
+fn c99_programs_behave_like_gcc() {
+    check_compiled_programs("differential-c99", &["--std=c99"]);
+}
+
+/// The programs are all standard C, so being pedantic about extensions finds nothing to reject.
+#[test]
+fn pedantic_programs_behave_like_gcc() {
+    check_compiled_programs("differential-pedantic", &["-pedantic-errors"]);
+}
+
+/// Exiting through the program's own `_start` gives the same status as returning to the C library.
+#[test]
+fn freestanding_programs_behave_like_gcc() {
+    check_compiled_programs("differential-freestanding", &["-nostdlib"]);
+}
+
+/// Calling `mcount` from every function must not change what the programs do, and they must write
+/// a profile when they exit.
+#[test]
+fn profiled_programs_behave_like_gcc() {
+    check_compiled_programs("differential-profiled", &["-pg"]);
+
+    let work_dir = Path::new(env!("CARGO_TARGET_TMPDIR")).join("differential-profiled");
+    assert!(work_dir.join("ecc/gmon.out").exists());
+}
+
+/// An `asm` statement after a `return` can never run, so it must not leave the function without an
+/// end. It is a GNU extension, so it cannot go with the other programs, which are checked with
+/// `-pedantic-errors`.
+#[test]
+fn asm_after_return_behaves_like_gcc() {
+    let work_dir = Path::new(env!("CARGO_TARGET_TMPDIR")).join("differential-asm-after-return");
+    fs::create_dir_all(&work_dir).unwrap();
+    let source = work_dir.join("asm_after_return.c");
+    fs::write(&source, "int main(void) { return 3; asm(\"nop\"); }\n").unwrap();
+
+    let expected = run(&compile_with_gcc(&source, &work_dir));
+    for flags in [["-O0"], ["-O1"]] {
+        let actual = run(&compile_with_ecc(&source, &work_dir, &flags));
+        assert_eq!(expected.status.code(), actual.status.code(), "{flags:?}");
+    }
+}
+
+/// With `--out-dir`, every file that is written goes in the directory, which is created first if
+/// it does not exist yet.
+#[test]
+fn out_dir_programs_behave_like_gcc() {
+    let work_dir = Path::new(env!("CARGO_TARGET_TMPDIR")).join("differential-out-dir");
+    let _ = fs::remove_dir_all(&work_dir);
+
+    for source in sources() {
+        let name = source.file_stem().unwrap();
+        let out_dir = work_dir.join("ecc").join(name);
+        run_successfully(
+            Command::new(env!("CARGO_BIN_EXE_ecc"))
+                .args(["--stack-usage", "-MD", "--out-dir"])
+                .arg(&out_dir)
+                .arg(&source),
+        );
+
+        let expected = run(&compile_with_gcc(&source, &work_dir));
+        let actual = run(&out_dir.join(name));
+        assert_eq!(
+            expected.status.code(),
+            actual.status.code(),
+            "{}",
+            source.display()
+        );
+        assert!(out_dir.join(name).with_extension("su").exists());
+        assert!(out_dir.join(name).with_extension("d").exists());
+    }
+}
+
+/// Formatting a program must not change what it does, and must keep its line directives.
+#[test]
+fn formatted_programs_behave_like_gcc() {
+    let work_dir = Path::new(env!("CARGO_TARGET_TMPDIR")).join("differential-formatted");
+    let formatted_dir = work_dir.join("formatted");
+    fs::create_dir_all(&formatted_dir).unwrap();
+
+    for source in sources() {
+        let formatted = formatted_dir.join(source.file_name().unwrap());
+        fs::copy(&source, &formatted).unwrap();
+        run_successfully(Command::new(env!("CARGO_BIN_EXE_ecc-fmt")).arg(&formatted));
+
+        let directives = |path: &Path| -> Vec<String> {
+            fs::read_to_string(path)
+                .unwrap()
+                .lines()
+                .filter(|line| line.trim_start().starts_with('#'))
+                .map(str::to_string)
+                .collect()
+        };
+        assert_eq!(
+            directives(&source),
+            directives(&formatted),
+            "{}",
+            source.display()
+        );
+
+        let expected = run(&compile_with_gcc(&source, &work_dir));
+        let actual = run(&compile_with_ecc(&formatted, &work_dir, &[]));
+        assert_eq!(
+            expected.status.code(),
+            actual.status.code(),
+            "{}",
+            source.display()
+        );
+    }
+}
+
+#[test]
+fn interpreted_programs_behave_like_gcc() {
+    let work_dir = Path::new(env!("CARGO_TARGET_TMPDIR")).join("differential-interpreted");
+    let sources = sources();
+
+    let mut mismatches = Vec::new();
+    for source in &sources {
+        let expected = run(&compile_with_gcc(source, &work_dir));
+        let actual = interpret_with_ecc(source);
+
+        if expected.status.code() != actual.status.code() {
+            mismatches.push(format!(
+                "{}: gcc's program exited with {:?}, the interpreter exited with {:?}: {}",
+                source.display(),
+                expected.status.code(),
+                actual.status.code(),
+                String::from_utf8_lossy(&actual.stderr),
+            ));
+        }
+    }
+
+    assert!(!sources.is_empty(), "no test programs were found");
+    assert!(mismatches.is_empty(), "{}", mismatches.join("\n"));
+}
@@ -0,0 +1,3 @@
+int main(void) {
+    return _Generic(1 / 0, default: 3, int: 40) + _Generic(0, default: 2);
+}
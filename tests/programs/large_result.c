@@ -0,0 +1,4 @@
+// Exit codes are taken modulo 256, so this checks the low byte.
+int main(void) {
+    return 1000 * 3 + ~255;
+}
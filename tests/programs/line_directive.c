@@ -0,0 +1,5 @@
+# 1 "generated.c"
+#line 100 "parser.y"
+int main(void) {
+    return 7;
+}
@@ -0,0 +1,3 @@
+int main(void) {
+    return (2 + 3) * (7 - 4);
+}
@@ -0,0 +1,3 @@
+int main(void) {
+    return (7 - 2) * 4 + (3 + 1) * 9 - -(2 * 8 + 1) + 6 * 3 % 7 + (1 + 1) * 5;
+}
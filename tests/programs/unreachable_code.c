@@ -0,0 +1,5 @@
+int main(void) {
+    return (7 - 2) * 3 % 8 + -~4 / 2;
+    return 1;
+    return 2;
+}
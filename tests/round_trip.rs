@@ -0,0 +1,300 @@
+//! Property tests for printing a syntax tree as C and parsing it back.
+//!
+//! Random trees are pretty-printed, lexed, and parsed again, and the result must be the tree that
+//! was printed. This exercises the printer, the lexer, and the parser all at once: the printer has
+//! to put parentheses in exactly the right places, and the parser has to agree with it about
+//! precedence and associativity.
+//!
+//! The code generated for the trees goes through the same thing: it is printed as assembly, and
+//! reading that back must give the same lines.
+
+use ecc::ast::pretty::PrettyPrinter;
+use ecc::ast::{
+    Association, Attribute, BinaryOp, BoolKeyword, Expr, Function, Program, Statement, UnaryOp,
+    VisitorMut, visit,
+};
+use ecc::compiler::{Compiler, asm};
+use ecc::options::OptLevel;
+use ecc::span::Span;
+use ecc::token::{self, TokenKind};
+use ecc::types::Type;
+use ecc::{lexer, parser};
+use proptest::prelude::*;
+
+fn unary_op() -> impl Strategy<Value = UnaryOp> {
+    prop_oneof![
+        Just(UnaryOp::Compliment),
+        Just(UnaryOp::NegateArith),
+        Just(UnaryOp::NegateLogical),
+    ]
+}
+
+fn binary_op() -> impl Strategy<Value = BinaryOp> {
+    prop_oneof![
+        Just(BinaryOp::Plus),
+        Just(BinaryOp::Minus),
+        Just(BinaryOp::Times),
+        Just(BinaryOp::Divide),
+        Just(BinaryOp::Mod),
+    ]
+}
+
+/// Generate a type that an object can have, and the keyword it is spelled with.
+fn object_type() -> impl Strategy<Value = (Type, BoolKeyword)> {
+    prop_oneof![
+        Just((Type::Int, BoolKeyword::Bool)),
+        Just((Type::Bool, BoolKeyword::Bool)),
+        Just((Type::Bool, BoolKeyword::UnderscoreBool)),
+    ]
+}
+
+/// Generate an expression.
+///
+/// Integer literals are never negative, since C has no negative literals: `-1` is the negation of
+/// `1`, and that is how it parses. Like in [`program`], every span is left empty.
+///
+/// Every `_Generic` has a `default` association somewhere, so that something is always selected
+/// when the tree is compiled.
+fn expr() -> impl Strategy<Value = Expr> {
+    let leaf = prop_oneof![
+        (0..=i32::MAX).prop_map(|value| Expr::Integer {
+            value,
+            span: Span::default(),
+        }),
+        any::<bool>().prop_map(|value| Expr::Boolean {
+            value,
+            span: Span::default(),
+        }),
+    ];
+    leaf.prop_recursive(8, 64, 2, |inner| {
+        prop_oneof![
+            (unary_op(), inner.clone()).prop_map(|(operator, operand)| Expr::Unary {
+                operator,
+                operand: Box::new(operand),
+                span: Span::default(),
+            }),
+            (binary_op(), inner.clone(), inner.clone()).prop_map(|(operator, left, right)| {
+                Expr::Binary {
+                    operator,
+                    left: Box::new(left),
+                    right: Box::new(right),
+                    span: Span::default(),
+                }
+            }),
+            (
+                inner.clone(),
+                prop::collection::vec((prop::option::of(object_type()), inner.clone()), 0..3),
+                inner,
+                any::<prop::sample::Index>(),
+            )
+                .prop_map(|(controlling, associations, default, index)| {
+                    let mut associations: Vec<_> = associations
+                        .into_iter()
+                        .map(|(ty, expr)| {
+                            let (ty, bool_keyword) = ty.unzip();
+                            Association {
+                                ty,
+                                bool_keyword: bool_keyword.unwrap_or_default(),
+                                type_span: Span::default(),
+                                expr,
+                            }
+                        })
+                        .collect();
+                    associations.insert(
+                        index.index(associations.len() + 1),
+                        Association {
+                            ty: None,
+                            bool_keyword: BoolKeyword::default(),
+                            type_span: Span::default(),
+                            expr: default,
+                        },
+                    );
+                    Expr::Generic {
+                        controlling: Box::new(controlling),
+                        associations,
+                        span: Span::default(),
+                    }
+                }),
+        ]
+    })
+}
+
+/// Generate an identifier that is not a keyword.
+fn identifier() -> impl Strategy<Value = String> {
+    "[A-Za-z_][A-Za-z0-9_]{0,15}".prop_filter("keywords are not identifiers", |name| {
+        token::check_keyword(name) == TokenKind::LiteralIdentifier
+    })
+}
+
+/// Generate a basic `asm` statement, whose text can have characters that need escaping.
+fn asm() -> impl Strategy<Value = Statement> {
+    "[a-z]{1,6}( \\$[0-9]{1,3}, %eax)?([\n\t\"\\\\][a-z]{1,6})?".prop_map(|template| {
+        Statement::Asm {
+            template,
+            span: Span::default(),
+        }
+    })
+}
+
+/// Generate an attribute that the compiler does not support, so that it is only kept around.
+///
+/// Its arguments are a few tokens with single spaces between some of them, the way the parser
+/// keeps them.
+fn ignored_attribute() -> impl Strategy<Value = Attribute> {
+    let name = identifier().prop_filter("supported attributes are not ignored", |name| {
+        let bare = name
+            .strip_prefix("__")
+            .and_then(|name| name.strip_suffix("__"))
+            .unwrap_or(name);
+        !matches!(bare, "noreturn" | "unused" | "aligned")
+    });
+    let arguments = prop::option::of(prop_oneof![
+        "[0-9]{1,3}",
+        identifier(),
+        "\"[a-z.]{1,8}\"",
+        (identifier(), 0..10u32, 0..10u32).prop_map(|(name, a, b)| format!("{name}, {a}, {b}")),
+    ]);
+    (name, arguments).prop_map(|(name, arguments)| Attribute {
+        name,
+        arguments,
+        span: Span::default(),
+    })
+}
+
+/// Generate a program.
+///
+/// The spans in the tree cannot be known until it is printed, so every span is left empty, and
+/// only the structure is compared after parsing.
+fn program() -> impl Strategy<Value = Program> {
+    let specifiers = (any::<bool>(), any::<bool>(), any::<bool>());
+    let attributes = (
+        prop::option::of((0..8u32).prop_map(|log| 1 << log)),
+        any::<bool>(),
+        prop::collection::vec(ignored_attribute(), 0..3),
+    );
+    let return_type = prop_oneof![object_type(), Just((Type::Void, BoolKeyword::Bool))];
+    (
+        identifier(),
+        specifiers,
+        attributes,
+        return_type,
+        prop::collection::vec(asm(), 0..3),
+        prop::option::of(expr()),
+    )
+        .prop_map(
+            |(
+                name,
+                (is_static, inline, noreturn),
+                (aligned, unused, ignored_attributes),
+                (return_type, bool_keyword),
+                mut body,
+                expr,
+            )| {
+                body.push(Statement::Return(expr));
+                Program {
+                    function: Function {
+                        name,
+                        name_span: Span::default(),
+                        is_static,
+                        inline,
+                        noreturn,
+                        aligned,
+                        unused,
+                        ignored_attributes,
+                        return_type,
+                        bool_keyword,
+                        body,
+                    },
+                }
+            },
+        )
+}
+
+/// Empty every span in an expression, so that it can be compared with a generated one.
+struct ClearSpans;
+
+impl VisitorMut for ClearSpans {
+    fn visit_expr_mut(&mut self, expr: &mut Expr) {
+        match expr {
+            Expr::Integer { span, .. }
+            | Expr::Boolean { span, .. }
+            | Expr::Unary { span, .. }
+            | Expr::Binary { span, .. } => {
+                *span = Span::default();
+            }
+            Expr::Generic {
+                associations, span, ..
+            } => {
+                *span = Span::default();
+                for association in associations {
+                    association.type_span = Span::default();
+                }
+            }
+        }
+
+        visit::walk_expr_mut(self, expr);
+    }
+
+    fn visit_statement_mut(&mut self, statement: &mut Statement) {
+        if let Statement::Asm { span, .. } = statement {
+            *span = Span::default();
+        }
+
+        visit::walk_statement_mut(self, statement);
+    }
+}
+
+proptest! {
+    #[test]
+    fn printed_programs_parse_to_the_same_tree(program in program()) {
+        let source = PrettyPrinter::new().print_program(&program);
+        let tokens = lexer::tokenize(&source).expect("printed code should lex");
+        let mut reparsed = parser::parse_token_stream(tokens).expect("printed code should parse");
+        ClearSpans.visit_program_mut(&mut reparsed);
+
+        prop_assert_eq!(&reparsed.function.name, &program.function.name);
+        prop_assert_eq!(&reparsed.function.return_type, &program.function.return_type);
+        prop_assert_eq!(reparsed.function.bool_keyword, program.function.bool_keyword);
+        prop_assert_eq!(
+            reparsed.function.specifiers().collect::<Vec<_>>(),
+            program.function.specifiers().collect::<Vec<_>>()
+        );
+        prop_assert_eq!(
+            reparsed.function.attributes().collect::<Vec<_>>(),
+            program.function.attributes().collect::<Vec<_>>()
+        );
+        prop_assert_eq!(
+            reparsed.function.ignored_attributes.iter().map(ToString::to_string).collect::<Vec<_>>(),
+            program.function.ignored_attributes.iter().map(ToString::to_string).collect::<Vec<_>>()
+        );
+        prop_assert_eq!(&reparsed.function.body, &program.function.body);
+    }
+
+    #[test]
+    fn printed_expressions_parse_to_the_same_tree(expr in expr()) {
+        let source = PrettyPrinter::new().print_expr(&expr);
+        let tokens = lexer::tokenize(&source).expect("printed code should lex");
+        let mut reparsed = parser::parse_expression(tokens).expect("printed code should parse");
+        ClearSpans.visit_expr_mut(&mut reparsed);
+
+        prop_assert_eq!(reparsed, expr);
+    }
+
+    #[test]
+    fn printed_assembly_parses_to_the_same_lines(mut program in program()) {
+        // What an `asm` statement copies into the output is read back as whatever it looks like.
+        program.function.body.retain(|statement| matches!(statement, Statement::Return(_)));
+        let source = PrettyPrinter::new().print_program(&program);
+        let mut compiler = Compiler::new()
+            .with_opt_level(OptLevel::O2)
+            .with_wrapv(true)
+            .with_profiling(true)
+            .with_debug_info("test.c", &source)
+            .with_source_comments("test.c", &source)
+            .with_ub_checks("test.c", &source);
+        compiler.compile_program(program);
+
+        let reparsed = asm::parse(&compiler.get_code()).expect("printed assembly should parse");
+        prop_assert_eq!(reparsed, compiler.lines());
+    }
+}